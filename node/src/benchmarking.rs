@@ -20,18 +20,138 @@
 //!
 //! Should only be used for benchmarking as it may break in other contexts.
 
-use crate::service::FullClient;
+use crate::{
+	extrinsic::{self, ExtrinsicParams},
+	service::{self, FullClient},
+};
 
+use clap::Parser;
+use frame_benchmarking_cli::{BenchmarkCmd, PalletCmd};
 use node_template_runtime as runtime;
-use runtime::{AccountId, Balance, BalancesCall, SystemCall};
-use sc_cli::Result;
+use runtime::{
+	pallet_template::Call as TemplateCall, AccountId, Balance, BalancesCall, Block, SystemCall,
+};
+use sc_cli::{CliConfiguration, Error, Result, SharedParams, SubstrateCli};
 use sc_client_api::BlockBackend;
-use sp_core::{Encode, Pair};
 use sp_inherents::{InherentData, InherentDataProvider};
 use sp_keyring::Sr25519Keyring;
-use sp_runtime::{OpaqueExtrinsic, SaturatedConversion};
+use sp_runtime::OpaqueExtrinsic;
 
-use std::{sync::Arc, time::Duration};
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+/// The pallets whose weights `benchmark all` writes, by their name in the runtime's
+/// `define_benchmarks!`. Each is written to `<name>.rs` in the output directory.
+const WEIGHTED_PALLETS: &[&str] =
+	&["frame_system", "pallet_balances", "pallet_grandpa", "pallet_template", "pallet_timestamp"];
+
+/// The pallets among [`WEIGHTED_PALLETS`] whose benchmarks do not match the functions of their
+/// `WeightInfo` trait. Their files are written from `--functions-template`, as functions the
+/// runtime builds the weights of their calls on.
+const UNMATCHED_PALLETS: &[&str] = &["pallet_grandpa"];
+
+/// Benchmarking subcommands.
+#[derive(Debug, clap::Subcommand)]
+pub enum BenchmarkSubcommand {
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	Base(BenchmarkCmd),
+
+	/// Benchmark every pallet of the runtime and write their weight files.
+	All(BenchmarkAllCmd),
+}
+
+/// The `benchmark all` command.
+#[derive(Debug, Clone, clap::Parser)]
+pub struct BenchmarkAllCmd {
+	/// The directory to write the weight files to.
+	#[clap(long, value_name = "PATH", default_value = "runtime/src/weights")]
+	pub output: PathBuf,
+
+	/// The handlebars template of the weight files.
+	#[clap(long, value_name = "PATH", default_value = ".maintain/frame-weight-template.hbs")]
+	pub template: PathBuf,
+
+	/// The handlebars template of the weight files of the pallets whose benchmarks do not match
+	/// their `WeightInfo` trait.
+	#[clap(
+		long,
+		value_name = "PATH",
+		default_value = ".maintain/frame-weight-functions-template.hbs"
+	)]
+	pub functions_template: PathBuf,
+
+	/// How many samples to take across the range of each component.
+	#[clap(long, default_value = "50")]
+	pub steps: u32,
+
+	/// How many times to repeat each sample.
+	#[clap(long, default_value = "20")]
+	pub repeat: u32,
+
+	/// Only benchmark these pallets. Defaults to all of them.
+	#[clap(long = "pallet", value_name = "PALLET", possible_values = WEIGHTED_PALLETS)]
+	pub pallets: Vec<String>,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub shared_params: SharedParams,
+}
+
+impl BenchmarkAllCmd {
+	/// Run the command.
+	pub fn run<C: SubstrateCli>(&self, cli: &C) -> Result<()> {
+		if !cfg!(feature = "runtime-benchmarks") {
+			return Err("Runtime benchmarking wasn't enabled when building the node. \
+				You can enable it with `--features runtime-benchmarks`."
+				.into())
+		}
+
+		let runner = cli.create_runner(self)?;
+		runner.sync_run(|config| {
+			for pallet in WEIGHTED_PALLETS {
+				if !self.pallets.is_empty() && !self.pallets.iter().any(|p| p == pallet) {
+					continue
+				}
+				log::info!("Benchmarking {}", pallet);
+				let cmd = self.pallet_cmd(pallet)?;
+				let config = cli.create_configuration(&cmd, config.tokio_handle.clone())?;
+				cmd.run::<Block, service::ExecutorDispatch>(config)?;
+			}
+			Ok(())
+		})
+	}
+
+	/// The `benchmark pallet` command writing the weights of `pallet`.
+	fn pallet_cmd(&self, pallet: &str) -> Result<PalletCmd> {
+		let chain = self.shared_params.chain_id(self.shared_params.is_dev());
+		let output = self.output.join(format!("{}.rs", pallet));
+		let template = if UNMATCHED_PALLETS.contains(&pallet) {
+			&self.functions_template
+		} else {
+			&self.template
+		};
+		PalletCmd::try_parse_from([
+			"benchmark-pallet".to_string(),
+			format!("--chain={}", chain),
+			"--execution=wasm".into(),
+			"--wasm-execution=compiled".into(),
+			format!("--pallet={}", pallet),
+			"--extrinsic=*".into(),
+			format!("--steps={}", self.steps),
+			format!("--repeat={}", self.repeat),
+			"--heap-pages=4096".into(),
+			format!("--output={}", output.display()),
+			format!("--template={}", template.display()),
+		])
+		.map_err(|e| Error::Input(e.to_string()))
+	}
+}
+
+impl CliConfiguration for BenchmarkAllCmd {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+}
 
 /// Generates extrinsics for the `benchmark overhead` command.
 ///
@@ -113,6 +233,87 @@ impl frame_benchmarking_cli::ExtrinsicBuilder for TransferKeepAliveBuilder {
 	}
 }
 
+/// Generates `TemplateModule::do_something` extrinsics for the benchmarks.
+///
+/// The calls are signed by Alice, the sudo key of the dev chain, whose template calls are not rate
+/// limited, so that they fill whole blocks.
+///
+/// Note: Should only be used for benchmarking.
+pub struct DoSomethingBuilder {
+	client: Arc<FullClient>,
+	something: u32,
+}
+
+impl DoSomethingBuilder {
+	/// Creates a new [`Self`] from the given client.
+	pub fn new(client: Arc<FullClient>, something: u32) -> Self {
+		Self { client, something }
+	}
+}
+
+impl frame_benchmarking_cli::ExtrinsicBuilder for DoSomethingBuilder {
+	fn pallet(&self) -> &str {
+		"template_module"
+	}
+
+	fn extrinsic(&self) -> &str {
+		"do_something"
+	}
+
+	fn build(&self, nonce: u32) -> std::result::Result<OpaqueExtrinsic, &'static str> {
+		let acc = Sr25519Keyring::Alice.pair();
+		let extrinsic: OpaqueExtrinsic = create_benchmark_extrinsic(
+			self.client.as_ref(),
+			acc,
+			TemplateCall::do_something { something: self.something }.into(),
+			nonce,
+		)
+		.into();
+
+		Ok(extrinsic)
+	}
+}
+
+/// Generates `TemplateModule::cause_error` extrinsics for the benchmarks.
+///
+/// The calls only succeed once `Something` is set, on the dev chain they measure the `NoneValue`
+/// error path. Like [`DoSomethingBuilder`], they are signed by Alice to escape the rate limit.
+///
+/// Note: Should only be used for benchmarking.
+pub struct CauseErrorBuilder {
+	client: Arc<FullClient>,
+}
+
+impl CauseErrorBuilder {
+	/// Creates a new [`Self`] from the given client.
+	pub fn new(client: Arc<FullClient>) -> Self {
+		Self { client }
+	}
+}
+
+impl frame_benchmarking_cli::ExtrinsicBuilder for CauseErrorBuilder {
+	fn pallet(&self) -> &str {
+		"template_module"
+	}
+
+	fn extrinsic(&self) -> &str {
+		"cause_error"
+	}
+
+	fn build(&self, nonce: u32) -> std::result::Result<OpaqueExtrinsic, &'static str> {
+		let acc = Sr25519Keyring::Alice.pair();
+		let extrinsic: OpaqueExtrinsic = create_benchmark_extrinsic(
+			self.client.as_ref(),
+			acc,
+			TemplateCall::cause_error {}.into(),
+			nonce,
+		)
+		.into();
+
+		Ok(extrinsic)
+	}
+}
+
 /// Create a transaction using the given `call`.
 ///
 /// Note: Should only be used for benchmarking.
@@ -122,50 +323,29 @@ pub fn create_benchmark_extrinsic(
 	call: runtime::Call,
 	nonce: u32,
 ) -> runtime::UncheckedExtrinsic {
+	extrinsic::create_signed(&sender, call, &benchmark_extrinsic_params(client, nonce))
+}
+
+/// The parameters of an extrinsic with the given `nonce`, mortal from the best block of `client`.
+///
+/// The template call index is the nonce as well: the benchmarks sign every extrinsic of an account
+/// that made no other calls, so both count the same extrinsics.
+///
+/// Note: Should only be used for benchmarking.
+pub fn benchmark_extrinsic_params(client: &FullClient, nonce: u32) -> ExtrinsicParams {
 	let genesis_hash = client.block_hash(0).ok().flatten().expect("Genesis block exists; qed");
 	let best_hash = client.chain_info().best_hash;
 	let best_block = client.chain_info().best_number;
 
-	let period = runtime::BlockHashCount::get()
-		.checked_next_power_of_two()
-		.map(|c| c / 2)
-		.unwrap_or(2) as u64;
-	let extra: runtime::SignedExtra = (
-		frame_system::CheckNonZeroSender::<runtime::Runtime>::new(),
-		frame_system::CheckSpecVersion::<runtime::Runtime>::new(),
-		frame_system::CheckTxVersion::<runtime::Runtime>::new(),
-		frame_system::CheckGenesis::<runtime::Runtime>::new(),
-		frame_system::CheckEra::<runtime::Runtime>::from(sp_runtime::generic::Era::mortal(
-			period,
-			best_block.saturated_into(),
-		)),
-		frame_system::CheckNonce::<runtime::Runtime>::from(nonce),
-		frame_system::CheckWeight::<runtime::Runtime>::new(),
-		pallet_transaction_payment::ChargeTransactionPayment::<runtime::Runtime>::from(0),
-	);
-
-	let raw_payload = runtime::SignedPayload::from_raw(
-		call.clone(),
-		extra.clone(),
-		(
-			(),
-			runtime::VERSION.spec_version,
-			runtime::VERSION.transaction_version,
-			genesis_hash,
-			best_hash,
-			(),
-			(),
-			(),
-		),
-	);
-	let signature = raw_payload.using_encoded(|e| sender.sign(e));
-
-	runtime::UncheckedExtrinsic::new_signed(
-		call.clone(),
-		sp_runtime::AccountId32::from(sender.public()).into(),
-		runtime::Signature::Sr25519(signature.clone()),
-		extra.clone(),
+	let mut params = ExtrinsicParams::immortal(
+		genesis_hash,
+		runtime::VERSION.spec_version,
+		runtime::VERSION.transaction_version,
+		nonce,
 	)
+	.mortal(extrinsic::max_mortal_period(), best_block, best_hash);
+	params.template_index = nonce;
+	params
 }
 
 /// Generates inherent data for the `benchmark overhead` command.
@@ -1,18 +1,124 @@
+//! The chain specifications built into the node.
+
 use node_template_runtime::{
-	AccountId, AuraConfig, BalancesConfig, GenesisConfig, GrandpaConfig, Signature, SudoConfig,
-	SystemConfig, WASM_BINARY,
+	opaque::Block, AccountId, AuraConfig, Balance, BalancesConfig, BlockNumber, GenesisConfig,
+	GrandpaConfig, Hash, SS58Prefix, Signature, SudoConfig, SystemConfig, TemplateModuleConfig,
+	WASM_BINARY,
 };
-use sc_service::ChainType;
+use sc_chain_spec::ChainSpecExtension;
+use sc_service::{ChainType, Properties};
+use serde::{Deserialize, Serialize};
+use sp_blockchain::HeaderBackend;
 use sp_consensus_aura::sr25519::AuthorityId as AuraId;
 use sp_core::{sr25519, Pair, Public};
-use sp_finality_grandpa::AuthorityId as GrandpaId;
+use sp_finality_grandpa::{AuthorityId as GrandpaId, AuthorityWeight, SetId};
 use sp_runtime::traits::{IdentifyAccount, Verify};
 
-// The URL for the telemetry server.
-// const STAGING_TELEMETRY_URL: &str = "wss://telemetry.polkadot.io/submit/";
+/// The symbol of the native token, as displayed by wallets.
+pub const TOKEN_SYMBOL: &str = "UNIT";
+
+/// The number of decimals of the native token, as displayed by wallets.
+pub const TOKEN_DECIMALS: u32 = 12;
+
+/// Node `ChainSpec` extensions.
+///
+/// Additional parameters for some Substrate core modules,
+/// customizable from the chain spec.
+#[derive(Default, Clone, Serialize, Deserialize, ChainSpecExtension)]
+#[serde(rename_all = "camelCase")]
+pub struct Extensions {
+	/// Block numbers with known hashes.
+	pub fork_blocks: sc_client_api::ForkBlocks<Block>,
+	/// Known bad block hashes.
+	pub bad_blocks: sc_client_api::BadBlocks<Block>,
+	/// Authority set hard forks, applied by GRANDPA block import and warp sync.
+	#[serde(default)]
+	pub grandpa_hard_forks: Vec<GrandpaHardFork>,
+	/// A finalized block light clients may start syncing from. Not to be confused with the
+	/// `lightSyncState` of `sc_sync_state_rpc`, which has another schema.
+	pub light_sync_checkpoint: Option<LightSyncCheckpoint>,
+}
+
+impl Extensions {
+	/// Try to get the extension from the given `ChainSpec`.
+	pub fn try_get(chain_spec: &dyn sc_service::ChainSpec) -> Option<&Self> {
+		sc_chain_spec::get_extension(chain_spec.extensions())
+	}
+}
+
+/// A forced change of the GRANDPA authority set at a given block.
+///
+/// This is the chain spec representation of [`sc_finality_grandpa::AuthoritySetHardFork`] and is
+/// meant to recover a network whose GRANDPA voters have stalled.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GrandpaHardFork {
+	/// The id of the new authority set.
+	pub set_id: SetId,
+	/// The hash of the block at which the new set is enacted.
+	pub block_hash: Hash,
+	/// The number of the block at which the new set is enacted.
+	pub block_number: BlockNumber,
+	/// The authorities of the new set.
+	pub authorities: Vec<(GrandpaId, AuthorityWeight)>,
+	/// The last block finalized before the hard fork. When given, the change is forced without
+	/// waiting for `block_number` to be finalized.
+	#[serde(default)]
+	pub last_finalized: Option<BlockNumber>,
+}
+
+impl From<GrandpaHardFork> for sc_finality_grandpa::AuthoritySetHardFork<Block> {
+	fn from(fork: GrandpaHardFork) -> Self {
+		sc_finality_grandpa::AuthoritySetHardFork {
+			set_id: fork.set_id,
+			block: (fork.block_hash, fork.block_number),
+			authorities: fork.authorities,
+			last_finalized: fork.last_finalized,
+		}
+	}
+}
+
+/// A finalized block, together with the GRANDPA authority set that finalizes its descendants.
+///
+/// Light clients can start syncing from this block instead of genesis. Full nodes refuse to import
+/// another block at its height, and to start when their database disagrees with it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LightSyncCheckpoint {
+	/// The hash of the checkpoint block.
+	pub block_hash: Hash,
+	/// The number of the checkpoint block.
+	pub block_number: BlockNumber,
+	/// The id of the GRANDPA authority set at the checkpoint block.
+	pub grandpa_set_id: SetId,
+	/// The GRANDPA authorities at the checkpoint block.
+	pub grandpa_authorities: Vec<(GrandpaId, AuthorityWeight)>,
+}
+
+impl LightSyncCheckpoint {
+	/// Checks that the local chain, if it already contains the checkpoint block, agrees with it.
+	pub fn verify<C: HeaderBackend<Block>>(&self, client: &C) -> Result<(), String> {
+		match client.hash(self.block_number).map_err(|e| e.to_string())? {
+			Some(hash) if hash != self.block_hash => Err(format!(
+				"Light sync checkpoint mismatch at block #{}: expected {}, found {} in the database",
+				self.block_number, self.block_hash, hash,
+			)),
+			_ => Ok(()),
+		}
+	}
+}
+
+/// Returns the GRANDPA authority set hard forks declared in the given chain spec.
+pub fn grandpa_hard_forks(
+	chain_spec: &dyn sc_service::ChainSpec,
+) -> Vec<sc_finality_grandpa::AuthoritySetHardFork<Block>> {
+	Extensions::try_get(chain_spec)
+		.map(|extensions| extensions.grandpa_hard_forks.iter().cloned().map(Into::into).collect())
+		.unwrap_or_default()
+}
 
 /// Specialized `ChainSpec`. This is a specialization of the general Substrate ChainSpec type.
-pub type ChainSpec = sc_service::GenericChainSpec<GenesisConfig>;
+pub type ChainSpec = sc_service::GenericChainSpec<GenesisConfig, Extensions>;
 
 /// Generate a crypto pair from seed.
 pub fn get_from_seed<TPublic: Public>(seed: &str) -> <TPublic::Pair as Pair>::Public {
@@ -36,6 +142,16 @@ pub fn authority_keys_from_seed(s: &str) -> (AuraId, GrandpaId) {
 	(get_from_seed::<AuraId>(s), get_from_seed::<GrandpaId>(s))
 }
 
+/// The chain properties used by wallets to display balances and addresses.
+pub fn chain_properties() -> Properties {
+	let mut properties = Properties::new();
+	properties.insert("tokenSymbol".into(), TOKEN_SYMBOL.into());
+	properties.insert("tokenDecimals".into(), TOKEN_DECIMALS.into());
+	properties.insert("ss58Format".into(), SS58Prefix::get().into());
+	properties
+}
+
+/// A single-authority development chain, with Alice as authority and sudo.
 pub fn development_config() -> Result<ChainSpec, String> {
 	let wasm_binary = WASM_BINARY.ok_or_else(|| "Development wasm not available".to_string())?;
 
@@ -68,14 +184,16 @@ pub fn development_config() -> Result<ChainSpec, String> {
 		None,
 		// Protocol ID
 		None,
+		// Fork ID
 		None,
 		// Properties
-		None,
+		Some(chain_properties()),
 		// Extensions
-		None,
+		Default::default(),
 	))
 }
 
+/// A local testnet, with Alice and Bob as authorities.
 pub fn local_testnet_config() -> Result<ChainSpec, String> {
 	let wasm_binary = WASM_BINARY.ok_or_else(|| "Development wasm not available".to_string())?;
 
@@ -116,14 +234,26 @@ pub fn local_testnet_config() -> Result<ChainSpec, String> {
 		None,
 		// Protocol ID
 		None,
-		// Properties
-		None,
+		// Fork ID
 		None,
+		// Properties
+		Some(chain_properties()),
 		// Extensions
-		None,
+		Default::default(),
 	))
 }
 
+/// The staging testnet.
+///
+/// Its authorities, sudo key, bootnodes and telemetry endpoint are not decided yet, so it cannot be
+/// loaded until they are filled in here.
+pub fn staging_testnet_config() -> Result<ChainSpec, String> {
+	Err("The staging testnet has no authority keys, sudo key, bootnodes or telemetry endpoint yet. \
+		Fill them in `staging_testnet_config`, or launch a network from a spec of your own, \
+		exported with `build-spec --chain local` and edited."
+		.into())
+}
+
 /// Configure initial storage state for FRAME modules.
 fn testnet_genesis(
 	wasm_binary: &[u8],
@@ -131,16 +261,31 @@ fn testnet_genesis(
 	root_key: AccountId,
 	endowed_accounts: Vec<AccountId>,
 	_enable_println: bool,
+) -> GenesisConfig {
+	genesis_config(
+		wasm_binary,
+		initial_authorities,
+		root_key,
+		// Configure endowed accounts with initial balance of 1 << 60.
+		endowed_accounts.into_iter().map(|k| (k, 1 << 60)).collect(),
+		None,
+	)
+}
+
+/// Configure initial storage state for FRAME modules with explicit balances.
+pub fn genesis_config(
+	wasm_binary: &[u8],
+	initial_authorities: Vec<(AuraId, GrandpaId)>,
+	root_key: AccountId,
+	balances: Vec<(AccountId, Balance)>,
+	something: Option<u32>,
 ) -> GenesisConfig {
 	GenesisConfig {
 		system: SystemConfig {
 			// Add Wasm runtime to storage.
 			code: wasm_binary.to_vec(),
 		},
-		balances: BalancesConfig {
-			// Configure endowed accounts with initial balance of 1 << 60.
-			balances: endowed_accounts.iter().cloned().map(|k| (k, 1 << 60)).collect(),
-		},
+		balances: BalancesConfig { balances },
 		aura: AuraConfig {
 			authorities: initial_authorities.iter().map(|x| (x.0.clone())).collect(),
 		},
@@ -152,5 +297,6 @@ fn testnet_genesis(
 			key: Some(root_key),
 		},
 		transaction_payment: Default::default(),
+		template_module: TemplateModuleConfig { something },
 	}
 }
@@ -0,0 +1,71 @@
+//! Enforcement of the light sync checkpoint of the chain spec in the block import pipeline.
+
+use crate::chain_spec::LightSyncCheckpoint;
+use node_template_runtime::{opaque::Block, BlockNumber, Hash};
+use sc_consensus::{BlockCheckParams, BlockImport, BlockImportParams, ImportResult};
+use sp_blockchain::well_known_cache_keys;
+use sp_runtime::traits::Header as _;
+use std::collections::HashMap;
+
+/// Wraps a block import, rejecting the blocks at the height of the checkpoint whose hash is not
+/// the one of the checkpoint, so that the node never follows a chain the checkpoint rules out.
+#[derive(Clone)]
+pub struct CheckpointBlockImport<I> {
+	inner: I,
+	checkpoint: Option<LightSyncCheckpoint>,
+}
+
+impl<I> CheckpointBlockImport<I> {
+	/// Wraps `inner`, enforcing `checkpoint` if any.
+	pub fn new(inner: I, checkpoint: Option<LightSyncCheckpoint>) -> Self {
+		Self { inner, checkpoint }
+	}
+
+	/// Whether the block `hash` at height `number` contradicts the checkpoint, logging its rejection.
+	fn contradicts(&self, number: BlockNumber, hash: &Hash) -> bool {
+		let checkpoint = match &self.checkpoint {
+			Some(checkpoint) => checkpoint,
+			None => return false,
+		};
+		if checkpoint.block_number != number || checkpoint.block_hash == *hash {
+			return false
+		}
+		log::warn!(
+			"⛔ Rejecting block #{} ({}): the light sync checkpoint expects {}",
+			number,
+			hash,
+			checkpoint.block_hash,
+		);
+		true
+	}
+}
+
+#[async_trait::async_trait]
+impl<I> BlockImport<Block> for CheckpointBlockImport<I>
+where
+	I: BlockImport<Block> + Send,
+{
+	type Error = I::Error;
+	type Transaction = I::Transaction;
+
+	async fn check_block(
+		&mut self,
+		block: BlockCheckParams<Block>,
+	) -> Result<ImportResult, Self::Error> {
+		if self.contradicts(block.number, &block.hash) {
+			return Ok(ImportResult::KnownBad)
+		}
+		self.inner.check_block(block).await
+	}
+
+	async fn import_block(
+		&mut self,
+		block: BlockImportParams<Block, Self::Transaction>,
+		cache: HashMap<well_known_cache_keys::Id, Vec<u8>>,
+	) -> Result<ImportResult, Self::Error> {
+		if self.contradicts(*block.header.number(), &block.post_hash()) {
+			return Ok(ImportResult::KnownBad)
+		}
+		self.inner.import_block(block, cache).await
+	}
+}
@@ -1,23 +1,80 @@
-use sc_cli::RunCmd;
+//! The command line of the node.
 
+use std::path::PathBuf;
+
+/// The command line of the node: a subcommand, or the options of the node itself.
 #[derive(Debug, clap::Parser)]
 pub struct Cli {
+	#[allow(missing_docs)]
 	#[clap(subcommand)]
 	pub subcommand: Option<Subcommand>,
 
+	#[allow(missing_docs)]
 	#[clap(flatten)]
 	pub run: RunCmd,
 }
 
+impl Cli {
+	/// The keystore options of the node and of the subcommand, if it opens a keystore.
+	pub fn keystore_params_mut(&mut self) -> Vec<&mut sc_cli::KeystoreParams> {
+		let mut params = vec![&mut self.run.base.keystore_params];
+		match &mut self.subcommand {
+			Some(Subcommand::Key(cmd)) => params.extend(cmd.keystore_params_mut()),
+			Some(Subcommand::SessionKeys(cmd)) => params.extend(cmd.keystore_params_mut()),
+			Some(Subcommand::Tx(cmd)) => params.push(&mut cmd.keystore_params),
+			_ => {},
+		}
+		params
+	}
+}
+
+/// The options of the node, on top of Substrate's.
+#[derive(Debug, clap::Parser)]
+pub struct RunCmd {
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub base: sc_cli::RunCmd,
+
+	/// Index the extrinsics and events of the best chain into the SQLite database at this path,
+	/// and serve the `indexer_*` RPC methods. A new database starts at the current best block,
+	/// earlier blocks are not indexed.
+	#[clap(long, value_name = "PATH")]
+	pub indexer_db: Option<PathBuf>,
+
+	/// Start an authority of a live chain even if its keystore has no password or holds keys that
+	/// are not password protected.
+	#[clap(long)]
+	pub allow_unencrypted_keystore: bool,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub health: crate::health::HealthParams,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub hardware: crate::hardware::HardwareParams,
+}
+
+/// The subcommands of the node.
 #[derive(Debug, clap::Subcommand)]
 pub enum Subcommand {
 	/// Key management cli utilities
 	#[clap(subcommand)]
-	Key(sc_cli::KeySubcommand),
+	Key(crate::keystore::KeySubcommand),
+
+	/// Rotate, decode and verify the Aura and GRANDPA session keys.
+	#[clap(subcommand)]
+	SessionKeys(crate::session_keys::SessionKeysSubcommand),
 
 	/// Build a chain specification.
 	BuildSpec(sc_cli::BuildSpecCmd),
 
+	/// Generate plain and raw chain specifications from a network description file.
+	GenerateSpec(crate::generate_spec::GenerateSpecCmd),
+
+	/// Generate keys, node base paths and a chain spec for a local multi-validator network.
+	Testnet(crate::testnet::TestnetCmd),
+
 	/// Validate blocks.
 	CheckBlock(sc_cli::CheckBlockCmd),
 
@@ -27,6 +84,9 @@ pub enum Subcommand {
 	/// Export the state of a given block into a chain spec.
 	ExportState(sc_cli::ExportStateCmd),
 
+	/// Build a dev chain spec from the state exported by `export-state`.
+	ForkOff(crate::fork_off::ForkOffCmd),
+
 	/// Import blocks.
 	ImportBlocks(sc_cli::ImportBlocksCmd),
 
@@ -36,9 +96,25 @@ pub enum Subcommand {
 	/// Revert the chain to a previous state.
 	Revert(sc_cli::RevertCmd),
 
+	/// Read storage items from the local database at a given block.
+	Storage(crate::storage::StorageCmd),
+
+	/// Check runtime upgrades against snapshots of the state of the local database.
+	#[clap(subcommand)]
+	Snapshot(crate::snapshot::SnapshotSubcommand),
+
+	/// Submit a new runtime to a running chain through the sudo key.
+	UpgradeRuntime(crate::upgrade_runtime::UpgradeRuntimeCmd),
+
+	/// Build, sign and submit an extrinsic for a common call.
+	Tx(crate::tx::TxCmd),
+
+	/// Submit signed transactions at a target rate and report how fast they are included.
+	Load(crate::load::LoadCmd),
+
 	/// Sub-commands concerned with benchmarking.
 	#[clap(subcommand)]
-	Benchmark(frame_benchmarking_cli::BenchmarkCmd),
+	Benchmark(crate::benchmarking::BenchmarkSubcommand),
 
 	/// Try some command against runtime state.
 	#[cfg(feature = "try-runtime")]
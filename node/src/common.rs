@@ -0,0 +1,47 @@
+//! Helpers shared by the subcommands and the RPC servers of the node.
+
+use node_template_runtime::{AccountId, Event, Hash};
+use sp_core::{
+	crypto::{ByteArray, Ss58Codec},
+	twox_128, Pair,
+};
+
+/// The events deposited in a block.
+pub type EventRecords = Vec<frame_system::EventRecord<Event, Hash>>;
+
+/// The final key of a `StorageValue`, or the prefix of all keys of a `StorageMap`.
+pub fn storage_prefix(pallet: &str, item: &str) -> Vec<u8> {
+	[twox_128(pallet.as_bytes()), twox_128(item.as_bytes())].concat()
+}
+
+/// Reads a public key from hex or SS58, falling back to deriving it from a secret URI.
+pub fn parse_public<P: Pair>(key: &str) -> Result<P::Public, String>
+where
+	P::Public: Ss58Codec,
+{
+	if let Some(hex) = key.strip_prefix("0x") {
+		let bytes = hex::decode(hex).map_err(|e| format!("invalid hex `{}`: {}", key, e))?;
+		return P::Public::from_slice(&bytes).map_err(|_| {
+			format!("`{}` is not a {}-byte public key", key, <P::Public as ByteArray>::LEN)
+		})
+	}
+	if let Ok(public) = P::Public::from_ss58check(key) {
+		return Ok(public)
+	}
+	P::from_string(key, None).map(|pair| pair.public()).map_err(|e| {
+		format!("`{}` is neither a public key nor a valid secret URI: {:?}", key, e)
+	})
+}
+
+/// Reads an account from its SS58 address or hex public key. Unlike [`parse_public`], secret URIs
+/// are refused: this is meant for inputs received over RPC, where seeds must not travel and
+/// deriving keys would cost a key stretching run per request.
+pub fn parse_account_id(account: &str) -> Result<AccountId, String> {
+	if let Some(hex) = account.strip_prefix("0x") {
+		let bytes = hex::decode(hex).map_err(|e| format!("invalid hex `{}`: {}", account, e))?;
+		return AccountId::from_slice(&bytes)
+			.map_err(|_| format!("`{}` is not a {}-byte public key", account, AccountId::LEN))
+	}
+	AccountId::from_ss58check(account)
+		.map_err(|e| format!("`{}` is not an SS58 address: {:?}", account, e))
+}
@@ -0,0 +1,136 @@
+//! Construction and signing of extrinsics for the node's client-side subcommands.
+
+use node_template_runtime as runtime;
+use runtime::{Balance, BlockNumber, Hash, Index};
+use sp_core::{sr25519, Encode, Pair};
+use sp_runtime::generic::Era;
+
+/// Everything, besides the call and the signer, that goes into a signed extrinsic.
+#[derive(Debug, Clone)]
+pub struct ExtrinsicParams {
+	/// The hash of the genesis block, checked by `CheckGenesis`.
+	pub genesis_hash: Hash,
+	/// The runtime spec version, checked by `CheckSpecVersion`.
+	pub spec_version: u32,
+	/// The runtime transaction version, checked by `CheckTxVersion`.
+	pub transaction_version: u32,
+	/// The nonce of the signer, checked by `CheckNonce`.
+	pub nonce: Index,
+	/// The validity period of the extrinsic, checked by `CheckEra`.
+	pub era: Era,
+	/// The hash of the block the era starts at, or the genesis hash for immortal extrinsics.
+	pub era_block_hash: Hash,
+	/// The tip paid to the block author, on top of the fees.
+	pub tip: Balance,
+	/// The index of the call among the template pallet calls of the signer, checked by
+	/// `CheckTemplateCall`. Ignored for calls to other pallets.
+	pub template_index: u32,
+}
+
+impl ExtrinsicParams {
+	/// Parameters for an immortal extrinsic without tip, and the first template call index.
+	pub fn immortal(
+		genesis_hash: Hash,
+		spec_version: u32,
+		transaction_version: u32,
+		nonce: Index,
+	) -> Self {
+		Self {
+			genesis_hash,
+			spec_version,
+			transaction_version,
+			nonce,
+			era: Era::Immortal,
+			era_block_hash: genesis_hash,
+			tip: 0,
+			template_index: 0,
+		}
+	}
+
+	/// Makes the extrinsic mortal, valid for `period` blocks starting at the given block.
+	pub fn mortal(mut self, period: u64, block_number: BlockNumber, block_hash: Hash) -> Self {
+		self.era = Era::mortal(period, block_number.into());
+		self.era_block_hash = block_hash;
+		self
+	}
+}
+
+/// The longest mortality period accepted by the runtime, given that it only remembers
+/// `BlockHashCount` block hashes.
+pub fn max_mortal_period() -> u64 {
+	runtime::BlockHashCount::get()
+		.checked_next_power_of_two()
+		.map(|c| c / 2)
+		.unwrap_or(2) as u64
+}
+
+/// The signed extensions of the runtime, matching the given parameters.
+pub fn signed_extra(params: &ExtrinsicParams) -> runtime::SignedExtra {
+	(
+		frame_system::CheckNonZeroSender::<runtime::Runtime>::new(),
+		frame_system::CheckSpecVersion::<runtime::Runtime>::new(),
+		frame_system::CheckTxVersion::<runtime::Runtime>::new(),
+		frame_system::CheckGenesis::<runtime::Runtime>::new(),
+		frame_system::CheckEra::<runtime::Runtime>::from(params.era),
+		frame_system::CheckNonce::<runtime::Runtime>::from(params.nonce),
+		frame_system::CheckWeight::<runtime::Runtime>::new(),
+		runtime::pallet_template::ChargeUnlessFeeless::<runtime::Runtime, _>::from(
+			pallet_transaction_payment::ChargeTransactionPayment::<runtime::Runtime>::from(
+				params.tip,
+			),
+			params.template_index,
+		),
+	)
+}
+
+/// The payload the signer of `call` signs, for the given parameters.
+pub fn signed_payload(call: runtime::Call, params: &ExtrinsicParams) -> runtime::SignedPayload {
+	runtime::SignedPayload::from_raw(
+		call,
+		signed_extra(params),
+		(
+			(),
+			params.spec_version,
+			params.transaction_version,
+			params.genesis_hash,
+			params.era_block_hash,
+			(),
+			(),
+			(),
+		),
+	)
+}
+
+/// The bytes the signer of `payload` signs: the payload itself, or its hash if it is longer than
+/// 256 bytes.
+pub fn message_to_sign(payload: &runtime::SignedPayload) -> Vec<u8> {
+	payload.using_encoded(|e| e.to_vec())
+}
+
+/// Create a transaction signed by `sender` using the given `call`.
+pub fn create_signed(
+	sender: &sr25519::Pair,
+	call: runtime::Call,
+	params: &ExtrinsicParams,
+) -> runtime::UncheckedExtrinsic {
+	let raw_payload = signed_payload(call, params);
+	let signature = raw_payload.using_encoded(|e| sender.sign(e));
+
+	assemble(sender.public(), raw_payload, signature)
+}
+
+/// Assemble a transaction from its payload and the signature of `signer` over it.
+pub fn assemble(
+	signer: sr25519::Public,
+	payload: runtime::SignedPayload,
+	signature: sr25519::Signature,
+) -> runtime::UncheckedExtrinsic {
+	let (call, extra, _) = payload.deconstruct();
+
+	runtime::UncheckedExtrinsic::new_signed(
+		call,
+		sp_runtime::AccountId32::from(signer).into(),
+		runtime::Signature::Sr25519(signature),
+		extra,
+	)
+}
@@ -0,0 +1,229 @@
+//! The `fork-off` subcommand: turn the state exported by `export-state` into a new dev chain.
+//!
+//! The exported raw storage is kept as is, except for:
+//!
+//! - the Aura and GRANDPA authorities, replaced by the given dev keys,
+//! - the sudo key, replaced by the given account (endowed if it has no funds),
+//! - the runtime `:code`, optionally replaced by a new wasm blob,
+//! - the block, consensus and finality bookkeeping of the exported chain, which is removed so the
+//!   new genesis starts from a clean slate.
+
+use crate::common::{parse_public, storage_prefix};
+use codec::{Decode, Encode};
+use node_template_runtime::{AccountId, Balance, Index, Runtime};
+use sc_cli::{Error, Result};
+use serde_json::Value;
+use sp_consensus_aura::sr25519::AuthorityId as AuraId;
+use sp_core::{blake2_128, ed25519, sr25519};
+use sp_finality_grandpa::{AuthorityId as GrandpaId, AuthorityWeight};
+use std::{fs, path::PathBuf};
+
+/// The storage key of the runtime wasm blob.
+const CODE_KEY: &[u8] = b":code";
+
+/// The storage key of the versioned GRANDPA authority list.
+const GRANDPA_AUTHORITIES_KEY: &[u8] = b":grandpa_authorities";
+
+/// The version of the encoding of the GRANDPA authority list.
+const GRANDPA_AUTHORITIES_VERSION: u8 = 1;
+
+/// Storage items tied to the exported chain's history, consensus or finality, removed from the
+/// forked genesis. Maps are removed with all their entries.
+const STRIPPED_ITEMS: &[(&str, &str)] = &[
+	("System", "BlockHash"),
+	("System", "Number"),
+	("System", "ParentHash"),
+	("System", "Digest"),
+	("System", "Events"),
+	("System", "EventCount"),
+	("System", "EventTopics"),
+	("System", "ExtrinsicCount"),
+	("System", "BlockWeight"),
+	("System", "AllExtrinsicsLen"),
+	("System", "ExecutionPhase"),
+	("Timestamp", "Now"),
+	("Timestamp", "DidUpdate"),
+	("Aura", "CurrentSlot"),
+	("Grandpa", "State"),
+	("Grandpa", "PendingChange"),
+	("Grandpa", "NextForced"),
+	("Grandpa", "Stalled"),
+	("Grandpa", "CurrentSetId"),
+	("Grandpa", "SetIdSession"),
+];
+
+/// The balance given to the new sudo account when it has none on the exported chain.
+const SUDO_ENDOWMENT: Balance = 1 << 60;
+
+/// The chain spec keys describing blocks of the source chain, dropped from the forked spec.
+const FORK_SPECIFIC_KEYS: &[&str] = &[
+	"forkBlocks",
+	"badBlocks",
+	"grandpaHardForks",
+	"lightSyncCheckpoint",
+	"lightSyncState",
+	"codeSubstitutes",
+];
+
+/// The `fork-off` command used to build a dev chain spec from an exported chain state.
+#[derive(Debug, Clone, clap::Parser)]
+pub struct ForkOffCmd {
+	/// The chain spec written by `export-state`.
+	#[clap(value_name = "EXPORTED_STATE")]
+	pub input: PathBuf,
+
+	/// Where to write the forked chain spec. Printed to stdout if not given.
+	#[clap(long, short = 'o')]
+	pub output: Option<PathBuf>,
+
+	/// The new authorities, as secret URIs or public keys. Defaults to `//Alice`.
+	#[clap(long = "authority", value_name = "KEY")]
+	pub authorities: Vec<String>,
+
+	/// The new sudo account, as a secret URI or public key.
+	#[clap(long, value_name = "KEY", default_value = "//Alice")]
+	pub sudo: String,
+
+	/// A wasm runtime to replace the exported chain's code with.
+	#[clap(long, value_name = "WASM")]
+	pub code: Option<PathBuf>,
+
+	/// Name of the forked chain.
+	#[clap(long, default_value = "Forked Development")]
+	pub name: String,
+
+	/// Identifier of the forked chain.
+	#[clap(long, default_value = "fork")]
+	pub id: String,
+}
+
+impl ForkOffCmd {
+	/// Run the command.
+	pub fn run(&self) -> Result<()> {
+		let content = fs::read_to_string(&self.input)?;
+		let mut spec: Value = serde_json::from_str(&content).map_err(|e| {
+			Error::Input(format!("Failed to parse {}: {}", self.input.display(), e))
+		})?;
+
+		{
+			let top = spec
+				.pointer_mut("/genesis/raw/top")
+				.and_then(Value::as_object_mut)
+				.ok_or("The input is not a raw chain spec as written by `export-state`")?;
+			let mut storage = Storage(top);
+
+			for (pallet, item) in STRIPPED_ITEMS {
+				storage.remove_prefix(&storage_prefix(pallet, item));
+			}
+
+			let authorities = if self.authorities.is_empty() {
+				vec!["//Alice".to_string()]
+			} else {
+				self.authorities.clone()
+			};
+			let mut aura = Vec::with_capacity(authorities.len());
+			let mut grandpa = Vec::with_capacity(authorities.len());
+			for key in &authorities {
+				aura.push(AuraId::from(parse_public::<sr25519::Pair>(key).map_err(Error::Input)?));
+				grandpa.push((
+					GrandpaId::from(parse_public::<ed25519::Pair>(key).map_err(Error::Input)?),
+					1 as AuthorityWeight,
+				));
+			}
+			storage.insert(&storage_prefix("Aura", "Authorities"), &aura.encode());
+			storage.insert(
+				GRANDPA_AUTHORITIES_KEY,
+				&(GRANDPA_AUTHORITIES_VERSION, grandpa).encode(),
+			);
+
+			let sudo: AccountId =
+				parse_public::<sr25519::Pair>(&self.sudo).map_err(Error::Input)?.into();
+			storage.insert(&storage_prefix("Sudo", "Key"), &sudo.encode());
+			endow(&mut storage, &sudo)?;
+
+			if let Some(code) = &self.code {
+				storage.insert(CODE_KEY, &fs::read(code)?);
+			}
+		}
+
+		spec["name"] = self.name.clone().into();
+		spec["id"] = self.id.clone().into();
+		spec["chainType"] = "Development".into();
+		spec["bootNodes"] = Value::Array(Vec::new());
+		spec["telemetryEndpoints"] = Value::Null;
+		spec["protocolId"] = Value::Null;
+		// The fork points, checkpoints and code substitutes of the source chain refer to blocks the
+		// fresh genesis does not have.
+		if let Some(spec) = spec.as_object_mut() {
+			for key in FORK_SPECIFIC_KEYS {
+				spec.remove(*key);
+			}
+		}
+
+		let json = serde_json::to_string_pretty(&spec).map_err(|e| Error::Input(e.to_string()))?;
+		match &self.output {
+			Some(path) => fs::write(path, json)?,
+			None => println!("{}", json),
+		}
+
+		Ok(())
+	}
+}
+
+/// Gives `who` a free balance, unless it already has an account, keeping the total issuance
+/// consistent.
+fn endow(storage: &mut Storage, who: &AccountId) -> Result<()> {
+	type AccountData = <Runtime as frame_system::Config>::AccountData;
+
+	let account_key =
+		[storage_prefix("System", "Account"), blake2_128_concat(&who.encode())].concat();
+	if storage.get(&account_key).is_some() {
+		return Ok(())
+	}
+
+	let issuance_key = storage_prefix("Balances", "TotalIssuance");
+	let issuance: Balance = match storage.get(&issuance_key) {
+		Some(encoded) => Balance::decode(&mut &encoded[..])
+			.map_err(|e| Error::Input(format!("Invalid total issuance: {}", e)))?,
+		None => 0,
+	};
+
+	let account = frame_system::AccountInfo::<Index, AccountData> {
+		providers: 1,
+		data: AccountData { free: SUDO_ENDOWMENT, ..Default::default() },
+		..Default::default()
+	};
+	storage.insert(&account_key, &account.encode());
+	storage.insert(&issuance_key, &issuance.saturating_add(SUDO_ENDOWMENT).encode());
+
+	Ok(())
+}
+
+/// The raw top-level storage of a chain spec, keyed and valued by `0x`-prefixed hex strings.
+struct Storage<'a>(&'a mut serde_json::Map<String, Value>);
+
+impl Storage<'_> {
+	fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+		self.0
+			.get(&to_hex(key))
+			.and_then(Value::as_str)
+			.and_then(|value| hex::decode(value.trim_start_matches("0x")).ok())
+	}
+
+	fn insert(&mut self, key: &[u8], value: &[u8]) {
+		self.0.insert(to_hex(key), to_hex(value).into());
+	}
+
+	fn remove_prefix(&mut self, prefix: &[u8]) {
+		let prefix = to_hex(prefix);
+		self.0.retain(|key, _| !key.starts_with(&prefix));
+	}
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+	format!("0x{}", hex::encode(bytes))
+}
+
+fn blake2_128_concat(data: &[u8]) -> Vec<u8> {
+	[&blake2_128(data)[..], data].concat()
+}
@@ -0,0 +1,365 @@
+//! The `generate-spec` subcommand: build a chain spec from a network description file.
+//!
+//! The network is described in TOML (or JSON, chosen by the `.json` extension), for example:
+//!
+//! ```toml
+//! name = "My Network"
+//! id = "my_network"
+//! chain_type = "Live"
+//! protocol_id = "mynet"
+//! boot_nodes = ["/dns/boot.example.com/tcp/30333/p2p/12D3KooW..."]
+//! sudo = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY"
+//!
+//! [[authorities]]
+//! seed = "//Alice"
+//!
+//! [[authorities]]
+//! aura = "0x8eaf04151687736326c9fea17e25fc5287613693c912909cb226aa4794f26a48"
+//! grandpa = "0xd17c2d7823ebf260fd138f2d7e27d114c0145d968b5ff5006125f2414fadae69"
+//!
+//! [[balances]]
+//! account = "//Alice"
+//! amount = "1000000000000000"
+//!
+//! [template]
+//! something = 42
+//!
+//! [properties]
+//! tokenSymbol = "UNIT"
+//! tokenDecimals = 12
+//! ss58Format = 42
+//! ```
+//!
+//! Keys are read as public keys when given in hex or SS58 and as secret URIs (e.g. `//Alice`)
+//! otherwise.
+
+use crate::{
+	chain_spec::{self, ChainSpec},
+	common::parse_public,
+};
+use node_template_runtime::{AccountId, Balance, EXISTENTIAL_DEPOSIT, WASM_BINARY};
+use sc_cli::{Error, Result};
+use sc_service::{config::MultiaddrWithPeerId, ChainSpec as _, ChainType, Properties};
+use sc_telemetry::TelemetryEndpoints;
+use serde::Deserialize;
+use sp_consensus_aura::sr25519::AuthorityId as AuraId;
+use sp_core::{crypto::ByteArray, ed25519, sr25519};
+use sp_finality_grandpa::AuthorityId as GrandpaId;
+use std::{collections::BTreeSet, fs, path::PathBuf};
+
+/// Description of a network to generate a chain spec for.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NetworkConfig {
+	/// Human readable name of the chain.
+	pub name: String,
+	/// Short identifier of the chain, also used to name the output files.
+	pub id: String,
+	/// The type of the chain.
+	#[serde(default = "default_chain_type")]
+	pub chain_type: ChainType,
+	/// The networking protocol id.
+	pub protocol_id: Option<String>,
+	/// Bootnode multiaddresses, including the peer id.
+	#[serde(default)]
+	pub boot_nodes: Vec<String>,
+	/// Telemetry endpoints with their verbosity.
+	#[serde(default)]
+	pub telemetry_endpoints: Vec<(String, u8)>,
+	/// Chain properties. Defaults to [`chain_spec::chain_properties`].
+	pub properties: Option<Properties>,
+	/// The initial Aura and GRANDPA authorities.
+	pub authorities: Vec<AuthorityConfig>,
+	/// The sudo key.
+	pub sudo: String,
+	/// The endowed accounts.
+	#[serde(default)]
+	pub balances: Vec<BalanceConfig>,
+	/// Genesis of the template pallet.
+	#[serde(default)]
+	pub template: TemplateConfig,
+}
+
+fn default_chain_type() -> ChainType {
+	ChainType::Live
+}
+
+/// The keys of a single authority, either derived from one seed or given separately.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AuthorityConfig {
+	/// Secret URI both the Aura and the GRANDPA key are derived from.
+	pub seed: Option<String>,
+	/// The Aura (sr25519) key.
+	pub aura: Option<String>,
+	/// The GRANDPA (ed25519) key.
+	pub grandpa: Option<String>,
+}
+
+/// An endowed account.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BalanceConfig {
+	/// The account to endow.
+	pub account: String,
+	/// Its free balance.
+	pub amount: Amount,
+}
+
+/// A balance, given as an integer or, for values that do not fit in TOML integers, a string.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Amount {
+	/// An integer amount.
+	Int(u64),
+	/// A decimal string amount.
+	Str(String),
+}
+
+impl Amount {
+	fn to_balance(&self) -> std::result::Result<Balance, String> {
+		match self {
+			Amount::Int(amount) => Ok((*amount).into()),
+			Amount::Str(amount) =>
+				amount.parse().map_err(|e| format!("invalid amount `{}`: {}", amount, e)),
+		}
+	}
+}
+
+/// Genesis of the template pallet.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TemplateConfig {
+	/// The initial value of `TemplateModule::Something`.
+	pub something: Option<u32>,
+}
+
+/// The `generate-spec` command used to build a chain spec from a network description file.
+#[derive(Debug, Clone, clap::Parser)]
+pub struct GenerateSpecCmd {
+	/// The TOML or JSON file describing the network.
+	#[clap(value_name = "CONFIG")]
+	pub config: PathBuf,
+
+	/// The directory `<id>.json` and `<id>-raw.json` are written to.
+	#[clap(long, short = 'o', default_value = ".")]
+	pub output_dir: PathBuf,
+}
+
+impl GenerateSpecCmd {
+	/// Run the command.
+	pub fn run(&self) -> Result<()> {
+		let content = fs::read_to_string(&self.config)?;
+		let config: NetworkConfig =
+			if self.config.extension().map_or(false, |ext| ext == "json") {
+				serde_json::from_str(&content).map_err(|e| e.to_string())
+			} else {
+				toml::from_str(&content).map_err(|e| e.to_string())
+			}
+			.map_err(|e| {
+				Error::Input(format!("Failed to parse {}: {}", self.config.display(), e))
+			})?;
+
+		let accounts = config.balances.len();
+		let (spec, total_issuance) = build_chain_spec(config).map_err(Error::Input)?;
+		eprintln!("Endowing {} accounts with a total issuance of {}", accounts, total_issuance);
+
+		fs::create_dir_all(&self.output_dir)?;
+		for raw in [false, true] {
+			let file_name =
+				if raw { format!("{}-raw.json", spec.id()) } else { format!("{}.json", spec.id()) };
+			let path = self.output_dir.join(file_name);
+			fs::write(&path, spec.as_json(raw)?)?;
+			eprintln!("Wrote {}", path.display());
+		}
+
+		Ok(())
+	}
+}
+
+/// Validates the given network description and builds its chain spec, returning it with the total
+/// issuance of its genesis.
+pub fn build_chain_spec(
+	config: NetworkConfig,
+) -> std::result::Result<(ChainSpec, Balance), String> {
+	let wasm_binary = WASM_BINARY.ok_or_else(|| "Wasm binary not available".to_string())?;
+
+	if config.authorities.is_empty() {
+		return Err("At least one authority is required".into())
+	}
+	let mut authorities = Vec::with_capacity(config.authorities.len());
+	for (index, authority) in config.authorities.iter().enumerate() {
+		authorities.push(
+			authority_keys(authority).map_err(|e| format!("authorities[{}]: {}", index, e))?,
+		);
+	}
+	let mut aura_keys = BTreeSet::new();
+	let mut grandpa_keys = BTreeSet::new();
+	for (aura, grandpa) in &authorities {
+		if !aura_keys.insert(aura.to_raw_vec()) || !grandpa_keys.insert(grandpa.to_raw_vec()) {
+			return Err(format!("Duplicate authority key {:?} / {:?}", aura, grandpa))
+		}
+	}
+
+	let root_key = parse_account(&config.sudo).map_err(|e| format!("sudo: {}", e))?;
+
+	let (balances, total_issuance) = validate_balances(&config.balances)?;
+
+	let boot_nodes = config
+		.boot_nodes
+		.iter()
+		.map(|addr| {
+			addr.parse::<MultiaddrWithPeerId>()
+				.map_err(|e| format!("Invalid boot node `{}`: {}", addr, e))
+		})
+		.collect::<std::result::Result<Vec<_>, _>>()?;
+
+	let telemetry = if config.telemetry_endpoints.is_empty() {
+		None
+	} else {
+		Some(
+			TelemetryEndpoints::new(config.telemetry_endpoints.clone())
+				.map_err(|e| format!("Invalid telemetry endpoint: {}", e))?,
+		)
+	};
+
+	let something = config.template.something;
+	let spec = ChainSpec::from_genesis(
+		&config.name,
+		&config.id,
+		config.chain_type,
+		move || {
+			chain_spec::genesis_config(
+				wasm_binary,
+				authorities.clone(),
+				root_key.clone(),
+				balances.clone(),
+				something,
+			)
+		},
+		boot_nodes,
+		telemetry,
+		config.protocol_id.as_deref(),
+		None,
+		Some(config.properties.unwrap_or_else(chain_spec::chain_properties)),
+		Default::default(),
+	);
+	Ok((spec, total_issuance))
+}
+
+/// Checks that every account is endowed once with at least the existential deposit, and that the
+/// total issuance does not overflow. Returns the endowments with the total issuance.
+fn validate_balances(
+	balances: &[BalanceConfig],
+) -> std::result::Result<(Vec<(AccountId, Balance)>, Balance), String> {
+	let mut accounts = BTreeSet::new();
+	let mut total: Balance = 0;
+	let mut endowed = Vec::with_capacity(balances.len());
+
+	for (index, balance) in balances.iter().enumerate() {
+		let account =
+			parse_account(&balance.account).map_err(|e| format!("balances[{}]: {}", index, e))?;
+		let amount =
+			balance.amount.to_balance().map_err(|e| format!("balances[{}]: {}", index, e))?;
+
+		if amount < EXISTENTIAL_DEPOSIT {
+			return Err(format!(
+				"balances[{}]: {} is below the existential deposit of {}",
+				index, amount, EXISTENTIAL_DEPOSIT
+			))
+		}
+		if !accounts.insert(account.clone()) {
+			return Err(format!("balances[{}]: account {} is endowed twice", index, account))
+		}
+		total = total
+			.checked_add(amount)
+			.ok_or_else(|| "The total issuance overflows the balance type".to_string())?;
+
+		endowed.push((account, amount));
+	}
+
+	Ok((endowed, total))
+}
+
+fn authority_keys(authority: &AuthorityConfig) -> std::result::Result<(AuraId, GrandpaId), String> {
+	match (&authority.seed, &authority.aura, &authority.grandpa) {
+		(Some(seed), None, None) => Ok((
+			parse_public::<sr25519::Pair>(seed)?.into(),
+			parse_public::<ed25519::Pair>(seed)?.into(),
+		)),
+		(None, Some(aura), Some(grandpa)) => Ok((
+			parse_public::<sr25519::Pair>(aura).map_err(|e| format!("aura: {}", e))?.into(),
+			parse_public::<ed25519::Pair>(grandpa).map_err(|e| format!("grandpa: {}", e))?.into(),
+		)),
+		_ => Err("either `seed` or both `aura` and `grandpa` must be given".into()),
+	}
+}
+
+fn parse_account(key: &str) -> std::result::Result<AccountId, String> {
+	parse_public::<sr25519::Pair>(key).map(Into::into)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_core::crypto::Ss58Codec;
+
+	fn balance(account: &str, amount: Amount) -> BalanceConfig {
+		BalanceConfig { account: account.into(), amount }
+	}
+
+	#[test]
+	fn validate_balances_sums_the_endowments() {
+		let balances = [
+			balance("//Alice", Amount::Int(1_000)),
+			balance("//Bob", Amount::Str(u128::MAX.to_string())),
+		];
+		assert_eq!(
+			validate_balances(&balances[..1]),
+			Ok((vec![(parse_account("//Alice").unwrap(), 1_000)], 1_000))
+		);
+		assert_eq!(
+			validate_balances(&balances),
+			Err("The total issuance overflows the balance type".into())
+		);
+	}
+
+	#[test]
+	fn validate_balances_rejects_duplicate_accounts() {
+		let alice = parse_account("//Alice").unwrap();
+		let balances = [
+			balance("//Alice", Amount::Int(1_000)),
+			balance(&alice.to_ss58check(), Amount::Int(2_000)),
+		];
+		assert_eq!(
+			validate_balances(&balances),
+			Err(format!("balances[1]: account {} is endowed twice", alice))
+		);
+	}
+
+	#[test]
+	fn validate_balances_rejects_zero_balances() {
+		assert_eq!(
+			validate_balances(&[balance("//Alice", Amount::Int(0))]),
+			Err(format!(
+				"balances[0]: 0 is below the existential deposit of {}",
+				EXISTENTIAL_DEPOSIT
+			))
+		);
+	}
+
+	#[test]
+	fn validate_balances_rejects_balances_below_the_existential_deposit() {
+		let amount = Amount::Str((EXISTENTIAL_DEPOSIT - 1).to_string());
+		assert_eq!(
+			validate_balances(&[balance("//Bob", Amount::Int(1_000)), balance("//Alice", amount)]),
+			Err(format!(
+				"balances[1]: {} is below the existential deposit of {}",
+				EXISTENTIAL_DEPOSIT - 1,
+				EXISTENTIAL_DEPOSIT
+			))
+		);
+		assert!(validate_balances(&[balance("//Alice", Amount::Int(EXISTENTIAL_DEPOSIT as u64))])
+			.is_ok());
+	}
+}
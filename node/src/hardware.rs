@@ -0,0 +1,75 @@
+//! Hardware benchmark run at the startup of authorities.
+
+use sc_sysinfo::HwBench;
+use std::path::Path;
+
+/// Options of the startup hardware benchmark. The default minimums are those of Substrate's
+/// reference hardware, as checked by `benchmark machine`.
+#[derive(Debug, Clone, clap::Parser)]
+pub struct HardwareParams {
+	/// Do not benchmark the hardware at startup.
+	#[clap(long)]
+	pub no_hardware_benchmarks: bool,
+
+	/// Author blocks on a live chain even if the hardware does not meet the minimums below.
+	#[clap(long)]
+	pub allow_insufficient_hardware: bool,
+
+	/// Minimum BLAKE2b-256 hashing speed, in MB/s.
+	#[clap(long, value_name = "MB/S", default_value = "1029")]
+	pub min_cpu_score: u64,
+
+	/// Minimum memory copy speed, in MB/s.
+	#[clap(long, value_name = "MB/S", default_value = "14666")]
+	pub min_memory_score: u64,
+
+	/// Minimum sequential disk write speed, in MB/s.
+	#[clap(long, value_name = "MB/S", default_value = "450")]
+	pub min_disk_sequential_write_score: u64,
+
+	/// Minimum random disk write speed, in MB/s.
+	#[clap(long, value_name = "MB/S", default_value = "200")]
+	pub min_disk_random_write_score: u64,
+}
+
+impl HardwareParams {
+	/// Benchmarks the hardware, writing to `database_path` to measure the disk.
+	pub fn benchmark(&self, database_path: Option<&Path>) -> Option<HwBench> {
+		if self.no_hardware_benchmarks {
+			return None
+		}
+		if let Some(path) = database_path {
+			let _ = std::fs::create_dir_all(path);
+		}
+		Some(sc_sysinfo::gather_hwbench(database_path))
+	}
+
+	/// The scores of `hwbench` below the minimums, as human readable messages.
+	pub fn check(&self, hwbench: &HwBench) -> Vec<String> {
+		let scores = [
+			("CPU", Some(hwbench.cpu_hashrate_score), self.min_cpu_score),
+			("Memory", Some(hwbench.memory_memcpy_score), self.min_memory_score),
+			(
+				"Disk sequential write",
+				hwbench.disk_sequential_write_score,
+				self.min_disk_sequential_write_score,
+			),
+			(
+				"Disk random write",
+				hwbench.disk_random_write_score,
+				self.min_disk_random_write_score,
+			),
+		];
+
+		scores
+			.into_iter()
+			.filter_map(|(name, score, minimum)| match score {
+				Some(score) if score < minimum => Some(format!(
+					"{} score {} MB/s is below the minimum of {} MB/s",
+					name, score, minimum
+				)),
+				_ => None,
+			})
+			.collect()
+	}
+}
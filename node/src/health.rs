@@ -0,0 +1,258 @@
+//! A lightweight HTTP server answering health probes.
+//!
+//! - `/health` always answers `200 OK` with a report of the node's state.
+//! - `/readiness` answers `503 Service Unavailable` while the node is syncing, has too few peers,
+//!   lags behind its peers or finality, and `200 OK` otherwise.
+//! - `/liveness` answers `503 Service Unavailable` if no block was imported for longer than
+//!   `--live-max-block-stall`, and `200 OK` otherwise.
+
+use crate::service::FullClient;
+use futures::{future, FutureExt, StreamExt};
+use hyper::{
+	header::CONTENT_TYPE,
+	service::{make_service_fn, service_fn},
+	Body, Method, Request, Response, Server, StatusCode,
+};
+use node_template_runtime::{opaque::Block, BlockNumber, Hash};
+use sc_client_api::BlockchainEvents;
+use sc_network::{NetworkService, SyncState};
+use serde::Serialize;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_consensus_aura::{sr25519::AuthorityId as AuraId, AuraApi};
+use sp_core::crypto::ByteArray;
+use sp_keystore::{SyncCryptoStore, SyncCryptoStorePtr};
+use sp_runtime::{generic::BlockId, KeyTypeId};
+use std::{
+	future::Future,
+	net::{Ipv4Addr, SocketAddr},
+	sync::{Arc, Mutex},
+	time::Instant,
+};
+
+const LOG_TARGET: &str = "health";
+
+/// Options of the health server.
+#[derive(Debug, Clone, clap::Parser)]
+pub struct HealthParams {
+	/// Serve `/health`, `/readiness` and `/liveness` over HTTP on this port.
+	#[clap(long, value_name = "PORT")]
+	pub health_port: Option<u16>,
+
+	/// Listen on all interfaces instead of localhost only.
+	#[clap(long)]
+	pub health_external: bool,
+
+	/// Minimum number of connected peers for the node to be ready.
+	#[clap(long, value_name = "COUNT", default_value = "1")]
+	pub ready_min_peers: usize,
+
+	/// Maximum number of blocks the best block may be behind the best block seen on the network
+	/// for the node to be ready.
+	#[clap(long, value_name = "BLOCKS", default_value = "8")]
+	pub ready_max_sync_gap: BlockNumber,
+
+	/// Maximum number of blocks between the best and the finalized block for the node to be ready.
+	#[clap(long, value_name = "BLOCKS", default_value = "64")]
+	pub ready_max_finality_lag: BlockNumber,
+
+	/// Seconds without any imported block after which the node is reported as not live. Disabled
+	/// if not given.
+	#[clap(long, value_name = "SECONDS")]
+	pub live_max_block_stall: Option<u64>,
+}
+
+/// The report served by the health endpoints.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthReport {
+	/// Number of connected peers.
+	pub peers: usize,
+	/// Whether the node is performing a major sync.
+	pub is_syncing: bool,
+	/// The best block.
+	pub best_block: BlockNumber,
+	/// The finalized block.
+	pub finalized_block: BlockNumber,
+	/// Number of blocks between the best and the finalized block.
+	pub finality_lag: BlockNumber,
+	/// The best block seen on the network, if any.
+	pub best_seen_block: Option<BlockNumber>,
+	/// Number of blocks the node is behind the best block seen on the network.
+	pub sync_gap: BlockNumber,
+	/// Whether the node runs as an authority.
+	pub is_authority: bool,
+	/// Whether the keystore holds the key of one of the current Aura authorities.
+	pub is_authoring: bool,
+	/// Seconds since the last imported block.
+	pub seconds_since_last_import: u64,
+	/// Why the node is not ready, if it is not.
+	pub not_ready: Vec<String>,
+}
+
+/// The state the health endpoints are computed from.
+pub struct Health {
+	params: HealthParams,
+	client: Arc<FullClient>,
+	network: Arc<NetworkService<Block, Hash>>,
+	keystore: SyncCryptoStorePtr,
+	is_authority: bool,
+	last_import: Mutex<Instant>,
+}
+
+impl Health {
+	/// Create the health state of a node.
+	pub fn new(
+		params: HealthParams,
+		client: Arc<FullClient>,
+		network: Arc<NetworkService<Block, Hash>>,
+		keystore: SyncCryptoStorePtr,
+		is_authority: bool,
+	) -> Self {
+		Self {
+			params,
+			client,
+			network,
+			keystore,
+			is_authority,
+			last_import: Mutex::new(Instant::now()),
+		}
+	}
+
+	/// Binds the server to the port given in the parameters and returns the future serving it,
+	/// or `None` if the server is disabled.
+	pub fn start(self) -> Result<Option<impl Future<Output = ()>>, String> {
+		let port = match self.params.health_port {
+			Some(port) => port,
+			None => return Ok(None),
+		};
+		let ip =
+			if self.params.health_external { Ipv4Addr::UNSPECIFIED } else { Ipv4Addr::LOCALHOST };
+		let address = SocketAddr::from((ip, port));
+
+		let health = Arc::new(self);
+		let make_service = make_service_fn({
+			let health = health.clone();
+			move |_| {
+				let health = health.clone();
+				async move {
+					Ok::<_, hyper::Error>(service_fn(move |request| {
+						let health = health.clone();
+						async move { Ok::<_, hyper::Error>(health.respond(request).await) }
+					}))
+				}
+			}
+		});
+		let server = Server::try_bind(&address)
+			.map_err(|e| format!("Failed to bind the health server to {}: {}", address, e))?
+			.serve(make_service);
+		log::info!(target: LOG_TARGET, "Health endpoints listening on http://{}", address);
+
+		let imports = health.client.import_notification_stream().for_each(move |_| {
+			*health.last_import.lock().unwrap_or_else(|e| e.into_inner()) = Instant::now();
+			future::ready(())
+		});
+		let server = server.map(|result| {
+			if let Err(e) = result {
+				log::error!(target: LOG_TARGET, "Health server failed: {}", e);
+			}
+		});
+
+		Ok(Some(future::select(Box::pin(server), Box::pin(imports)).map(|_| ())))
+	}
+
+	async fn respond(&self, request: Request<Body>) -> Response<Body> {
+		if request.method() != Method::GET {
+			return response(StatusCode::METHOD_NOT_ALLOWED, Body::empty())
+		}
+
+		let report = match self.report().await {
+			Ok(report) => report,
+			Err(e) => {
+				let error = serde_json::json!({ "error": e }).to_string();
+				return response(StatusCode::SERVICE_UNAVAILABLE, Body::from(error))
+			},
+		};
+		let healthy = match request.uri().path() {
+			"/health" => true,
+			"/readiness" => report.not_ready.is_empty(),
+			"/liveness" => self.params.live_max_block_stall.map_or(true, |max_stall| {
+				report.seconds_since_last_import <= max_stall
+			}),
+			_ => return response(StatusCode::NOT_FOUND, Body::empty()),
+		};
+
+		let status = if healthy { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+		match serde_json::to_vec(&report) {
+			Ok(json) => response(status, Body::from(json)),
+			Err(e) => {
+				let error = serde_json::json!({ "error": e.to_string() }).to_string();
+				response(StatusCode::INTERNAL_SERVER_ERROR, Body::from(error))
+			},
+		}
+	}
+
+	async fn report(&self) -> Result<HealthReport, String> {
+		let network = self.network.status().await.map_err(|_| "The network is not running")?;
+		let info = self.client.info();
+		let best_seen_block = network.best_seen_block;
+		let sync_gap = best_seen_block.map_or(0, |seen| seen.saturating_sub(info.best_number));
+		let finality_lag = info.best_number.saturating_sub(info.finalized_number);
+		let is_syncing = matches!(network.sync_state, SyncState::Downloading);
+		let elapsed = self.last_import.lock().unwrap_or_else(|e| e.into_inner()).elapsed();
+
+		let mut not_ready = Vec::new();
+		if network.num_connected_peers < self.params.ready_min_peers {
+			not_ready.push(format!(
+				"{} peers, at least {} required",
+				network.num_connected_peers, self.params.ready_min_peers
+			));
+		}
+		if is_syncing {
+			not_ready.push("major sync in progress".into());
+		}
+		if sync_gap > self.params.ready_max_sync_gap {
+			not_ready.push(format!("{} blocks behind the network", sync_gap));
+		}
+		if finality_lag > self.params.ready_max_finality_lag {
+			not_ready.push(format!("{} blocks behind finality", finality_lag));
+		}
+
+		Ok(HealthReport {
+			peers: network.num_connected_peers,
+			is_syncing,
+			best_block: info.best_number,
+			finalized_block: info.finalized_number,
+			finality_lag,
+			best_seen_block,
+			sync_gap,
+			is_authority: self.is_authority,
+			is_authoring: self.is_authority && self.has_authority_key(info.best_hash),
+			seconds_since_last_import: elapsed.as_secs(),
+			not_ready,
+		})
+	}
+
+	/// Whether the keystore holds the key of one of the Aura authorities at `best`.
+	fn has_authority_key(&self, best: Hash) -> bool {
+		const AURA: KeyTypeId = sp_core::crypto::key_types::AURA;
+
+		let authorities: Vec<AuraId> =
+			match self.client.runtime_api().authorities(&BlockId::Hash(best)) {
+				Ok(authorities) => authorities,
+				Err(_) => return false,
+			};
+		authorities
+			.iter()
+			.any(|id| SyncCryptoStore::has_keys(&*self.keystore, &[(id.to_raw_vec(), AURA)]))
+	}
+}
+
+fn response(status: StatusCode, body: Body) -> Response<Body> {
+	let mut response = Response::new(body);
+	*response.status_mut() = status;
+	response
+		.headers_mut()
+		.insert(CONTENT_TYPE, hyper::header::HeaderValue::from_static("application/json"));
+	response
+}
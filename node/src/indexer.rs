@@ -0,0 +1,709 @@
+//! An optional indexer writing the blocks, extrinsics and events of the best chain to SQLite, so
+//! that questions such as "which extrinsics touched account X" can be answered over RPC.
+//!
+//! Only the best chain is kept. When the best block changes, the indexer walks back from it to the
+//! last indexed ancestor, rolls back the non-finalized blocks above that ancestor and indexes the
+//! new branch. Finalized blocks are never rolled back.
+//!
+//! A new database starts at the best block of the node: the blocks before it are not indexed.
+//! Extrinsics and events the runtime cannot decode are indexed with the pallet and call `unknown`.
+
+use crate::{
+	common::{parse_account_id, storage_prefix, EventRecords},
+	rpc::DenyUnsafe,
+	service::FullClient,
+};
+use codec::{Decode, Encode};
+use frame_support::dispatch::GetCallMetadata;
+use futures::{future, stream, StreamExt};
+use jsonrpsee::{
+	core::{Error as JsonRpseeError, RpcResult},
+	proc_macros::rpc,
+	types::error::{CallError, ErrorObject},
+};
+use node_template_runtime::{
+	self as runtime,
+	opaque::{Header, UncheckedExtrinsic as OpaqueExtrinsic},
+	pallet_template, AccountId, BlockNumber, Hash,
+};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use sc_client_api::{BlockBackend, BlockchainEvents, StorageProvider};
+use serde::Serialize;
+use sp_blockchain::HeaderBackend;
+use sp_core::storage::StorageKey;
+use sp_runtime::{generic::BlockId, traits::Header as _, MultiAddress};
+use std::{
+	path::Path,
+	sync::{Arc, Mutex},
+};
+
+const LOG_TARGET: &str = "indexer";
+
+/// Errors of the indexer.
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+
+const SCHEMA: &str = "
+	PRAGMA foreign_keys = ON;
+
+	CREATE TABLE IF NOT EXISTS blocks (
+		number INTEGER PRIMARY KEY,
+		hash TEXT NOT NULL UNIQUE,
+		parent_hash TEXT NOT NULL,
+		finalized INTEGER NOT NULL DEFAULT 0
+	);
+
+	CREATE TABLE IF NOT EXISTS extrinsics (
+		block_number INTEGER NOT NULL REFERENCES blocks(number) ON DELETE CASCADE,
+		extrinsic_index INTEGER NOT NULL,
+		signer TEXT,
+		pallet TEXT NOT NULL,
+		call TEXT NOT NULL,
+		success INTEGER,
+		PRIMARY KEY (block_number, extrinsic_index)
+	);
+
+	CREATE TABLE IF NOT EXISTS events (
+		block_number INTEGER NOT NULL REFERENCES blocks(number) ON DELETE CASCADE,
+		event_index INTEGER NOT NULL,
+		extrinsic_index INTEGER,
+		pallet TEXT NOT NULL,
+		name TEXT NOT NULL,
+		data TEXT NOT NULL,
+		PRIMARY KEY (block_number, event_index)
+	);
+
+	CREATE TABLE IF NOT EXISTS account_extrinsics (
+		account TEXT NOT NULL,
+		block_number INTEGER NOT NULL REFERENCES blocks(number) ON DELETE CASCADE,
+		extrinsic_index INTEGER NOT NULL,
+		PRIMARY KEY (account, block_number, extrinsic_index)
+	);
+";
+
+/// An extrinsic of the indexed chain.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexedExtrinsic {
+	/// The number of the block including the extrinsic.
+	pub block_number: BlockNumber,
+	/// The hash of the block including the extrinsic.
+	pub block_hash: String,
+	/// The index of the extrinsic in the block.
+	pub index: u32,
+	/// The signer, for signed extrinsics.
+	pub signer: Option<String>,
+	/// The pallet of the call.
+	pub pallet: String,
+	/// The name of the call.
+	pub call: String,
+	/// Whether the extrinsic was dispatched successfully, if known.
+	pub success: Option<bool>,
+	/// Whether the block is finalized.
+	pub finalized: bool,
+}
+
+/// An event of the indexed chain.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexedEvent {
+	/// The index of the event in the block.
+	pub index: u32,
+	/// The index of the extrinsic that deposited the event, if any.
+	pub extrinsic_index: Option<u32>,
+	/// The pallet of the event.
+	pub pallet: String,
+	/// The name of the event.
+	pub name: String,
+	/// The event and its fields, as printed by `Debug`.
+	pub data: String,
+}
+
+/// The range of indexed blocks.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexerStatus {
+	/// The first indexed block.
+	pub first: Option<BlockNumber>,
+	/// The best indexed block.
+	pub best: Option<BlockNumber>,
+	/// The last indexed finalized block.
+	pub finalized: Option<BlockNumber>,
+}
+
+/// The pallet and call, or event name, of the extrinsics and events that do not decode.
+const UNKNOWN: &str = "unknown";
+
+/// The blocks the indexer reads, [`FullClient`] in the node.
+pub trait Chain {
+	/// The header of the block `hash`, if known.
+	fn header(&self, hash: Hash) -> Result<Option<Header>, Error>;
+
+	/// The extrinsics of the block `hash`, empty if unknown.
+	fn body(&self, hash: Hash) -> Result<Vec<OpaqueExtrinsic>, Error>;
+
+	/// The encoded `System::Events` at the block `hash`, if its state is available.
+	fn events(&self, hash: Hash) -> Result<Option<Vec<u8>>, Error>;
+}
+
+impl Chain for FullClient {
+	fn header(&self, hash: Hash) -> Result<Option<Header>, Error> {
+		Ok(HeaderBackend::header(self, BlockId::Hash(hash))?)
+	}
+
+	fn body(&self, hash: Hash) -> Result<Vec<OpaqueExtrinsic>, Error> {
+		Ok(self.block_body(&BlockId::Hash(hash))?.unwrap_or_default())
+	}
+
+	fn events(&self, hash: Hash) -> Result<Option<Vec<u8>>, Error> {
+		let key = StorageKey(storage_prefix("System", "Events"));
+		Ok(self.storage(&BlockId::Hash(hash), &key)?.map(|data| data.0))
+	}
+}
+
+/// The SQLite database of the indexer.
+pub struct Indexer {
+	connection: Mutex<Connection>,
+}
+
+impl Indexer {
+	/// Opens the database at `path`, creating it if needed.
+	pub fn open(path: &Path) -> Result<Self, Error> {
+		let connection = Connection::open(path)?;
+		connection.execute_batch(SCHEMA)?;
+		Ok(Self { connection: Mutex::new(connection) })
+	}
+
+	fn connection(&self) -> std::sync::MutexGuard<'_, Connection> {
+		self.connection.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+	}
+
+	/// Indexes the best chain up to `best`, rolling back the blocks no longer part of it.
+	pub fn on_best_block(&self, chain: &impl Chain, best: Hash) -> Result<(), Error> {
+		let mut connection = self.connection();
+		let tx = connection.transaction()?;
+
+		let first: Option<BlockNumber> =
+			tx.query_row("SELECT MIN(number) FROM blocks", [], |row| row.get(0))?;
+
+		// Walk back to the last indexed ancestor, or to the first indexed height.
+		let mut headers = Vec::new();
+		let mut next = best;
+		let from = loop {
+			let header = chain
+				.header(next)?
+				.ok_or_else(|| format!("Missing header of block {:?}", next))?;
+			let number = *header.number();
+			if hash_at(&tx, number)? == Some(header.hash()) {
+				break number + 1
+			}
+			next = *header.parent_hash();
+			headers.push(header);
+			if number == 0 || first.map_or(true, |first| number <= first) {
+				break number
+			}
+		};
+
+		let rolled_back = tx.execute(
+			"DELETE FROM blocks WHERE number >= ?1 AND finalized = 0",
+			params![from],
+		)?;
+		if rolled_back > 0 {
+			log::info!(target: LOG_TARGET, "Rolled back {} blocks from #{}", rolled_back, from);
+		}
+
+		for header in headers.iter().rev() {
+			index_block(&tx, chain, header)?;
+		}
+
+		tx.commit()?;
+		Ok(())
+	}
+
+	/// Marks the blocks up to `number` as finalized, if `hash` is part of the indexed chain.
+	pub fn on_finalized_block(&self, number: BlockNumber, hash: Hash) -> Result<(), Error> {
+		let connection = self.connection();
+		if hash_at(&connection, number)? != Some(hash) {
+			return Ok(())
+		}
+		connection.execute(
+			"UPDATE blocks SET finalized = 1 WHERE number <= ?1 AND finalized = 0",
+			params![number],
+		)?;
+		Ok(())
+	}
+
+	/// The extrinsics signed by `account` or depositing events involving it, latest first.
+	pub fn account_extrinsics(
+		&self,
+		account: &AccountId,
+		limit: u32,
+	) -> Result<Vec<IndexedExtrinsic>, Error> {
+		let connection = self.connection();
+		let mut statement = connection.prepare(
+			"SELECT b.number, b.hash, e.extrinsic_index, e.signer, e.pallet, e.call, e.success,
+				b.finalized
+			FROM account_extrinsics a
+			JOIN extrinsics e
+				ON e.block_number = a.block_number AND e.extrinsic_index = a.extrinsic_index
+			JOIN blocks b ON b.number = a.block_number
+			WHERE a.account = ?1
+			ORDER BY a.block_number DESC, a.extrinsic_index DESC
+			LIMIT ?2",
+		)?;
+		let rows = statement.query_map(params![account.to_string(), limit], |row| {
+			Ok(IndexedExtrinsic {
+				block_number: row.get(0)?,
+				block_hash: row.get(1)?,
+				index: row.get(2)?,
+				signer: row.get(3)?,
+				pallet: row.get(4)?,
+				call: row.get(5)?,
+				success: row.get(6)?,
+				finalized: row.get(7)?,
+			})
+		})?;
+		Ok(rows.collect::<Result<_, _>>()?)
+	}
+
+	/// The events of the indexed block at `number`.
+	pub fn block_events(&self, number: BlockNumber) -> Result<Vec<IndexedEvent>, Error> {
+		let connection = self.connection();
+		let mut statement = connection.prepare(
+			"SELECT event_index, extrinsic_index, pallet, name, data FROM events
+			WHERE block_number = ?1 ORDER BY event_index",
+		)?;
+		let rows = statement.query_map(params![number], |row| {
+			Ok(IndexedEvent {
+				index: row.get(0)?,
+				extrinsic_index: row.get(1)?,
+				pallet: row.get(2)?,
+				name: row.get(3)?,
+				data: row.get(4)?,
+			})
+		})?;
+		Ok(rows.collect::<Result<_, _>>()?)
+	}
+
+	/// The range of indexed blocks.
+	pub fn status(&self) -> Result<IndexerStatus, Error> {
+		let connection = self.connection();
+		Ok(connection.query_row(
+			"SELECT MIN(number), MAX(number), MAX(CASE WHEN finalized = 1 THEN number END)
+			FROM blocks",
+			[],
+			|row| {
+				Ok(IndexerStatus { first: row.get(0)?, best: row.get(1)?, finalized: row.get(2)? })
+			},
+		)?)
+	}
+}
+
+/// Follows the best and finalized blocks of `client` until the node shuts down.
+pub async fn run(client: Arc<FullClient>, indexer: Arc<Indexer>) {
+	enum Notification {
+		Best(Hash),
+		Finalized(BlockNumber, Hash),
+	}
+
+	let imports = client
+		.import_notification_stream()
+		.filter_map(|n| future::ready(n.is_new_best.then(|| Notification::Best(n.hash))));
+	let finality = client
+		.finality_notification_stream()
+		.map(|n| Notification::Finalized(*n.header.number(), n.hash));
+
+	// Catch up with the blocks imported since the last run.
+	let info = client.info();
+	let catch_up = stream::iter([
+		Notification::Best(info.best_hash),
+		Notification::Finalized(info.finalized_number, info.finalized_hash),
+	]);
+
+	let mut notifications = catch_up.chain(stream::select(imports, finality));
+	while let Some(notification) = notifications.next().await {
+		// The database writes and the client reads block, keep them off the async executor.
+		let (client, indexer) = (client.clone(), indexer.clone());
+		let result = tokio::task::spawn_blocking(move || match notification {
+			Notification::Best(hash) => indexer.on_best_block(&*client, hash),
+			Notification::Finalized(number, hash) => indexer.on_finalized_block(number, hash),
+		})
+		.await
+		.map_err(Error::from)
+		.and_then(|result| result);
+		if let Err(e) = result {
+			log::warn!(target: LOG_TARGET, "Failed to index the chain: {}", e);
+		}
+	}
+}
+
+fn hash_at(connection: &Connection, number: BlockNumber) -> Result<Option<Hash>, Error> {
+	let hash: Option<String> = connection
+		.query_row("SELECT hash FROM blocks WHERE number = ?1", params![number], |row| row.get(0))
+		.optional()?;
+	Ok(hash.and_then(|hash| hash.parse().ok()))
+}
+
+/// Writes the block, its extrinsics, its events and the accounts they touch.
+fn index_block(tx: &Transaction, chain: &impl Chain, header: &Header) -> Result<(), Error> {
+	let hash = header.hash();
+	let number = *header.number();
+
+	tx.execute(
+		"INSERT INTO blocks (number, hash, parent_hash) VALUES (?1, ?2, ?3)",
+		params![number, format!("{:?}", hash), format!("{:?}", header.parent_hash())],
+	)?;
+
+	let events = match chain.events(hash)? {
+		Some(encoded) => match EventRecords::decode(&mut &encoded[..]) {
+			Ok(events) => events,
+			Err(e) => {
+				log::warn!(target: LOG_TARGET, "Undecodable events in block #{}: {}", number, e);
+				tx.execute(
+					"INSERT INTO events (block_number, event_index, pallet, name, data)
+					VALUES (?1, 0, ?2, ?2, ?3)",
+					params![number, UNKNOWN, format!("0x{}", hex::encode(&encoded))],
+				)?;
+				Vec::new()
+			},
+		},
+		None => {
+			log::debug!(target: LOG_TARGET, "No events for block #{}, state pruned?", number);
+			Vec::new()
+		},
+	};
+
+	let mut touched = Vec::new();
+	for (index, record) in events.iter().enumerate() {
+		let extrinsic_index = match record.phase {
+			frame_system::Phase::ApplyExtrinsic(index) => Some(index),
+			_ => None,
+		};
+		let data = format!("{:?}", record.event);
+		let (pallet, name) = event_name(&data);
+		tx.execute(
+			"INSERT INTO events (block_number, event_index, extrinsic_index, pallet, name, data)
+			VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+			params![number, index as u32, extrinsic_index, pallet, name, data],
+		)?;
+		if let Some(extrinsic_index) = extrinsic_index {
+			touched.extend(event_accounts(&record.event).into_iter().map(|a| (a, extrinsic_index)));
+		}
+	}
+
+	for (index, opaque) in chain.body(hash)?.iter().enumerate() {
+		let index = index as u32;
+		let (signer, pallet, call) =
+			match runtime::UncheckedExtrinsic::decode(&mut &opaque.encode()[..]) {
+				Ok(extrinsic) => {
+					let signer = match &extrinsic.signature {
+						Some((MultiAddress::Id(account), _, _)) => Some(account.clone()),
+						_ => None,
+					};
+					let metadata = extrinsic.function.get_call_metadata();
+					(signer, metadata.pallet_name, metadata.function_name)
+				},
+				Err(e) => {
+					log::warn!(
+						target: LOG_TARGET,
+						"Undecodable extrinsic #{} in block #{}: {}",
+						index,
+						number,
+						e,
+					);
+					(None, UNKNOWN, UNKNOWN)
+				},
+			};
+		let success = events.iter().find_map(|record| match (&record.phase, &record.event) {
+			(
+				frame_system::Phase::ApplyExtrinsic(i),
+				runtime::Event::System(frame_system::Event::ExtrinsicSuccess { .. }),
+			) if *i == index => Some(true),
+			(
+				frame_system::Phase::ApplyExtrinsic(i),
+				runtime::Event::System(frame_system::Event::ExtrinsicFailed { .. }),
+			) if *i == index => Some(false),
+			_ => None,
+		});
+
+		tx.execute(
+			"INSERT INTO extrinsics (block_number, extrinsic_index, signer, pallet, call, success)
+			VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+			params![number, index, signer.as_ref().map(ToString::to_string), pallet, call, success],
+		)?;
+		if let Some(signer) = signer {
+			touched.push((signer, index));
+		}
+	}
+
+	for (account, extrinsic_index) in touched {
+		tx.execute(
+			"INSERT OR IGNORE INTO account_extrinsics (account, block_number, extrinsic_index)
+			VALUES (?1, ?2, ?3)",
+			params![account.to_string(), number, extrinsic_index],
+		)?;
+	}
+
+	Ok(())
+}
+
+/// Splits the `Debug` output of a runtime event, `Pallet(Name { .. })`, into its pallet and name.
+fn event_name(data: &str) -> (&str, &str) {
+	let (pallet, rest) = data.split_once('(').unwrap_or((data, ""));
+	let end = rest.find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(rest.len());
+	(pallet, &rest[..end])
+}
+
+/// The accounts involved in an event.
+fn event_accounts(event: &runtime::Event) -> Vec<AccountId> {
+	use pallet_balances::Event as BalancesEvent;
+
+	match event {
+		runtime::Event::System(
+			frame_system::Event::NewAccount { account } |
+			frame_system::Event::KilledAccount { account },
+		) => vec![account.clone()],
+		runtime::Event::Balances(
+			BalancesEvent::Endowed { account, .. } | BalancesEvent::DustLost { account, .. },
+		) => vec![account.clone()],
+		runtime::Event::Balances(
+			BalancesEvent::Transfer { from, to, .. } |
+			BalancesEvent::ReserveRepatriated { from, to, .. },
+		) => vec![from.clone(), to.clone()],
+		runtime::Event::Balances(
+			BalancesEvent::BalanceSet { who, .. } |
+			BalancesEvent::Reserved { who, .. } |
+			BalancesEvent::Unreserved { who, .. } |
+			BalancesEvent::Deposit { who, .. } |
+			BalancesEvent::Withdraw { who, .. } |
+			BalancesEvent::Slashed { who, .. },
+		) => vec![who.clone()],
+		runtime::Event::TemplateModule(pallet_template::Event::SomethingStored(_, who)) =>
+			vec![who.clone()],
+		_ => Vec::new(),
+	}
+}
+
+/// Queries of the indexer. `indexer_accountExtrinsics` is unsafe.
+#[rpc(server)]
+pub trait IndexerApi {
+	/// The extrinsics signed by `account`, an SS58 address or hex public key, or depositing events
+	/// involving it, latest first.
+	#[method(name = "indexer_accountExtrinsics")]
+	fn account_extrinsics(
+		&self,
+		account: String,
+		limit: Option<u32>,
+	) -> RpcResult<Vec<IndexedExtrinsic>>;
+
+	/// The events of the indexed block at `number`.
+	#[method(name = "indexer_blockEvents")]
+	fn block_events(&self, number: BlockNumber) -> RpcResult<Vec<IndexedEvent>>;
+
+	/// The range of indexed blocks.
+	#[method(name = "indexer_status")]
+	fn status(&self) -> RpcResult<IndexerStatus>;
+}
+
+/// The default and maximum number of extrinsics returned by `indexer_accountExtrinsics`.
+const MAX_LIMIT: u32 = 1000;
+
+/// Error code of the indexer RPC.
+const INDEXER_ERROR: i32 = 1;
+
+/// The RPC handler of the indexer queries.
+pub struct IndexerRpc {
+	indexer: Arc<Indexer>,
+	deny_unsafe: DenyUnsafe,
+}
+
+impl IndexerRpc {
+	/// Create a new instance of the indexer RPC handler.
+	pub fn new(indexer: Arc<Indexer>, deny_unsafe: DenyUnsafe) -> Self {
+		Self { indexer, deny_unsafe }
+	}
+}
+
+impl IndexerApiServer for IndexerRpc {
+	fn account_extrinsics(
+		&self,
+		account: String,
+		limit: Option<u32>,
+	) -> RpcResult<Vec<IndexedExtrinsic>> {
+		// Scans up to `MAX_LIMIT` rows of an arbitrary account.
+		self.deny_unsafe.check_if_safe()?;
+
+		let account = parse_account_id(&account).map_err(|e| rpc_error("Invalid account", e))?;
+		self.indexer
+			.account_extrinsics(&account, limit.unwrap_or(MAX_LIMIT).min(MAX_LIMIT))
+			.map_err(|e| rpc_error("Indexer query failed", e))
+	}
+
+	fn block_events(&self, number: BlockNumber) -> RpcResult<Vec<IndexedEvent>> {
+		self.indexer.block_events(number).map_err(|e| rpc_error("Indexer query failed", e))
+	}
+
+	fn status(&self) -> RpcResult<IndexerStatus> {
+		self.indexer.status().map_err(|e| rpc_error("Indexer query failed", e))
+	}
+}
+
+fn rpc_error(message: &str, error: impl std::fmt::Display) -> JsonRpseeError {
+	JsonRpseeError::Call(CallError::Custom(ErrorObject::owned(
+		INDEXER_ERROR,
+		message,
+		Some(error.to_string()),
+	)))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_runtime::traits::Header as _;
+	use std::collections::HashMap;
+
+	/// A chain of headers with no state and empty blocks, unless given.
+	#[derive(Default)]
+	struct TestChain {
+		headers: HashMap<Hash, Header>,
+		bodies: HashMap<Hash, Vec<OpaqueExtrinsic>>,
+		events: HashMap<Hash, Vec<u8>>,
+	}
+
+	impl TestChain {
+		/// Adds a child of `parent`, told apart from its siblings by `fork`, returning its hash.
+		fn push(&mut self, parent: Option<Hash>, fork: u8) -> Hash {
+			let number = parent.map_or(0, |parent| self.headers[&parent].number + 1);
+			let header = Header::new(
+				number,
+				Default::default(),
+				Hash::repeat_byte(fork),
+				parent.unwrap_or_default(),
+				Default::default(),
+			);
+			let hash = header.hash();
+			self.headers.insert(hash, header);
+			hash
+		}
+
+		/// Adds `length` blocks on top of `parent`, returning their hashes.
+		fn extend(&mut self, parent: Option<Hash>, length: usize, fork: u8) -> Vec<Hash> {
+			let mut hashes: Vec<Hash> = Vec::new();
+			for _ in 0..length {
+				let parent = hashes.last().copied().or(parent);
+				hashes.push(self.push(parent, fork));
+			}
+			hashes
+		}
+	}
+
+	impl Chain for TestChain {
+		fn header(&self, hash: Hash) -> Result<Option<Header>, Error> {
+			Ok(self.headers.get(&hash).cloned())
+		}
+
+		fn body(&self, hash: Hash) -> Result<Vec<OpaqueExtrinsic>, Error> {
+			Ok(self.bodies.get(&hash).cloned().unwrap_or_default())
+		}
+
+		fn events(&self, hash: Hash) -> Result<Option<Vec<u8>>, Error> {
+			Ok(self.events.get(&hash).cloned())
+		}
+	}
+
+	fn indexed(indexer: &Indexer) -> Vec<(BlockNumber, Hash, bool)> {
+		let connection = indexer.connection();
+		let mut statement = connection
+			.prepare("SELECT number, hash, finalized FROM blocks ORDER BY number")
+			.unwrap();
+		let rows = statement
+			.query_map([], |row| {
+				let hash: String = row.get(1)?;
+				Ok((row.get(0)?, hash.parse().unwrap(), row.get(2)?))
+			})
+			.unwrap();
+		rows.collect::<Result<_, _>>().unwrap()
+	}
+
+	fn open() -> Indexer {
+		Indexer::open(Path::new(":memory:")).unwrap()
+	}
+
+	#[test]
+	fn starts_at_the_best_block_and_follows_the_chain() {
+		let mut chain = TestChain::default();
+		let blocks = chain.extend(None, 4, 0);
+		let indexer = open();
+
+		indexer.on_best_block(&chain, blocks[1]).unwrap();
+		assert_eq!(indexed(&indexer), vec![(1, blocks[1], false)]);
+
+		indexer.on_best_block(&chain, blocks[3]).unwrap();
+		assert_eq!(
+			indexed(&indexer),
+			vec![(1, blocks[1], false), (2, blocks[2], false), (3, blocks[3], false)],
+		);
+	}
+
+	#[test]
+	fn reorgs_roll_back_the_retracted_blocks() {
+		let mut chain = TestChain::default();
+		let blocks = chain.extend(None, 4, 0);
+		let fork = chain.extend(Some(blocks[1]), 1, 1);
+		let indexer = open();
+		indexer.on_best_block(&chain, blocks[0]).unwrap();
+		indexer.on_best_block(&chain, blocks[3]).unwrap();
+
+		indexer.on_best_block(&chain, fork[0]).unwrap();
+		assert_eq!(
+			indexed(&indexer),
+			vec![(0, blocks[0], false), (1, blocks[1], false), (2, fork[0], false)],
+		);
+	}
+
+	#[test]
+	fn finalized_blocks_are_never_rolled_back() {
+		let mut chain = TestChain::default();
+		let blocks = chain.extend(None, 3, 0);
+		let fork = chain.extend(Some(blocks[0]), 2, 1);
+		let indexer = open();
+		indexer.on_best_block(&chain, blocks[0]).unwrap();
+		indexer.on_best_block(&chain, blocks[2]).unwrap();
+
+		// Finality of a block outside the index changes nothing.
+		indexer.on_finalized_block(1, fork[0]).unwrap();
+		assert!(indexed(&indexer).iter().all(|(_, _, finalized)| !finalized));
+
+		indexer.on_finalized_block(1, blocks[1]).unwrap();
+		let expected = vec![(0, blocks[0], true), (1, blocks[1], true), (2, blocks[2], false)];
+		assert_eq!(indexed(&indexer), expected);
+
+		// A branch retracting a finalized block is refused as a whole.
+		assert!(indexer.on_best_block(&chain, fork[1]).is_err());
+		assert_eq!(indexed(&indexer), expected);
+	}
+
+	#[test]
+	fn undecodable_extrinsics_and_events_are_indexed_as_unknown() {
+		let mut chain = TestChain::default();
+		let block = chain.push(None, 0);
+		let garbage = OpaqueExtrinsic::decode(&mut &vec![1u8, 2, 3].encode()[..]).unwrap();
+		chain.bodies.insert(block, vec![garbage]);
+		chain.events.insert(block, vec![0xff]);
+		let indexer = open();
+
+		indexer.on_best_block(&chain, block).unwrap();
+		let connection = indexer.connection();
+		let extrinsic: (Option<String>, String, String) = connection
+			.query_row("SELECT signer, pallet, call FROM extrinsics", [], |row| {
+				Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+			})
+			.unwrap();
+		assert_eq!(extrinsic, (None, UNKNOWN.to_string(), UNKNOWN.to_string()));
+		let event: (String, String, String) = connection
+			.query_row("SELECT pallet, name, data FROM events", [], |row| {
+				Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+			})
+			.unwrap();
+		assert_eq!(event, (UNKNOWN.to_string(), UNKNOWN.to_string(), "0xff".to_string()));
+	}
+}
@@ -0,0 +1,411 @@
+//! Keystore management: passwords from the environment, the `key list`, `key export` and
+//! `key remove` subcommands, and the check refusing to author with keys that are not password
+//! protected.
+//!
+//! The local keystore stores the secret URI of every key in a file named after its key type and
+//! public key. When the keystore has a password, keys are derived from their secret URI *and* the
+//! password, so that the files alone do not give the keys away. The password is thus not an
+//! encryption key of the files but a part of the secret of every key: changing it changes the
+//! keys, so keys cannot be re-encrypted under another password. Moving to a new password takes
+//! new keys, generated with `session-keys rotate`, and putting them in the authority sets.
+
+use crate::cli::Cli;
+use sc_cli::{
+	utils::print_from_uri, with_crypto_scheme, CryptoScheme, Error, KeystoreParams, OutputType,
+	Result, SharedParams, SubstrateCli,
+};
+use sc_keystore::LocalKeystore;
+use sc_service::config::{BasePath, KeystoreConfig};
+use sp_core::{
+	crypto::{key_types, KeyTypeId, SecretString},
+	ed25519, sr25519, Pair,
+};
+use sp_keystore::SyncCryptoStore;
+use std::{
+	fs,
+	path::{Path, PathBuf},
+};
+
+/// The environment variable read for the keystore password when none is given on the command
+/// line.
+pub const PASSWORD_ENV: &str = "NODE_KEYSTORE_PASSWORD";
+
+/// Takes the password of [`PASSWORD_ENV`] out of the environment of the process, and uses it for
+/// every keystore of the command line, the node's and the subcommand's, that is given no password.
+pub fn password_from_env(cli: &mut Cli) {
+	let password = match std::env::var(PASSWORD_ENV) {
+		Ok(password) => password,
+		Err(_) => return,
+	};
+	std::env::remove_var(PASSWORD_ENV);
+	for params in cli.keystore_params_mut() {
+		let given = params.password.is_some() ||
+			params.password_filename.is_some() ||
+			params.password_interactive;
+		if !given {
+			params.password = Some(SecretString::new(password.clone()));
+		}
+	}
+}
+
+/// The key types managed by the `key` subcommands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ArgEnum)]
+pub enum KeyType {
+	/// Aura block authoring keys, sr25519.
+	Aura,
+	/// GRANDPA finality keys, ed25519.
+	Gran,
+}
+
+impl KeyType {
+	const ALL: [KeyType; 2] = [KeyType::Aura, KeyType::Gran];
+
+	fn id(self) -> KeyTypeId {
+		match self {
+			KeyType::Aura => key_types::AURA,
+			KeyType::Gran => key_types::GRANDPA,
+		}
+	}
+
+	fn scheme(self) -> CryptoScheme {
+		match self {
+			KeyType::Aura => CryptoScheme::Sr25519,
+			KeyType::Gran => CryptoScheme::Ed25519,
+		}
+	}
+
+	fn from_id(id: KeyTypeId) -> Option<Self> {
+		Self::ALL.into_iter().find(|key_type| key_type.id() == id)
+	}
+
+	/// The given key type, or all of them.
+	fn selected(key_type: Option<KeyType>) -> Vec<KeyType> {
+		key_type.map_or_else(|| Self::ALL.to_vec(), |key_type| vec![key_type])
+	}
+}
+
+/// A key file of the local keystore.
+#[derive(Debug, Clone)]
+pub struct KeyFile {
+	/// The key type.
+	pub key_type: KeyTypeId,
+	/// The raw public key.
+	pub public: Vec<u8>,
+	/// The path of the file.
+	pub path: PathBuf,
+}
+
+impl KeyFile {
+	/// The key files of the keystore at `path`.
+	pub fn all(path: &Path) -> Result<Vec<KeyFile>> {
+		let mut files = Vec::new();
+		if !path.exists() {
+			return Ok(files)
+		}
+		for entry in fs::read_dir(path)? {
+			let path = entry?.path();
+			let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+			let key_type = name.get(..8).and_then(|key_type| hex::decode(key_type).ok());
+			let public = name.get(8..).and_then(|public| hex::decode(public).ok());
+			if let (Some(key_type), Some(public)) = (key_type, public) {
+				let key_type =
+					KeyTypeId::try_from(&key_type[..]).map_err(|_| Error::KeystoreOperation)?;
+				files.push(KeyFile { key_type, public, path });
+			}
+		}
+		files.sort_by(|a, b| a.path.cmp(&b.path));
+		Ok(files)
+	}
+
+	/// The secret URI stored in the file.
+	pub fn suri(&self) -> Result<String> {
+		serde_json::from_slice(&fs::read(&self.path)?).map_err(|e| {
+			Error::Input(format!("Invalid key file {}: {}", self.path.display(), e))
+		})
+	}
+
+	/// Whether the key can only be derived from its secret URI together with a password.
+	pub fn is_password_protected(&self) -> Result<bool> {
+		let suri = self.suri()?;
+		let derives = |public: Option<Vec<u8>>| public.map_or(false, |p| p == self.public);
+		let sr25519 = sr25519::Pair::from_string(&suri, None).ok().map(|p| p.public().0.to_vec());
+		let ed25519 = ed25519::Pair::from_string(&suri, None).ok().map(|p| p.public().0.to_vec());
+		Ok(!derives(sr25519) && !derives(ed25519))
+	}
+}
+
+/// Fails if the keystore at `path` holds keys that are not password protected.
+pub fn ensure_password_protected(path: &Path) -> Result<()> {
+	let mut unprotected = Vec::new();
+	for file in KeyFile::all(path)? {
+		if !file.is_password_protected()? {
+			unprotected.push(file.path.display().to_string());
+		}
+	}
+	if unprotected.is_empty() {
+		return Ok(())
+	}
+	Err(Error::Input(format!(
+		"The keystore holds keys that are not password protected: {}. Replace them with keys \
+		generated by `session-keys rotate` with a password, or pass \
+		`--allow-unencrypted-keystore`.",
+		unprotected.join(", ")
+	)))
+}
+
+/// The keystore used by the `key` subcommands.
+#[derive(Debug, Clone, clap::Parser)]
+pub struct KeystoreArgs {
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub shared_params: SharedParams,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub keystore_params: KeystoreParams,
+}
+
+impl KeystoreArgs {
+	/// The path and password of the keystore of the selected chain. The password of
+	/// [`PASSWORD_ENV`] is applied by [`password_from_env`] when the command line is parsed.
+	pub(crate) fn open<C: SubstrateCli>(&self, cli: &C) -> Result<(PathBuf, Option<SecretString>)> {
+		// The chain spec only matters for locating the keystore under the base path.
+		let config_dir = match self.keystore_params.keystore_path {
+			Some(_) => PathBuf::new(),
+			None => {
+				let base_path = self
+					.shared_params
+					.base_path()
+					.unwrap_or_else(|| BasePath::from_project("", "", &C::executable_name()));
+				base_path.config_dir(self.chain_spec(cli)?.id())
+			},
+		};
+
+		match self.keystore_params.keystore_config(&config_dir)? {
+			(_, KeystoreConfig::Path { path, password }) => Ok((path, password)),
+			_ => unreachable!("keystore_config always returns path and password; qed"),
+		}
+	}
+
+	/// The spec of the selected chain.
+	fn chain_spec<C: SubstrateCli>(&self, cli: &C) -> Result<Box<dyn sc_service::ChainSpec>> {
+		let chain_id = self.shared_params.chain_id(self.shared_params.is_dev());
+		Ok(cli.load_spec(&chain_id)?)
+	}
+}
+
+/// Key management subcommands.
+#[derive(Debug, clap::Subcommand)]
+pub enum KeySubcommand {
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	Base(sc_cli::KeySubcommand),
+
+	/// List the keys of the keystore.
+	List(ListKeysCmd),
+
+	/// Print the secret URIs of keys of the keystore.
+	Export(ExportKeysCmd),
+
+	/// Remove keys from the keystore.
+	Remove(RemoveKeysCmd),
+}
+
+impl KeySubcommand {
+	/// The keystore options of the command.
+	pub fn keystore_params_mut(&mut self) -> Option<&mut KeystoreParams> {
+		match self {
+			KeySubcommand::Base(sc_cli::KeySubcommand::Insert(cmd)) =>
+				Some(&mut cmd.keystore_params),
+			KeySubcommand::Base(_) => None,
+			KeySubcommand::List(cmd) => Some(&mut cmd.keystore.keystore_params),
+			KeySubcommand::Export(cmd) => Some(&mut cmd.keystore.keystore_params),
+			KeySubcommand::Remove(cmd) => Some(&mut cmd.keystore.keystore_params),
+		}
+	}
+
+	/// Run the command.
+	pub fn run<C: SubstrateCli>(&self, cli: &C) -> Result<()> {
+		match self {
+			KeySubcommand::Base(cmd) => cmd.run(cli),
+			KeySubcommand::List(cmd) => cmd.run(cli),
+			KeySubcommand::Export(cmd) => cmd.run(cli),
+			KeySubcommand::Remove(cmd) => cmd.run(cli),
+		}
+	}
+}
+
+/// The `key list` command.
+#[derive(Debug, Clone, clap::Parser)]
+pub struct ListKeysCmd {
+	/// Only list keys of this type.
+	#[clap(long, arg_enum)]
+	pub key_type: Option<KeyType>,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub keystore: KeystoreArgs,
+}
+
+impl ListKeysCmd {
+	/// Run the command.
+	pub fn run<C: SubstrateCli>(&self, cli: &C) -> Result<()> {
+		let (path, _) = self.keystore.open(cli)?;
+		for file in KeyFile::all(&path)? {
+			match (KeyType::from_id(file.key_type), self.key_type) {
+				(Some(key_type), Some(selected)) if key_type != selected => continue,
+				(None, Some(_)) => continue,
+				_ => {},
+			}
+			let key_type = String::from_utf8_lossy(&file.key_type.0).into_owned();
+			let protection =
+				if file.is_password_protected()? { "password protected" } else { "unprotected" };
+			println!("{} 0x{} ({})", key_type, hex::encode(&file.public), protection);
+		}
+		Ok(())
+	}
+}
+
+/// The `key export` command.
+#[derive(Debug, Clone, clap::Parser)]
+pub struct ExportKeysCmd {
+	/// Only export keys of this type.
+	#[clap(long, arg_enum)]
+	pub key_type: Option<KeyType>,
+
+	/// Only export the key with this hex encoded public key.
+	#[clap(long, value_name = "PUBLIC")]
+	pub public: Option<String>,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub keystore: KeystoreArgs,
+}
+
+impl ExportKeysCmd {
+	/// Run the command.
+	pub fn run<C: SubstrateCli>(&self, cli: &C) -> Result<()> {
+		let (path, password) = self.keystore.open(cli)?;
+		let files = selected_files(&path, self.key_type, self.public.as_deref())?;
+		if files.is_empty() {
+			return Err("No matching key in the keystore".into())
+		}
+
+		eprintln!("Anyone with these secret URIs and the keystore password controls the keys.");
+		for file in files {
+			let key_type = KeyType::from_id(file.key_type).ok_or(Error::KeystoreOperation)?;
+			println!("{:?} key:", key_type);
+			let suri = file.suri()?;
+			with_crypto_scheme!(
+				key_type.scheme(),
+				print_from_uri(&suri, password.clone(), None, OutputType::Text)
+			);
+		}
+		Ok(())
+	}
+}
+
+/// The `key remove` command.
+#[derive(Debug, Clone, clap::Parser)]
+pub struct RemoveKeysCmd {
+	/// The type of the keys to remove.
+	#[clap(long, arg_enum)]
+	pub key_type: KeyType,
+
+	/// The hex encoded public key to remove. All keys of the type are removed if not given.
+	#[clap(long, value_name = "PUBLIC")]
+	pub public: Option<String>,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub keystore: KeystoreArgs,
+}
+
+impl RemoveKeysCmd {
+	/// Run the command.
+	pub fn run<C: SubstrateCli>(&self, cli: &C) -> Result<()> {
+		let (path, _) = self.keystore.open(cli)?;
+		let files = selected_files(&path, Some(self.key_type), self.public.as_deref())?;
+		if files.is_empty() {
+			return Err("No matching key in the keystore".into())
+		}
+		for file in files {
+			fs::remove_file(&file.path)?;
+			println!("Removed 0x{}", hex::encode(&file.public));
+		}
+		Ok(())
+	}
+}
+
+/// Generates a new key of `key_type` protected by `password` in `keystore`, returning its raw
+/// public key.
+pub(crate) fn generate(
+	keystore: &LocalKeystore,
+	key_type: KeyType,
+	password: Option<&str>,
+) -> Result<Vec<u8>> {
+	let (phrase, public) = match key_type {
+		KeyType::Aura => {
+			let (pair, phrase, _) = sr25519::Pair::generate_with_phrase(password);
+			(phrase, pair.public().0.to_vec())
+		},
+		KeyType::Gran => {
+			let (pair, phrase, _) = ed25519::Pair::generate_with_phrase(password);
+			(phrase, pair.public().0.to_vec())
+		},
+	};
+	SyncCryptoStore::insert_unknown(keystore, key_type.id(), &phrase, &public)
+		.map_err(|_| Error::KeystoreOperation)?;
+	Ok(public)
+}
+
+/// The key files of the given type and public key.
+fn selected_files(
+	path: &Path,
+	key_type: Option<KeyType>,
+	public: Option<&str>,
+) -> Result<Vec<KeyFile>> {
+	let public = public
+		.map(|public| hex::decode(public.trim_start_matches("0x")))
+		.transpose()
+		.map_err(|_| Error::HexDataConversion)?;
+	let key_types: Vec<_> = KeyType::selected(key_type).into_iter().map(KeyType::id).collect();
+
+	Ok(KeyFile::all(path)?
+		.into_iter()
+		.filter(|file| key_types.contains(&file.key_type))
+		.filter(|file| public.as_ref().map_or(true, |public| *public == file.public))
+		.collect())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use clap::Parser;
+	use sp_core::crypto::ExposeSecret;
+
+	fn parse(args: &[&str]) -> Cli {
+		let command = ["node-template", "key", "list", "--keystore-path", "/tmp/keys"];
+		Cli::parse_from([&command[..], args].concat())
+	}
+
+	#[test]
+	fn password_from_env_fills_in_the_keystores_given_no_password() {
+		std::env::set_var(PASSWORD_ENV, "secret");
+		let mut cli = parse(&[]);
+		password_from_env(&mut cli);
+		assert!(std::env::var(PASSWORD_ENV).is_err());
+		let passwords: Vec<_> = cli
+			.keystore_params_mut()
+			.into_iter()
+			.map(|params| params.password.as_ref().map(|p| p.expose_secret().clone()))
+			.collect();
+		assert_eq!(passwords, vec![Some("secret".to_string()); 2]);
+
+		std::env::set_var(PASSWORD_ENV, "secret");
+		let mut cli = parse(&["--password", "given"]);
+		password_from_env(&mut cli);
+		let password = cli.keystore_params_mut().pop().unwrap().password.as_ref().unwrap();
+		assert_eq!(password.expose_secret(), "given");
+	}
+}
@@ -1,3 +1,31 @@
+//! Substrate Node Template CLI library.
+#![warn(missing_docs)]
+
 pub mod chain_spec;
-pub mod rpc;
+#[macro_use]
 pub mod service;
+mod benchmarking;
+pub mod checkpoint;
+pub mod cli;
+mod command;
+mod common;
+pub mod extrinsic;
+mod fork_off;
+mod generate_spec;
+mod hardware;
+mod health;
+mod indexer;
+mod keystore;
+mod load;
+mod metrics;
+pub mod rpc;
+mod rpc_client;
+mod session_keys;
+mod snapshot;
+mod storage;
+mod testnet;
+mod tx;
+mod tx_pool;
+mod upgrade_runtime;
+
+pub use command::run;
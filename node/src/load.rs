@@ -0,0 +1,571 @@
+//! The `load` subcommand: floods a node with signed transactions and measures how it copes.
+//!
+//! The senders are accounts derived from one seed, `<SEED>//0`, `<SEED>//1`, …, so that the pool
+//! can include their transactions side by side instead of queueing them behind a single nonce.
+//! They are first topped up by a funder, then every transaction is signed up front, the way the
+//! `benchmark` commands build theirs, and submitted at a fixed rate: over RPC to a running node,
+//! or straight into the transaction pool of a node started in this process. Each transaction is
+//! followed until it lands in the best chain, and the command reports the inclusion latency, the
+//! throughput and the transactions the pool rejected.
+
+use crate::{
+	benchmarking::benchmark_extrinsic_params,
+	extrinsic::{self, ExtrinsicParams},
+	rpc_client::RpcClient,
+	service::{self, FullClient, FullPool, NewFull},
+};
+use codec::Decode;
+use futures::{stream::FuturesUnordered, StreamExt};
+use node_template_runtime::{
+	self as runtime, pallet_template, AccountId, Balance, BalancesCall, BlockNumber, Hash, Index,
+	EXISTENTIAL_DEPOSIT,
+};
+use sc_cli::{Error, Result, SubstrateCli};
+use sc_client_api::{BlockBackend, BlockchainEvents, StorageProvider};
+use sc_transaction_pool_api::{TransactionPool, TransactionSource};
+use serde_json::json;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_core::{sr25519, storage::StorageKey, Pair};
+use sp_runtime::{
+	generic::BlockId,
+	traits::{BlakeTwo256, Hash as _, Header as _},
+	OpaqueExtrinsic,
+};
+use std::{
+	cell::RefCell,
+	collections::{BTreeMap, HashMap},
+	sync::Arc,
+	time::{Duration, Instant},
+};
+use substrate_frame_rpc_system::AccountNonceApi;
+
+/// The account data stored by the runtime, holding the balances.
+type AccountInfo = frame_system::AccountInfo<Index, pallet_balances::AccountData<Balance>>;
+
+/// The call of the load transactions.
+#[derive(Debug, Clone, Copy, clap::ArgEnum)]
+pub enum LoadCall {
+	/// `Balances::transfer_keep_alive` of the existential deposit to the next sender.
+	TransferKeepAlive,
+	/// `TemplateModule::do_something` storing the nonce of the sender.
+	DoSomething,
+}
+
+/// The `load` command used to measure how a node copes with many transactions.
+#[derive(Debug, clap::Parser)]
+pub struct LoadCmd {
+	/// The call of the transactions.
+	#[clap(long, arg_enum, default_value = "transfer-keep-alive")]
+	pub call: LoadCall,
+
+	/// The number of transactions to submit.
+	#[clap(long, default_value = "10000")]
+	pub count: u32,
+
+	/// The number of accounts sending the transactions, taking turns.
+	#[clap(long, default_value = "100")]
+	pub senders: u32,
+
+	/// The secret URI the senders are derived from, as `<SEED>//<INDEX>`.
+	#[clap(long, value_name = "SURI", default_value = "//Load")]
+	pub seed: String,
+
+	/// The secret URI of the account topping up the senders before the load starts.
+	#[clap(long, value_name = "SURI", default_value = "//Alice")]
+	pub funder: String,
+
+	/// The free balance the senders are topped up to.
+	#[clap(long, default_value = "1000000000000000")]
+	pub endowment: Balance,
+
+	/// The number of transactions submitted per second.
+	#[clap(long, value_name = "TPS", default_value = "100")]
+	pub rate: u32,
+
+	/// Seconds to wait for the inclusion of the transactions after the last submission.
+	#[clap(long, value_name = "SECONDS", default_value = "60")]
+	pub timeout: u64,
+
+	/// The WebSocket RPC endpoint of the node to load.
+	#[clap(long, default_value = "ws://127.0.0.1:9944", conflicts_with = "in_process")]
+	pub url: String,
+
+	/// Start a node in this process, configured by the node options, and submit straight into its
+	/// transaction pool instead of over RPC.
+	#[clap(long)]
+	pub in_process: bool,
+
+	/// Print the report as JSON.
+	#[clap(long)]
+	pub json: bool,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub node: crate::cli::RunCmd,
+}
+
+impl LoadCmd {
+	/// Run the command.
+	pub fn run<C: SubstrateCli>(&self, cli: &C) -> Result<()> {
+		if self.rate == 0 || self.senders == 0 {
+			return Err("`--rate` and `--senders` must be positive".into())
+		}
+		// At about a billion per second, the interval between submissions drops below a nanosecond.
+		if submission_interval(self.rate).is_zero() {
+			return Err(format!("`--rate` of {} transactions per second is too high", self.rate)
+				.into())
+		}
+
+		if !self.in_process {
+			return tokio::runtime::Runtime::new()?.block_on(async {
+				let target = Target::Rpc(RpcClient::connect(&self.url).await?);
+				self.load(&target).await
+			})
+		}
+
+		let runner = cli.create_runner(&self.node.base)?;
+		runner.async_run(|config| {
+			let NewFull { task_manager, client, transaction_pool, .. } =
+				service::new_full_base(config, &self.node)?;
+			let target = Target::Pool { client, pool: transaction_pool };
+			Ok((async move { self.load(&target).await }, task_manager))
+		})
+	}
+
+	async fn load(&self, target: &Target) -> Result<()> {
+		let funder = parse_pair(&self.funder)?;
+		let senders = (0..self.senders)
+			.map(|index| parse_pair(&format!("{}//{}", self.seed, index)))
+			.collect::<Result<Vec<_>>>()?;
+		let params = target.extrinsic_params(&funder.public().into()).await?;
+
+		self.fund(target, &funder, &senders, &params).await?;
+
+		eprintln!("Signing {} transactions from {} senders", self.count, senders.len());
+		let started = Instant::now();
+		let extrinsics = self.sign(target, &senders, &params).await?;
+		eprintln!("Signed in {:.1?}", started.elapsed());
+
+		eprintln!("Submitting at {} transactions per second", self.rate);
+		let tracker = self.execute(target, extrinsics).await?;
+		self.report(&tracker)
+	}
+
+	/// Tops up the free balance of every sender to the endowment, and waits for the transfers to
+	/// be included.
+	async fn fund(
+		&self,
+		target: &Target,
+		funder: &sr25519::Pair,
+		senders: &[sr25519::Pair],
+		params: &ExtrinsicParams,
+	) -> Result<()> {
+		let mut transfers = Vec::new();
+		for sender in senders {
+			let account: AccountId = sender.public().into();
+			let free = target.free_balance(&account).await?;
+			if free >= self.endowment {
+				continue
+			}
+			let call = BalancesCall::transfer_keep_alive {
+				dest: account.into(),
+				value: self.endowment - free,
+			};
+			let nonce = params.nonce + transfers.len() as Index;
+			let params = ExtrinsicParams { nonce, ..params.clone() };
+			transfers.push(extrinsic::create_signed(funder, call.into(), &params));
+		}
+		if transfers.is_empty() {
+			return Ok(())
+		}
+
+		eprintln!("Funding {} senders", transfers.len());
+		let tracker = self.execute(target, transfers).await?;
+		if !tracker.rejections.is_empty() || !tracker.pending.is_empty() {
+			return Err(Error::Input(format!(
+				"Failed to fund the senders: {} transfers rejected ({:?}), {} not included",
+				tracker.rejections.values().sum::<usize>(),
+				tracker.rejections,
+				tracker.pending.len(),
+			)))
+		}
+		Ok(())
+	}
+
+	/// Signs the load transactions, the senders taking turns from their next nonce.
+	async fn sign(
+		&self,
+		target: &Target,
+		senders: &[sr25519::Pair],
+		params: &ExtrinsicParams,
+	) -> Result<Vec<runtime::UncheckedExtrinsic>> {
+		let mut next = Vec::with_capacity(senders.len());
+		for sender in senders {
+			let account = sender.public().into();
+			let nonce = target.account_nonce(&account).await?;
+			next.push((nonce, target.template_index(&account).await?));
+		}
+
+		Ok((0..self.count as usize)
+			.map(|i| {
+				let sender = i % senders.len();
+				let (nonce, template_index) = next[sender];
+				let round = (i / senders.len()) as u32;
+				let nonce = nonce + round;
+				let call: runtime::Call = match self.call {
+					LoadCall::TransferKeepAlive => BalancesCall::transfer_keep_alive {
+						dest: AccountId::from(senders[(sender + 1) % senders.len()].public())
+							.into(),
+						value: EXISTENTIAL_DEPOSIT,
+					}
+					.into(),
+					LoadCall::DoSomething =>
+						pallet_template::Call::do_something { something: nonce }.into(),
+				};
+				let params = ExtrinsicParams {
+					nonce,
+					template_index: template_index + round,
+					..params.clone()
+				};
+				extrinsic::create_signed(&senders[sender], call, &params)
+			})
+			.collect())
+	}
+
+	/// Submits `extrinsics` at the target rate while following the best chain, until every
+	/// accepted extrinsic is included or the timeout expires.
+	async fn execute(
+		&self,
+		target: &Target,
+		extrinsics: Vec<runtime::UncheckedExtrinsic>,
+	) -> Result<Tracker> {
+		let tracker = RefCell::new(Tracker::new());
+		futures::try_join!(
+			submit(target, &tracker, extrinsics, self.rate),
+			target.follow(&tracker, Duration::from_secs(self.timeout)),
+		)?;
+		Ok(tracker.into_inner())
+	}
+
+	fn report(&self, tracker: &Tracker) -> Result<()> {
+		let rejected = tracker.rejections.values().sum::<usize>();
+		let included = tracker.latencies.len();
+		let submission = tracker.submission_end.unwrap_or(tracker.start) - tracker.start;
+		let inclusion = tracker.last_inclusion.unwrap_or(tracker.start) - tracker.start;
+		let submission_rate = per_second(tracker.submitted, submission);
+		let throughput = per_second(included, inclusion);
+		let max_per_block = tracker.blocks.iter().copied().max().unwrap_or(0);
+
+		let mut latencies = tracker.latencies.clone();
+		latencies.sort();
+		let percentile = |p: usize| {
+			latencies.get((latencies.len() * p / 100).min(latencies.len().saturating_sub(1)))
+		};
+		let latency = [("min", 0), ("median", 50), ("p95", 95), ("max", 100)]
+			.into_iter()
+			.filter_map(|(name, p)| percentile(p).map(|latency| (name, latency.as_millis() as u64)))
+			.collect::<Vec<_>>();
+
+		if self.json {
+			let output = json!({
+				"submitted": tracker.submitted,
+				"rejected": rejected,
+				"rejections": tracker.rejections,
+				"included": included,
+				"notIncluded": tracker.pending.len(),
+				"submissionSecs": submission.as_secs_f64(),
+				"submissionRate": submission_rate,
+				"throughput": throughput,
+				"blocks": tracker.blocks.len(),
+				"maxPerBlock": max_per_block,
+				"latencyMs": latency.iter().cloned().collect::<BTreeMap<_, _>>(),
+			});
+			println!(
+				"{}",
+				serde_json::to_string_pretty(&output).map_err(|e| Error::Input(e.to_string()))?
+			);
+		} else {
+			println!(
+				"Submitted {} transactions in {:.1?} ({:.1} per second)",
+				tracker.submitted, submission, submission_rate
+			);
+			println!("Rejected {}", rejected);
+			for (error, count) in &tracker.rejections {
+				println!("  {} x {}", count, error);
+			}
+			println!(
+				"Included {} in {} blocks, at most {} per block, not included {}",
+				included,
+				tracker.blocks.len(),
+				max_per_block,
+				tracker.pending.len()
+			);
+			println!("Throughput {:.1} transactions per second", throughput);
+			if !latency.is_empty() {
+				let latency = latency
+					.iter()
+					.map(|(name, ms)| format!("{} {} ms", name, ms))
+					.collect::<Vec<_>>();
+				println!("Inclusion latency: {}", latency.join(", "));
+			}
+		}
+		Ok(())
+	}
+}
+
+/// The node receiving the load.
+enum Target {
+	/// A node reached over RPC.
+	Rpc(RpcClient),
+	/// A node running in this process.
+	Pool { client: Arc<FullClient>, pool: Arc<FullPool> },
+}
+
+impl Target {
+	/// The parameters of a new extrinsic of `signer`, mortal from the best block.
+	async fn extrinsic_params(&self, signer: &AccountId) -> Result<ExtrinsicParams> {
+		match self {
+			Target::Rpc(rpc) => rpc.extrinsic_params(signer).await,
+			Target::Pool { client, .. } =>
+				Ok(benchmark_extrinsic_params(client, self.account_nonce(signer).await?)),
+		}
+	}
+
+	/// The next nonce of `account`.
+	async fn account_nonce(&self, account: &AccountId) -> Result<Index> {
+		match self {
+			Target::Rpc(rpc) => rpc.account_nonce(account).await,
+			Target::Pool { client, .. } => client
+				.runtime_api()
+				.account_nonce(&BlockId::Hash(client.info().best_hash), account.clone())
+				.map_err(|e| Error::Application(Box::new(e))),
+		}
+	}
+
+	/// The index the next template pallet call of `account` must carry.
+	async fn template_index(&self, account: &AccountId) -> Result<u32> {
+		match self {
+			Target::Rpc(rpc) => rpc.template_index(account).await,
+			Target::Pool { .. } => {
+				let key =
+					pallet_template::CallCounters::<runtime::Runtime>::hashed_key_for(account);
+				let counter: pallet_template::CallCounter<BlockNumber> =
+					self.storage(key).await?.unwrap_or_default();
+				Ok(counter.index)
+			},
+		}
+	}
+
+	/// The free balance of `account` at the best block.
+	async fn free_balance(&self, account: &AccountId) -> Result<Balance> {
+		let key = frame_system::Account::<runtime::Runtime>::hashed_key_for(account);
+		let info: AccountInfo = self.storage(key).await?.unwrap_or_default();
+		Ok(info.data.free)
+	}
+
+	/// The decoded value at `key` in the state of the best block.
+	async fn storage<T: Decode>(&self, key: Vec<u8>) -> Result<Option<T>> {
+		let encoded = match self {
+			Target::Rpc(rpc) => rpc.storage(key, None).await?,
+			Target::Pool { client, .. } => client
+				.storage(&BlockId::Hash(client.info().best_hash), &StorageKey(key))
+				.map_err(|e| Error::Application(Box::new(e)))?
+				.map(|data| data.0),
+		};
+		encoded
+			.map(|encoded| T::decode(&mut &encoded[..]))
+			.transpose()
+			.map_err(|e| Error::Input(format!("Failed to decode a storage value: {}", e)))
+	}
+
+	/// Submits `extrinsic` to the pool, returning why the pool rejected it.
+	async fn submit(
+		&self,
+		extrinsic: runtime::UncheckedExtrinsic,
+	) -> std::result::Result<(), String> {
+		match self {
+			Target::Rpc(rpc) => rpc.submit(&extrinsic).await.map(drop).map_err(|e| e.to_string()),
+			Target::Pool { client, pool } => pool
+				.submit_one(
+					&BlockId::Hash(client.info().best_hash),
+					TransactionSource::External,
+					extrinsic.into(),
+				)
+				.await
+				.map(drop)
+				.map_err(|e| e.to_string()),
+		}
+	}
+
+	/// The extrinsics of the block `hash`.
+	async fn block_body(&self, hash: Hash) -> Result<Vec<OpaqueExtrinsic>> {
+		let body = match self {
+			Target::Rpc(rpc) => rpc.block_body(hash).await?,
+			Target::Pool { client, .. } => client
+				.block_body(&BlockId::Hash(hash))
+				.map_err(|e| Error::Application(Box::new(e)))?,
+		};
+		Ok(body.unwrap_or_default())
+	}
+
+	/// Passes the extrinsics of every new best block to `tracker`, until it is done or `timeout`
+	/// after the end of the submission.
+	async fn follow(&self, tracker: &RefCell<Tracker>, timeout: Duration) -> Result<()> {
+		let mut heads = match self {
+			Target::Rpc(rpc) => rpc
+				.new_heads()
+				.await?
+				.map(|header| {
+					header
+						.map(|header| header.hash())
+						.map_err(|e| Error::Application(Box::new(e)))
+				})
+				.boxed(),
+			Target::Pool { client, .. } => client
+				.import_notification_stream()
+				.filter_map(|block| async move {
+					block.is_new_best.then(|| Ok::<_, Error>(block.hash))
+				})
+				.boxed(),
+		};
+		// Wakes the loop up to check the timeout while no block comes in.
+		let mut ticks = tokio::time::interval(Duration::from_millis(500));
+
+		while !tracker.borrow().is_done(timeout) {
+			tokio::select! {
+				hash = heads.next() => match hash {
+					Some(hash) => {
+						let body = self.block_body(hash?).await?;
+						tracker.borrow_mut().include(&body);
+					},
+					None => return Err("The node stopped announcing new blocks".into()),
+				},
+				_ = ticks.tick() => {},
+			}
+		}
+		Ok(())
+	}
+}
+
+/// The interval between two submissions at `rate` per second, zero if too short to represent.
+fn submission_interval(rate: u32) -> Duration {
+	Duration::from_secs_f64(1.0 / rate as f64)
+}
+
+/// Submits `extrinsics` to `target`, `rate` per second, without waiting for the pool to answer
+/// before submitting the next one.
+async fn submit(
+	target: &Target,
+	tracker: &RefCell<Tracker>,
+	extrinsics: Vec<runtime::UncheckedExtrinsic>,
+	rate: u32,
+) -> Result<()> {
+	let mut ticks = tokio::time::interval(submission_interval(rate));
+	let mut extrinsics = extrinsics.into_iter();
+	let mut submissions = FuturesUnordered::new();
+	tracker.borrow_mut().start = Instant::now();
+
+	loop {
+		tokio::select! {
+			_ = ticks.tick() => match extrinsics.next() {
+				Some(extrinsic) => {
+					let hash = BlakeTwo256::hash_of(&extrinsic);
+					tracker.borrow_mut().submitted(hash);
+					submissions.push(async move { (hash, target.submit(extrinsic).await) });
+				},
+				None => break,
+			},
+			Some((hash, result)) = submissions.next(), if !submissions.is_empty() =>
+				tracker.borrow_mut().answered(hash, result),
+		}
+	}
+	while let Some((hash, result)) = submissions.next().await {
+		tracker.borrow_mut().answered(hash, result);
+	}
+
+	tracker.borrow_mut().submission_end = Some(Instant::now());
+	Ok(())
+}
+
+/// Follows the transactions of a run from their submission to their inclusion.
+struct Tracker {
+	/// When the submission started.
+	start: Instant,
+	/// When the pool answered the last submission.
+	submission_end: Option<Instant>,
+	/// The number of submitted transactions.
+	submitted: usize,
+	/// The submission time of the transactions neither rejected nor included yet.
+	pending: HashMap<Hash, Instant>,
+	/// The number of rejected transactions, by error.
+	rejections: BTreeMap<String, usize>,
+	/// The time from submission to inclusion of each included transaction.
+	latencies: Vec<Duration>,
+	/// The number of included transactions in each block that included any.
+	blocks: Vec<usize>,
+	/// When the last transaction was included.
+	last_inclusion: Option<Instant>,
+}
+
+impl Tracker {
+	fn new() -> Self {
+		Self {
+			start: Instant::now(),
+			submission_end: None,
+			submitted: 0,
+			pending: HashMap::new(),
+			rejections: BTreeMap::new(),
+			latencies: Vec::new(),
+			blocks: Vec::new(),
+			last_inclusion: None,
+		}
+	}
+
+	fn submitted(&mut self, hash: Hash) {
+		self.submitted += 1;
+		self.pending.insert(hash, Instant::now());
+	}
+
+	fn answered(&mut self, hash: Hash, result: std::result::Result<(), String>) {
+		if let Err(error) = result {
+			self.pending.remove(&hash);
+			*self.rejections.entry(error).or_default() += 1;
+		}
+	}
+
+	fn include(&mut self, body: &[OpaqueExtrinsic]) {
+		let now = Instant::now();
+		let mut included = 0;
+		for extrinsic in body {
+			if let Some(submitted) = self.pending.remove(&BlakeTwo256::hash_of(extrinsic)) {
+				self.latencies.push(now - submitted);
+				included += 1;
+			}
+		}
+		if included > 0 {
+			self.blocks.push(included);
+			self.last_inclusion = Some(now);
+		}
+	}
+
+	/// Whether every transaction was submitted and is either rejected or included, or `timeout`
+	/// expired since the end of the submission.
+	fn is_done(&self, timeout: Duration) -> bool {
+		self.submission_end
+			.map_or(false, |end| self.pending.is_empty() || end.elapsed() >= timeout)
+	}
+}
+
+fn parse_pair(suri: &str) -> Result<sr25519::Pair> {
+	sr25519::Pair::from_string(suri, None)
+		.map_err(|e| Error::Input(format!("Invalid secret URI {}: {:?}", suri, e)))
+}
+
+fn per_second(count: usize, duration: Duration) -> f64 {
+	if duration.is_zero() {
+		0.0
+	} else {
+		count as f64 / duration.as_secs_f64()
+	}
+}
@@ -0,0 +1,292 @@
+//! Node-side Prometheus metrics derived from the extrinsics and events of imported best blocks.
+
+use crate::{
+	common::{storage_prefix, EventRecords},
+	service::FullClient,
+};
+use codec::{Decode, Encode};
+use frame_metadata::v14::RuntimeMetadataV14;
+use frame_support::dispatch::GetCallMetadata;
+use futures::StreamExt;
+use node_template_runtime::{
+	self as runtime,
+	opaque::{Block, Header},
+};
+use prometheus_endpoint::{
+	register, Counter, CounterVec, Gauge, Opts, PrometheusError, Registry, F64, U64,
+};
+use sc_client_api::{BlockBackend, BlockchainEvents, StorageProvider};
+use sc_consensus_aura::find_pre_digest;
+use scale_info::TypeDef;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_consensus_aura::{
+	sr25519::{AuthorityId as AuraId, AuthoritySignature},
+	AuraApi,
+};
+use sp_core::{
+	crypto::{key_types::AURA, ByteArray},
+	storage::StorageKey,
+};
+use sp_keystore::{SyncCryptoStore, SyncCryptoStorePtr};
+use sp_runtime::{generic::BlockId, traits::Header as _, DispatchError, ModuleError};
+use std::sync::Arc;
+
+const LOG_TARGET: &str = "metrics";
+
+/// The metrics, registered in the node's Prometheus registry.
+#[derive(Clone)]
+pub struct Metrics {
+	extrinsics: CounterVec<U64>,
+	dispatch_errors: CounterVec<U64>,
+	fees: Counter<F64>,
+	tips: Counter<F64>,
+	something: Gauge<U64>,
+	missed_slots: Counter<U64>,
+	own_missed_slots: Counter<U64>,
+}
+
+impl Metrics {
+	/// Create and register the metrics.
+	pub fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+		Ok(Self {
+			extrinsics: register(
+				CounterVec::new(
+					Opts::new(
+						"node_template_extrinsics_total",
+						"Number of extrinsics in imported best blocks, by pallet, call and result",
+					),
+					&["pallet", "call", "result"],
+				)?,
+				registry,
+			)?,
+			dispatch_errors: register(
+				CounterVec::new(
+					Opts::new(
+						"node_template_dispatch_errors_total",
+						"Number of failed dispatches in imported best blocks, by pallet and error",
+					),
+					&["pallet", "error"],
+				)?,
+				registry,
+			)?,
+			fees: register(
+				Counter::new(
+					"node_template_transaction_fees_total",
+					"Total transaction fees paid in imported best blocks, tips included",
+				)?,
+				registry,
+			)?,
+			tips: register(
+				Counter::new(
+					"node_template_transaction_tips_total",
+					"Total tips paid in imported best blocks",
+				)?,
+				registry,
+			)?,
+			something: register(
+				Gauge::new(
+					"node_template_template_something",
+					"The value of `TemplateModule::Something` at the best block, 0 when unset",
+				)?,
+				registry,
+			)?,
+			missed_slots: register(
+				Counter::new(
+					"node_template_aura_missed_slots_total",
+					"Number of Aura slots without a block between imported best blocks",
+				)?,
+				registry,
+			)?,
+			own_missed_slots: register(
+				Counter::new(
+					"node_template_aura_own_missed_slots_total",
+					"Number of missed Aura slots that were assigned to a key of this node",
+				)?,
+				registry,
+			)?,
+		})
+	}
+}
+
+/// Updates `metrics` from every new best block of `client` until the node shuts down. Missed
+/// slots of the node's own keys are only tracked if a `keystore` is given.
+pub async fn run(client: Arc<FullClient>, keystore: Option<SyncCryptoStorePtr>, metrics: Metrics) {
+	let mut metadata: Option<(u32, RuntimeMetadataV14)> = None;
+	let mut imports = client.import_notification_stream();
+
+	while let Some(notification) = imports.next().await {
+		if !notification.is_new_best {
+			continue
+		}
+		let (hash, header) = (notification.hash, notification.header);
+
+		// The client reads block, keep them off the async executor. The metadata goes along and
+		// comes back with the result.
+		let (client, keystore, metrics) = (client.clone(), keystore.clone(), metrics.clone());
+		let mut current = metadata.take();
+		let result = tokio::task::spawn_blocking(move || {
+			let at = BlockId::Hash(hash);
+
+			// Error names are looked up in the metadata, refreshed on runtime upgrades.
+			let spec_version = client.runtime_version_at(&at).map(|v| v.spec_version).ok();
+			if spec_version.is_some() && current.as_ref().map(|(v, _)| *v) != spec_version {
+				current = crate::storage::runtime_metadata(&client, &at)
+					.ok()
+					.zip(spec_version)
+					.map(|(metadata, version)| (version, metadata));
+			}
+
+			if let Err(e) = metrics.on_best_block(
+				&client,
+				keystore.as_ref(),
+				current.as_ref().map(|(_, m)| m),
+				&header,
+			) {
+				log::debug!(target: LOG_TARGET, "Failed to update the metrics: {}", e);
+			}
+			current
+		})
+		.await;
+
+		metadata = match result {
+			Ok(metadata) => metadata,
+			Err(e) => {
+				log::warn!(target: LOG_TARGET, "Failed to update the metrics: {}", e);
+				None
+			},
+		};
+	}
+}
+
+impl Metrics {
+	fn on_best_block(
+		&self,
+		client: &FullClient,
+		keystore: Option<&SyncCryptoStorePtr>,
+		metadata: Option<&RuntimeMetadataV14>,
+		header: &Header,
+	) -> Result<(), Box<dyn std::error::Error>> {
+		let hash = header.hash();
+		let at = BlockId::Hash(hash);
+
+		let events = match client.storage(&at, &StorageKey(storage_prefix("System", "Events")))? {
+			Some(encoded) => EventRecords::decode(&mut &encoded.0[..])?,
+			None => Vec::new(),
+		};
+		let mut results = Vec::new();
+		for record in &events {
+			match &record.event {
+				runtime::Event::System(frame_system::Event::ExtrinsicSuccess { .. }) =>
+					results.push((record.phase.clone(), "success")),
+				runtime::Event::System(frame_system::Event::ExtrinsicFailed {
+					dispatch_error,
+					..
+				}) => {
+					results.push((record.phase.clone(), "failed"));
+					let (pallet, error) = error_labels(metadata, dispatch_error);
+					self.dispatch_errors
+						.with_label_values(&[pallet.as_str(), error.as_str()])
+						.inc();
+				},
+				runtime::Event::TransactionPayment(
+					pallet_transaction_payment::Event::TransactionFeePaid { actual_fee, tip, .. },
+				) => {
+					self.fees.inc_by(*actual_fee as f64);
+					self.tips.inc_by(*tip as f64);
+				},
+				_ => {},
+			}
+		}
+
+		let extrinsics = client.block_body(&at)?.unwrap_or_default();
+		for (index, opaque) in extrinsics.iter().enumerate() {
+			let extrinsic = runtime::UncheckedExtrinsic::decode(&mut &opaque.encode()[..])?;
+			let call = extrinsic.function.get_call_metadata();
+			let phase = frame_system::Phase::ApplyExtrinsic(index as u32);
+			let result = results
+				.iter()
+				.find(|(p, _)| *p == phase)
+				.map_or("unknown", |(_, result)| *result);
+			self.extrinsics
+				.with_label_values(&[call.pallet_name, call.function_name, result])
+				.inc();
+		}
+
+		let something = client
+			.storage(&at, &StorageKey(storage_prefix("TemplateModule", "Something")))?
+			.and_then(|value| u32::decode(&mut &value.0[..]).ok());
+		self.something.set(something.unwrap_or_default().into());
+
+		self.on_slots(client, keystore, header)
+	}
+
+	/// Counts the slots between the parent of `header` and `header` as missed.
+	fn on_slots(
+		&self,
+		client: &FullClient,
+		keystore: Option<&SyncCryptoStorePtr>,
+		header: &Header,
+	) -> Result<(), Box<dyn std::error::Error>> {
+		let parent = match client.header(BlockId::Hash(*header.parent_hash()))? {
+			Some(parent) => parent,
+			None => return Ok(()),
+		};
+		let slot = find_pre_digest::<Block, AuthoritySignature>(header);
+		let parent_slot = find_pre_digest::<Block, AuthoritySignature>(&parent);
+		// The genesis block has no slot.
+		let (slot, parent_slot) = match (slot, parent_slot) {
+			(Ok(slot), Ok(parent_slot)) if *slot > *parent_slot + 1 => (*slot, *parent_slot),
+			_ => return Ok(()),
+		};
+		self.missed_slots.inc_by(slot - parent_slot - 1);
+
+		let keystore = match keystore {
+			Some(keystore) => keystore,
+			None => return Ok(()),
+		};
+		let authorities: Vec<AuraId> =
+			client.runtime_api().authorities(&BlockId::Hash(parent.hash()))?;
+		let count = authorities.len() as u64;
+		for (index, authority) in authorities.iter().enumerate() {
+			if SyncCryptoStore::has_keys(&**keystore, &[(authority.to_raw_vec(), AURA)]) {
+				let index = index as u64;
+				// The slots `s` in `(parent_slot, slot)` with `s % count == index`.
+				let assigned_before =
+					|s: u64| if s > index { (s - index - 1) / count + 1 } else { 0 };
+				self.own_missed_slots
+					.inc_by(assigned_before(slot) - assigned_before(parent_slot + 1));
+			}
+		}
+
+		Ok(())
+	}
+}
+
+/// The pallet and name of a dispatch error, resolved with the metadata for module errors.
+fn error_labels(metadata: Option<&RuntimeMetadataV14>, error: &DispatchError) -> (String, String) {
+	match error {
+		DispatchError::Module(ModuleError { index, error, .. }) => {
+			let pallet = metadata.and_then(|m| m.pallets.iter().find(|p| p.index == *index));
+			let name = metadata.zip(pallet.and_then(|p| p.error.as_ref())).and_then(|(m, e)| {
+				match m.types.resolve(e.ty.id()).map(|ty| ty.type_def()) {
+					Some(TypeDef::Variant(variant)) => variant
+						.variants()
+						.iter()
+						.find(|v| v.index() == error[0])
+						.map(|v| v.name().clone()),
+					_ => None,
+				}
+			});
+			(
+				pallet.map_or_else(|| index.to_string(), |p| p.name.clone()),
+				name.unwrap_or_else(|| error[0].to_string()),
+			)
+		},
+		other => {
+			let debug = format!("{:?}", other);
+			let name = debug.split(|c: char| !c.is_alphanumeric()).next().unwrap_or_default();
+			(String::new(), name.to_string())
+		},
+	}
+}
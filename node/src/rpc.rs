@@ -7,12 +7,17 @@
 
 use std::sync::Arc;
 
+use crate::indexer::Indexer;
 use jsonrpsee::RpcModule;
-use node_template_runtime::{opaque::Block, AccountId, Balance, Index};
+use node_template_runtime::{opaque::Block, AccountId, Balance, Hash, Index};
 use sc_transaction_pool_api::TransactionPool;
 use sp_api::ProvideRuntimeApi;
 use sp_block_builder::BlockBuilder;
 use sp_blockchain::{Error as BlockChainError, HeaderBackend, HeaderMetadata};
+use sp_consensus_aura::{sr25519::AuthorityId as AuraId, AuraApi};
+use sp_finality_grandpa::GrandpaApi;
+use sp_keystore::SyncCryptoStorePtr;
+use sp_session::SessionKeys;
 
 pub use sc_rpc_api::DenyUnsafe;
 
@@ -22,8 +27,14 @@ pub struct FullDeps<C, P> {
 	pub client: Arc<C>,
 	/// Transaction pool instance.
 	pub pool: Arc<P>,
+	/// The options the transaction pool was built with.
+	pub pool_options: sc_transaction_pool::Options,
+	/// The keystore of the node.
+	pub keystore: SyncCryptoStorePtr,
 	/// Whether to deny unsafe calls
 	pub deny_unsafe: DenyUnsafe,
+	/// The block indexer, if enabled.
+	pub indexer: Option<Arc<Indexer>>,
 }
 
 /// Instantiate all full RPC extensions.
@@ -37,16 +48,27 @@ where
 	C::Api: substrate_frame_rpc_system::AccountNonceApi<Block, AccountId, Index>,
 	C::Api: pallet_transaction_payment_rpc::TransactionPaymentRuntimeApi<Block, Balance>,
 	C::Api: BlockBuilder<Block>,
-	P: TransactionPool + 'static,
+	C::Api: AuraApi<Block, AuraId> + GrandpaApi<Block> + SessionKeys<Block>,
+	P: TransactionPool<Block = Block, Hash = Hash> + 'static,
 {
+	use crate::{
+		indexer::{IndexerApiServer, IndexerRpc},
+		session_keys::{SessionKeysRpc, SessionKeysRpcApiServer},
+		tx_pool::{TxPoolApiServer, TxPoolRpc},
+	};
 	use pallet_transaction_payment_rpc::{TransactionPayment, TransactionPaymentApiServer};
 	use substrate_frame_rpc_system::{System, SystemApiServer};
 
 	let mut module = RpcModule::new(());
-	let FullDeps { client, pool, deny_unsafe } = deps;
+	let FullDeps { client, pool, pool_options, keystore, deny_unsafe, indexer } = deps;
 
 	module.merge(System::new(client.clone(), pool.clone(), deny_unsafe).into_rpc())?;
+	module.merge(TxPoolRpc::new(pool, pool_options, deny_unsafe).into_rpc())?;
+	module.merge(SessionKeysRpc::new(client.clone(), keystore, deny_unsafe).into_rpc())?;
 	module.merge(TransactionPayment::new(client).into_rpc())?;
+	if let Some(indexer) = indexer {
+		module.merge(IndexerRpc::new(indexer, deny_unsafe).into_rpc())?;
+	}
 
 	// Extend this RPC with a custom API by using the following syntax.
 	// `YourRpcStruct` should have a reference to a client, which is needed
@@ -0,0 +1,188 @@
+//! A minimal RPC client for the subcommands talking to a running node.
+
+use crate::{
+	common::{storage_prefix, EventRecords},
+	extrinsic::{self, ExtrinsicParams},
+};
+use codec::{Decode, Encode};
+use futures::StreamExt;
+use jsonrpsee::{
+	core::client::{ClientT, Subscription, SubscriptionClientT},
+	rpc_params,
+	ws_client::{WsClient, WsClientBuilder},
+};
+use node_template_runtime as runtime;
+use runtime::{pallet_template, AccountId, BlockNumber, Hash, Header, Index};
+use sc_cli::{Error, Result};
+use sc_transaction_pool_api::TransactionStatus;
+use sp_core::{storage::StorageKey, Bytes};
+use sp_runtime::{generic::SignedBlock, traits::Header as _, OpaqueExtrinsic};
+use sp_version::RuntimeVersion;
+
+/// A WebSocket connection to a node.
+pub struct RpcClient {
+	client: WsClient,
+}
+
+impl RpcClient {
+	/// Connects to the node at `url`.
+	pub async fn connect(url: &str) -> Result<Self> {
+		let client = WsClientBuilder::default()
+			.max_request_body_size(u32::MAX)
+			.build(url)
+			.await
+			.map_err(|e| Error::Input(format!("Failed to connect to {}: {}", url, e)))?;
+		Ok(Self { client })
+	}
+
+	/// The runtime version at the best block.
+	pub async fn runtime_version(&self) -> Result<RuntimeVersion> {
+		self.request("state_getRuntimeVersion", rpc_params![]).await
+	}
+
+	/// The hash of the genesis block.
+	pub async fn genesis_hash(&self) -> Result<Hash> {
+		self.request::<Option<Hash>>("chain_getBlockHash", rpc_params![0u32])
+			.await?
+			.ok_or_else(|| "The node has no genesis block".into())
+	}
+
+	/// The header of the best block.
+	pub async fn best_header(&self) -> Result<Header> {
+		self.request::<Option<Header>>("chain_getHeader", rpc_params![])
+			.await?
+			.ok_or_else(|| "The node has no best block".into())
+	}
+
+	/// The next nonce of `account`, including the transactions in the pool.
+	pub async fn account_nonce(&self, account: &AccountId) -> Result<Index> {
+		self.request("system_accountNextIndex", rpc_params![account.to_string()]).await
+	}
+
+	/// The index the next template pallet call of `account` must carry.
+	pub async fn template_index(&self, account: &AccountId) -> Result<u32> {
+		let key = pallet_template::CallCounters::<runtime::Runtime>::hashed_key_for(account);
+		let counter = match self.storage(key, None).await? {
+			Some(encoded) => pallet_template::CallCounter::<BlockNumber>::decode(&mut &encoded[..])
+				.map_err(|e| Error::Input(format!("Failed to decode the call counter: {}", e)))?,
+			None => Default::default(),
+		};
+		Ok(counter.index)
+	}
+
+	/// The raw value at `key` in the state of the given block, or the best block.
+	pub async fn storage(&self, key: Vec<u8>, at: Option<Hash>) -> Result<Option<Vec<u8>>> {
+		Ok(self
+			.request::<Option<Bytes>>("state_getStorage", rpc_params![StorageKey(key), at])
+			.await?
+			.map(|bytes| bytes.0))
+	}
+
+	/// Calls the runtime API function `method` with the SCALE encoded `data` at the given block, or
+	/// the best block.
+	pub async fn runtime_call<R: Decode>(
+		&self,
+		method: &str,
+		data: Vec<u8>,
+		at: Option<Hash>,
+	) -> Result<R> {
+		let result: Bytes = self.request("state_call", rpc_params![method, Bytes(data), at]).await?;
+		R::decode(&mut &result.0[..])
+			.map_err(|e| Error::Input(format!("Failed to decode the result of {}: {}", method, e)))
+	}
+
+	/// The extrinsics of the given block, if the node knows it.
+	pub async fn block_body(&self, hash: Hash) -> Result<Option<Vec<OpaqueExtrinsic>>> {
+		Ok(self
+			.request::<Option<SignedBlock<runtime::opaque::Block>>>(
+				"chain_getBlock",
+				rpc_params![hash],
+			)
+			.await?
+			.map(|signed| signed.block.extrinsics))
+	}
+
+	/// Subscribes to the headers of the new best blocks.
+	pub async fn new_heads(&self) -> Result<Subscription<Header>> {
+		self.client
+			.subscribe("chain_subscribeNewHeads", rpc_params![], "chain_unsubscribeNewHeads")
+			.await
+			.map_err(rpc_error)
+	}
+
+	/// The events deposited in the given block.
+	pub async fn events(&self, at: Hash) -> Result<EventRecords> {
+		match self.storage(storage_prefix("System", "Events"), Some(at)).await? {
+			Some(encoded) => EventRecords::decode(&mut &encoded[..])
+				.map_err(|e| Error::Input(format!("Failed to decode the events: {}", e))),
+			None => Ok(Vec::new()),
+		}
+	}
+
+	/// Fetches the parameters for a new extrinsic of `signer`, mortal for the longest period the
+	/// runtime allows and with the index of its next template pallet call.
+	pub async fn extrinsic_params(&self, signer: &AccountId) -> Result<ExtrinsicParams> {
+		let version = self.runtime_version().await?;
+		let genesis_hash = self.genesis_hash().await?;
+		let best = self.best_header().await?;
+
+		let mut params = ExtrinsicParams::immortal(
+			genesis_hash,
+			version.spec_version,
+			version.transaction_version,
+			self.account_nonce(signer).await?,
+		)
+		.mortal(extrinsic::max_mortal_period(), *best.number(), best.hash());
+		params.template_index = self.template_index(signer).await?;
+		Ok(params)
+	}
+
+	/// Submits `extrinsic` to the pool.
+	pub async fn submit(&self, extrinsic: &runtime::UncheckedExtrinsic) -> Result<Hash> {
+		self.request("author_submitExtrinsic", rpc_params![Bytes(extrinsic.encode())]).await
+	}
+
+	/// Submits `extrinsic` and waits until it is included in a block, returning that block's hash.
+	pub async fn submit_and_watch(&self, extrinsic: &runtime::UncheckedExtrinsic) -> Result<Hash> {
+		let mut subscription: Subscription<TransactionStatus<Hash, Hash>> = self
+			.client
+			.subscribe(
+				"author_submitAndWatchExtrinsic",
+				rpc_params![Bytes(extrinsic.encode())],
+				"author_unwatchExtrinsic",
+			)
+			.await
+			.map_err(rpc_error)?;
+
+		while let Some(status) = subscription.next().await {
+			match status.map_err(rpc_error)? {
+				TransactionStatus::InBlock(hash) | TransactionStatus::Finalized(hash) =>
+					return Ok(hash),
+				TransactionStatus::Future |
+				TransactionStatus::Ready |
+				TransactionStatus::Broadcast(_) |
+				TransactionStatus::Retracted(_) => continue,
+				status =>
+					return Err(Error::Input(format!(
+						"The transaction was not included: {:?}",
+						status
+					))),
+			}
+		}
+
+		Err("The node closed the subscription before the transaction was included".into())
+	}
+
+	/// Performs a raw request.
+	pub async fn request<R: serde::de::DeserializeOwned>(
+		&self,
+		method: &str,
+		params: Option<jsonrpsee::types::ParamsSer<'_>>,
+	) -> Result<R> {
+		self.client.request(method, params).await.map_err(rpc_error)
+	}
+}
+
+fn rpc_error(error: jsonrpsee::core::Error) -> Error {
+	Error::Application(Box::new(error))
+}
@@ -1,17 +1,22 @@
 //! Service and ServiceFactory implementation. Specialized wrapper over substrate service.
 
+use crate::{
+	chain_spec::{Extensions, LightSyncCheckpoint},
+	checkpoint::CheckpointBlockImport,
+};
 use node_template_runtime::{self, opaque::Block, RuntimeApi};
 use sc_client_api::{BlockBackend, ExecutorProvider};
 use sc_consensus_aura::{ImportQueueParams, SlotProportion, StartAuraParams};
 pub use sc_executor::NativeElseWasmExecutor;
 use sc_finality_grandpa::SharedVoterState;
 use sc_keystore::LocalKeystore;
-use sc_service::{error::Error as ServiceError, Configuration, TaskManager};
+use sc_network::NetworkService;
+use sc_service::{error::Error as ServiceError, ChainType, Configuration, TaskManager};
 use sc_telemetry::{Telemetry, TelemetryWorker};
 use sp_consensus_aura::sr25519::AuthorityPair as AuraPair;
 use std::{sync::Arc, time::Duration};
 
-// Our native executor instance.
+/// Our native executor instance.
 pub struct ExecutorDispatch;
 
 impl sc_executor::NativeExecutionDispatch for ExecutorDispatch {
@@ -31,11 +36,16 @@ impl sc_executor::NativeExecutionDispatch for ExecutorDispatch {
 	}
 }
 
-pub(crate) type FullClient =
+/// The client of a full node, executing the runtime natively when possible.
+pub type FullClient =
 	sc_service::TFullClient<Block, RuntimeApi, NativeElseWasmExecutor<ExecutorDispatch>>;
 type FullBackend = sc_service::TFullBackend<Block>;
 type FullSelectChain = sc_consensus::LongestChain<FullBackend, Block>;
 
+/// The transaction pool of a full node.
+pub type FullPool = sc_transaction_pool::FullPool<Block, FullClient>;
+
+/// Builds the components shared by the full node and the subcommands.
 pub fn new_partial(
 	config: &Configuration,
 ) -> Result<
@@ -46,11 +56,13 @@ pub fn new_partial(
 		sc_consensus::DefaultImportQueue<Block, FullClient>,
 		sc_transaction_pool::FullPool<Block, FullClient>,
 		(
-			sc_finality_grandpa::GrandpaBlockImport<
-				FullBackend,
-				Block,
-				FullClient,
-				FullSelectChain,
+			CheckpointBlockImport<
+				sc_finality_grandpa::GrandpaBlockImport<
+					FullBackend,
+					Block,
+					FullClient,
+					FullSelectChain,
+				>,
 			>,
 			sc_finality_grandpa::LinkHalf<Block, FullClient, FullSelectChain>,
 			Option<Telemetry>,
@@ -103,18 +115,25 @@ pub fn new_partial(
 		client.clone(),
 	);
 
-	let (grandpa_block_import, grandpa_link) = sc_finality_grandpa::block_import(
-		client.clone(),
-		&(client.clone() as Arc<_>),
-		select_chain.clone(),
-		telemetry.as_ref().map(|x| x.handle()),
-	)?;
+	let (grandpa_block_import, grandpa_link) =
+		sc_finality_grandpa::block_import_with_authority_set_hard_forks(
+			client.clone(),
+			&(client.clone() as Arc<_>),
+			select_chain.clone(),
+			crate::chain_spec::grandpa_hard_forks(&*config.chain_spec),
+			telemetry.as_ref().map(|x| x.handle()),
+		)?;
+
+	// Blocks contradicting the light sync checkpoint are rejected whether they are synced or
+	// authored, justifications only reach blocks that made it through.
+	let block_import =
+		CheckpointBlockImport::new(grandpa_block_import.clone(), light_sync_checkpoint(config));
 
 	let slot_duration = sc_consensus_aura::slot_duration(&*client)?;
 
 	let import_queue =
 		sc_consensus_aura::import_queue::<AuraPair, _, _, _, _, _, _>(ImportQueueParams {
-			block_import: grandpa_block_import.clone(),
+			block_import: block_import.clone(),
 			justification_import: Some(Box::new(grandpa_block_import.clone())),
 			client: client.clone(),
 			create_inherent_data_providers: move |_, ()| async move {
@@ -145,10 +164,16 @@ pub fn new_partial(
 		keystore_container,
 		select_chain,
 		transaction_pool,
-		other: (grandpa_block_import, grandpa_link, telemetry),
+		other: (block_import, grandpa_link, telemetry),
 	})
 }
 
+/// The light sync checkpoint of the chain spec of `config`, if any.
+fn light_sync_checkpoint(config: &Configuration) -> Option<LightSyncCheckpoint> {
+	Extensions::try_get(&*config.chain_spec)
+		.and_then(|extensions| extensions.light_sync_checkpoint.clone())
+}
+
 fn remote_keystore(_url: &String) -> Result<Arc<LocalKeystore>, &'static str> {
 	// FIXME: here would the concrete keystore be built,
 	//        must return a concrete type (NOT `LocalKeystore`) that
@@ -156,8 +181,31 @@ fn remote_keystore(_url: &String) -> Result<Arc<LocalKeystore>, &'static str> {
 	Err("Remote Keystore not supported.")
 }
 
+/// The components of a running full node.
+pub struct NewFull {
+	/// The task manager of the node, stopping the node when dropped.
+	pub task_manager: TaskManager,
+	/// The client of the node.
+	pub client: Arc<FullClient>,
+	/// The network service of the node.
+	pub network: Arc<NetworkService<Block, node_template_runtime::Hash>>,
+	/// The transaction pool of the node.
+	pub transaction_pool: Arc<FullPool>,
+}
+
 /// Builds a new service for a full client.
-pub fn new_full(mut config: Configuration) -> Result<TaskManager, ServiceError> {
+pub fn new_full(
+	config: Configuration,
+	run_cmd: &crate::cli::RunCmd,
+) -> Result<TaskManager, ServiceError> {
+	new_full_base(config, run_cmd).map(|NewFull { task_manager, .. }| task_manager)
+}
+
+/// Builds a new service for a full client, returning its components.
+pub fn new_full_base(
+	mut config: Configuration,
+	run_cmd: &crate::cli::RunCmd,
+) -> Result<NewFull, ServiceError> {
 	let sc_service::PartialComponents {
 		client,
 		backend,
@@ -169,6 +217,60 @@ pub fn new_full(mut config: Configuration) -> Result<TaskManager, ServiceError>
 		other: (block_import, grandpa_link, mut telemetry),
 	} = new_partial(&config)?;
 
+	// A database that already followed another chain past the checkpoint cannot be repaired by
+	// rejecting new blocks.
+	if let Some(checkpoint) = light_sync_checkpoint(&config) {
+		checkpoint.verify(&*client).map_err(ServiceError::Other)?;
+	}
+
+	// Authorities of live chains only start with password protected keys.
+	if let sc_service::config::KeystoreConfig::Path { path, password } = &config.keystore {
+		if config.role.is_authority() &&
+			config.chain_spec.chain_type() == ChainType::Live &&
+			!run_cmd.allow_unencrypted_keystore
+		{
+			if password.is_none() {
+				return Err(ServiceError::Other(format!(
+					"The keystore has no password. Set one with `--password-filename` or `{}`, \
+					or pass `--allow-unencrypted-keystore`.",
+					crate::keystore::PASSWORD_ENV
+				)))
+			}
+			crate::keystore::ensure_password_protected(path)
+				.map_err(|e| ServiceError::Other(e.to_string()))?;
+		}
+	}
+
+	// Authorities check their hardware before authoring.
+	let mut can_author = config.role.is_authority();
+	if let Some(hwbench) =
+		can_author.then(|| run_cmd.hardware.benchmark(config.database.path())).flatten()
+	{
+		sc_sysinfo::print_hwbench(&hwbench);
+		let insufficient = run_cmd.hardware.check(&hwbench);
+		for message in &insufficient {
+			log::warn!("⚠️  {}", message);
+		}
+		if !insufficient.is_empty() &&
+			config.chain_spec.chain_type() == ChainType::Live &&
+			!run_cmd.hardware.allow_insufficient_hardware
+		{
+			log::error!(
+				"The hardware does not meet the requirements to author blocks on a live chain. \
+				Pass `--allow-insufficient-hardware` to author anyway."
+			);
+			can_author = false;
+		}
+
+		if let Some(telemetry) = telemetry.as_ref() {
+			task_manager.spawn_handle().spawn(
+				"telemetry_hwbench",
+				None,
+				sc_sysinfo::initialize_hwbench_telemetry(telemetry.handle(), hwbench),
+			);
+		}
+	}
+
 	if let Some(url) = &config.keystore_remote {
 		match remote_keystore(url) {
 			Ok(k) => keystore_container.set_remote_keystore(k),
@@ -191,7 +293,7 @@ pub fn new_full(mut config: Configuration) -> Result<TaskManager, ServiceError>
 	let warp_sync = Arc::new(sc_finality_grandpa::warp_proof::NetworkProvider::new(
 		backend.clone(),
 		grandpa_link.shared_authority_set().clone(),
-		Vec::default(),
+		crate::chain_spec::grandpa_hard_forks(&*config.chain_spec),
 	));
 
 	let (network, system_rpc_tx, network_starter) =
@@ -214,6 +316,17 @@ pub fn new_full(mut config: Configuration) -> Result<TaskManager, ServiceError>
 		);
 	}
 
+	let health = crate::health::Health::new(
+		run_cmd.health.clone(),
+		client.clone(),
+		network.clone(),
+		keystore_container.sync_keystore(),
+		config.role.is_authority(),
+	);
+	if let Some(server) = health.start().map_err(ServiceError::Other)? {
+		task_manager.spawn_handle().spawn("health", None, server);
+	}
+
 	let role = config.role.clone();
 	let force_authoring = config.force_authoring;
 	let backoff_authoring_blocks: Option<()> = None;
@@ -221,13 +334,59 @@ pub fn new_full(mut config: Configuration) -> Result<TaskManager, ServiceError>
 	let enable_grandpa = !config.disable_grandpa;
 	let prometheus_registry = config.prometheus_registry().cloned();
 
+	if let Some(registry) = prometheus_registry.as_ref() {
+		let metrics = crate::metrics::Metrics::register(registry)?;
+		let keystore = role.is_authority().then(|| keystore_container.sync_keystore());
+		task_manager.spawn_handle().spawn(
+			"node-metrics",
+			None,
+			crate::metrics::run(client.clone(), keystore, metrics),
+		);
+	}
+
+	if role.is_authority() {
+		task_manager.spawn_handle().spawn(
+			"session-keys",
+			None,
+			crate::session_keys::monitor(client.clone(), keystore_container.sync_keystore()),
+		);
+	}
+
+	let indexer = match &run_cmd.indexer_db {
+		Some(path) => {
+			let indexer = crate::indexer::Indexer::open(path).map_err(|e| {
+				ServiceError::Other(format!(
+					"Failed to open the indexer database {}: {}",
+					path.display(),
+					e
+				))
+			})?;
+			let indexer = Arc::new(indexer);
+			task_manager.spawn_handle().spawn(
+				"indexer",
+				None,
+				crate::indexer::run(client.clone(), indexer.clone()),
+			);
+			Some(indexer)
+		},
+		None => None,
+	};
+
 	let rpc_extensions_builder = {
 		let client = client.clone();
 		let pool = transaction_pool.clone();
+		let pool_options = config.transaction_pool.clone();
+		let keystore = keystore_container.sync_keystore();
 
 		Box::new(move |deny_unsafe, _| {
-			let deps =
-				crate::rpc::FullDeps { client: client.clone(), pool: pool.clone(), deny_unsafe };
+			let deps = crate::rpc::FullDeps {
+				client: client.clone(),
+				pool: pool.clone(),
+				pool_options: pool_options.clone(),
+				keystore: keystore.clone(),
+				deny_unsafe,
+				indexer: indexer.clone(),
+			};
 			crate::rpc::create_full(deps).map_err(Into::into)
 		})
 	};
@@ -245,11 +404,11 @@ pub fn new_full(mut config: Configuration) -> Result<TaskManager, ServiceError>
 		telemetry: telemetry.as_mut(),
 	})?;
 
-	if role.is_authority() {
+	if can_author {
 		let proposer_factory = sc_basic_authorship::ProposerFactory::new(
 			task_manager.spawn_handle(),
 			client.clone(),
-			transaction_pool,
+			transaction_pool.clone(),
 			prometheus_registry.as_ref(),
 			telemetry.as_ref().map(|x| x.handle()),
 		);
@@ -262,7 +421,7 @@ pub fn new_full(mut config: Configuration) -> Result<TaskManager, ServiceError>
 		let aura = sc_consensus_aura::start_aura::<AuraPair, _, _, _, _, _, _, _, _, _, _, _>(
 			StartAuraParams {
 				slot_duration,
-				client,
+				client: client.clone(),
 				select_chain,
 				block_import,
 				proposer_factory,
@@ -298,7 +457,8 @@ pub fn new_full(mut config: Configuration) -> Result<TaskManager, ServiceError>
 
 	if enable_grandpa {
 		// if the node isn't actively participating in consensus then it doesn't
-		// need a keystore, regardless of which protocol we use below.
+		// need a keystore, regardless of which protocol we use below. Insufficient hardware only
+		// stops authoring, authorities keep voting on finality.
 		let keystore =
 			if role.is_authority() { Some(keystore_container.sync_keystore()) } else { None };
 
@@ -323,7 +483,7 @@ pub fn new_full(mut config: Configuration) -> Result<TaskManager, ServiceError>
 		let grandpa_config = sc_finality_grandpa::GrandpaParams {
 			config: grandpa_config,
 			link: grandpa_link,
-			network,
+			network: network.clone(),
 			voting_rule: sc_finality_grandpa::VotingRulesBuilder::default().build(),
 			prometheus_registry,
 			shared_voter_state: SharedVoterState::empty(),
@@ -340,5 +500,5 @@ pub fn new_full(mut config: Configuration) -> Result<TaskManager, ServiceError>
 	}
 
 	network_starter.start_network();
-	Ok(task_manager)
+	Ok(NewFull { task_manager, client, network, transaction_pool })
 }
@@ -0,0 +1,456 @@
+//! Session key tooling: the `session-keys` subcommands, the `sessionKeys_*` RPC methods and a
+//! task warning authorities whose keystore does not match the authorities on chain.
+//!
+//! The runtime has no session pallet, so the keys registered on chain are the Aura and GRANDPA
+//! authority sets. Both sets are ordered alike, the session keys of the authority at index `i`
+//! being the Aura key at index `i` and the GRANDPA key at index `i`.
+
+use crate::{
+	keystore::{self, KeyType, KeystoreArgs},
+	rpc::DenyUnsafe,
+	rpc_client::RpcClient,
+	service::FullClient,
+};
+use codec::Decode;
+use futures::StreamExt;
+use jsonrpsee::{
+	core::{Error as JsonRpseeError, RpcResult},
+	proc_macros::rpc,
+	rpc_params,
+	types::error::{CallError, ErrorObject},
+};
+use node_template_runtime::{
+	opaque::{Block, SessionKeys},
+	Hash,
+};
+use sc_cli::{Error, KeystoreParams, Result, SubstrateCli};
+use sc_client_api::BlockchainEvents;
+use sc_keystore::LocalKeystore;
+use serde::Serialize;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_consensus_aura::{sr25519::AuthorityId as AuraId, AuraApi};
+use sp_core::{
+	crypto::{key_types, ByteArray, ExposeSecret},
+	Bytes,
+};
+use sp_finality_grandpa::{AuthorityList, GrandpaApi};
+use sp_keystore::{SyncCryptoStore, SyncCryptoStorePtr};
+use sp_runtime::generic::BlockId;
+use sp_session::SessionKeys as SessionKeysApi;
+use std::sync::Arc;
+
+const LOG_TARGET: &str = "session-keys";
+
+/// Session keys, SCALE encoded and decoded.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionKeysInfo {
+	/// The SCALE encoded keys, as expected by the chain.
+	pub keys: Bytes,
+	/// The Aura key.
+	pub aura: String,
+	/// The GRANDPA key.
+	pub grandpa: String,
+}
+
+impl SessionKeysInfo {
+	/// Decodes the SCALE encoded `keys`.
+	pub fn decode(keys: Vec<u8>) -> std::result::Result<Self, codec::Error> {
+		let decoded = SessionKeys::decode(&mut &keys[..])?;
+		Ok(Self {
+			aura: to_hex(decoded.aura.as_slice()),
+			grandpa: to_hex(decoded.grandpa.as_slice()),
+			keys: Bytes(keys),
+		})
+	}
+
+	fn print(&self) {
+		println!("Session keys: {}", to_hex(&self.keys));
+		println!("Aura:         {}", self.aura);
+		println!("GRANDPA:      {}", self.grandpa);
+	}
+}
+
+/// A key of the keystore and whether it is registered on chain.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyStatus {
+	/// The public key.
+	pub public: String,
+	/// The index of the key in the authority set, if it is an authority.
+	pub authority_index: Option<u32>,
+}
+
+/// The keys of the keystore compared with the authorities on chain.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyReport {
+	/// The block the authorities were read at.
+	pub at: Hash,
+	/// The Aura keys of the keystore.
+	pub aura: Vec<KeyStatus>,
+	/// The GRANDPA keys of the keystore.
+	pub grandpa: Vec<KeyStatus>,
+	/// The mismatches between the keystore and the authorities, empty if they match.
+	pub mismatches: Vec<String>,
+}
+
+impl VerifyReport {
+	/// Compares the raw public keys of the keystore with the authority sets at `at`.
+	pub fn new(
+		at: Hash,
+		local_aura: Vec<Vec<u8>>,
+		local_grandpa: Vec<Vec<u8>>,
+		aura_authorities: Vec<AuraId>,
+		grandpa_authorities: AuthorityList,
+	) -> Self {
+		let aura_authorities: Vec<_> = aura_authorities.iter().map(|id| id.to_raw_vec()).collect();
+		let grandpa_authorities: Vec<_> =
+			grandpa_authorities.iter().map(|(id, _)| id.to_raw_vec()).collect();
+		let status = |local: Vec<Vec<u8>>, authorities: &[Vec<u8>]| -> Vec<KeyStatus> {
+			local
+				.iter()
+				.map(|public| KeyStatus {
+					public: to_hex(public),
+					authority_index: authorities
+						.iter()
+						.position(|authority| authority == public)
+						.map(|index| index as u32),
+				})
+				.collect()
+		};
+		let aura = status(local_aura, &aura_authorities);
+		let grandpa = status(local_grandpa, &grandpa_authorities);
+
+		let mut mismatches = Vec::new();
+		for (name, keys) in [("Aura", &aura), ("GRANDPA", &grandpa)] {
+			if keys.is_empty() {
+				mismatches.push(format!("The keystore holds no {} key", name));
+			} else if keys.iter().all(|key| key.authority_index.is_none()) {
+				mismatches.push(format!(
+					"None of the {} {} keys of the keystore is a {} authority",
+					keys.len(),
+					name,
+					name
+				));
+			}
+		}
+
+		// Authorities at the same index of both sets are the same node.
+		if aura_authorities.len() == grandpa_authorities.len() {
+			let indices = |keys: &[KeyStatus]| -> Vec<u32> {
+				keys.iter().filter_map(|key| key.authority_index).collect()
+			};
+			let (aura_indices, grandpa_indices) = (indices(&aura), indices(&grandpa));
+			for index in aura_indices.iter().filter(|index| !grandpa_indices.contains(index)) {
+				mismatches.push(format!(
+					"The keystore holds Aura authority #{} but not GRANDPA authority #{} ({})",
+					index,
+					index,
+					to_hex(&grandpa_authorities[*index as usize])
+				));
+			}
+			for index in grandpa_indices.iter().filter(|index| !aura_indices.contains(index)) {
+				mismatches.push(format!(
+					"The keystore holds GRANDPA authority #{} but not Aura authority #{} ({})",
+					index,
+					index,
+					to_hex(&aura_authorities[*index as usize])
+				));
+			}
+		}
+
+		Self { at, aura, grandpa, mismatches }
+	}
+
+	fn print(&self) {
+		println!("Authorities at block {:?}", self.at);
+		for (name, keys) in [("Aura", &self.aura), ("GRANDPA", &self.grandpa)] {
+			for key in keys {
+				match key.authority_index {
+					Some(index) => println!("{} key {}: authority #{}", name, key.public, index),
+					None => println!("{} key {}: not an authority", name, key.public),
+				}
+			}
+		}
+		for mismatch in &self.mismatches {
+			println!("Mismatch: {}", mismatch);
+		}
+	}
+}
+
+/// The raw Aura and GRANDPA public keys of `keystore`.
+fn local_keys(keystore: &dyn SyncCryptoStore) -> (Vec<Vec<u8>>, Vec<Vec<u8>>) {
+	let aura = SyncCryptoStore::sr25519_public_keys(keystore, key_types::AURA);
+	let grandpa = SyncCryptoStore::ed25519_public_keys(keystore, key_types::GRANDPA);
+	(
+		aura.into_iter().map(|public| public.0.to_vec()).collect(),
+		grandpa.into_iter().map(|public| public.0.to_vec()).collect(),
+	)
+}
+
+/// Compares `keystore` with the authorities of `client` at `at`.
+pub fn verify<C>(
+	client: &C,
+	keystore: &dyn SyncCryptoStore,
+	at: Hash,
+) -> std::result::Result<VerifyReport, sp_api::ApiError>
+where
+	C: ProvideRuntimeApi<Block>,
+	C::Api: AuraApi<Block, AuraId> + GrandpaApi<Block>,
+{
+	let api = client.runtime_api();
+	let id = BlockId::Hash(at);
+	let (local_aura, local_grandpa) = local_keys(keystore);
+	Ok(VerifyReport::new(
+		at,
+		local_aura,
+		local_grandpa,
+		api.authorities(&id)?,
+		api.grandpa_authorities(&id)?,
+	))
+}
+
+/// Logs a warning whenever the keystore stops matching the authorities at the finalized block,
+/// and a message when it matches again.
+pub async fn monitor(client: Arc<FullClient>, keystore: SyncCryptoStorePtr) {
+	let mut last_mismatches = None;
+	let mut finality = client.finality_notification_stream();
+	let mut at = Some(client.info().finalized_hash);
+
+	loop {
+		let hash = match at.take() {
+			Some(hash) => hash,
+			None => match finality.next().await {
+				Some(notification) => notification.hash,
+				None => break,
+			},
+		};
+		let report = match verify(&*client, &*keystore, hash) {
+			Ok(report) => report,
+			Err(e) => {
+				log::debug!(target: LOG_TARGET, "Failed to verify the session keys: {}", e);
+				continue
+			},
+		};
+		if last_mismatches.as_ref() == Some(&report.mismatches) {
+			continue
+		}
+
+		if report.mismatches.is_empty() {
+			log::info!(target: LOG_TARGET, "🔑 The keystore matches the authorities on chain");
+		}
+		for mismatch in &report.mismatches {
+			log::warn!(target: LOG_TARGET, "⚠️  Session keys mismatch: {}", mismatch);
+		}
+		last_mismatches = Some(report.mismatches);
+	}
+}
+
+/// Session key subcommands.
+#[derive(Debug, clap::Subcommand)]
+pub enum SessionKeysSubcommand {
+	/// Generate new session keys and print them SCALE encoded.
+	Rotate(RotateSessionKeysCmd),
+
+	/// Decode SCALE encoded session keys.
+	Decode(DecodeSessionKeysCmd),
+
+	/// Check that the keys of the keystore are the Aura and GRANDPA keys of an authority on chain.
+	Verify(VerifySessionKeysCmd),
+}
+
+impl SessionKeysSubcommand {
+	/// The keystore options of the command.
+	pub fn keystore_params_mut(&mut self) -> Option<&mut KeystoreParams> {
+		match self {
+			SessionKeysSubcommand::Rotate(cmd) => Some(&mut cmd.keystore.keystore_params),
+			SessionKeysSubcommand::Decode(_) => None,
+			SessionKeysSubcommand::Verify(cmd) => Some(&mut cmd.keystore.keystore_params),
+		}
+	}
+
+	/// Run the command.
+	pub fn run<C: SubstrateCli>(&self, cli: &C) -> Result<()> {
+		match self {
+			SessionKeysSubcommand::Rotate(cmd) => cmd.run(cli),
+			SessionKeysSubcommand::Decode(cmd) => cmd.run(),
+			SessionKeysSubcommand::Verify(cmd) => cmd.run(cli),
+		}
+	}
+}
+
+/// The `session-keys rotate` command.
+#[derive(Debug, Clone, clap::Parser)]
+pub struct RotateSessionKeysCmd {
+	/// Generate the keys in the keystore of the running node at this URL, with
+	/// `author_rotateKeys`, instead of in the local keystore. The node must allow unsafe RPC
+	/// methods.
+	#[clap(long, value_name = "URL")]
+	pub url: Option<String>,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub keystore: KeystoreArgs,
+}
+
+impl RotateSessionKeysCmd {
+	/// Run the command.
+	pub fn run<C: SubstrateCli>(&self, cli: &C) -> Result<()> {
+		let keys = match &self.url {
+			Some(url) => tokio::runtime::Runtime::new()?.block_on(async {
+				let client = RpcClient::connect(url).await?;
+				client.request::<Bytes>("author_rotateKeys", rpc_params![]).await
+			})?,
+			None => {
+				let (path, password) = self.keystore.open(cli)?;
+				let keystore = LocalKeystore::open(&path, password.clone())?;
+				let password = password.as_ref().map(|p| p.expose_secret().as_str());
+				let mut keys = keystore::generate(&keystore, KeyType::Aura, password)?;
+				keys.extend(keystore::generate(&keystore, KeyType::Gran, password)?);
+				Bytes(keys)
+			},
+		};
+
+		SessionKeysInfo::decode(keys.0)
+			.map_err(|e| Error::Input(format!("Invalid session keys: {}", e)))?
+			.print();
+		Ok(())
+	}
+}
+
+/// The `session-keys decode` command.
+#[derive(Debug, Clone, clap::Parser)]
+pub struct DecodeSessionKeysCmd {
+	/// The hex encoded session keys.
+	#[clap(value_name = "KEYS")]
+	pub keys: String,
+}
+
+impl DecodeSessionKeysCmd {
+	/// Run the command.
+	pub fn run(&self) -> Result<()> {
+		let keys =
+			hex::decode(self.keys.trim_start_matches("0x")).map_err(|_| Error::HexDataConversion)?;
+		SessionKeysInfo::decode(keys)
+			.map_err(|e| Error::Input(format!("Invalid session keys: {}", e)))?
+			.print();
+		Ok(())
+	}
+}
+
+/// The `session-keys verify` command.
+#[derive(Debug, Clone, clap::Parser)]
+pub struct VerifySessionKeysCmd {
+	/// The URL of a node of the chain, to read the authorities from.
+	#[clap(long, value_name = "URL", default_value = "ws://127.0.0.1:9944")]
+	pub url: String,
+
+	/// Print the report as JSON.
+	#[clap(long)]
+	pub json: bool,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub keystore: KeystoreArgs,
+}
+
+impl VerifySessionKeysCmd {
+	/// Run the command. Fails if the keystore does not match the authorities.
+	pub fn run<C: SubstrateCli>(&self, cli: &C) -> Result<()> {
+		let (path, password) = self.keystore.open(cli)?;
+		let keystore = LocalKeystore::open(&path, password)?;
+		let (local_aura, local_grandpa) = local_keys(&keystore);
+
+		let report = tokio::runtime::Runtime::new()?.block_on(async {
+			let client = RpcClient::connect(&self.url).await?;
+			let at = client.request::<Hash>("chain_getFinalizedHead", rpc_params![]).await?;
+			let aura = client.runtime_call("AuraApi_authorities", Vec::new(), Some(at)).await?;
+			let grandpa = client
+				.runtime_call("GrandpaApi_grandpa_authorities", Vec::new(), Some(at))
+				.await?;
+			Ok::<_, Error>(VerifyReport::new(at, local_aura, local_grandpa, aura, grandpa))
+		})?;
+
+		if self.json {
+			let json =
+				serde_json::to_string_pretty(&report).map_err(|e| Error::Input(e.to_string()))?;
+			println!("{}", json);
+		} else {
+			report.print();
+		}
+		if !report.mismatches.is_empty() {
+			return Err("The keystore does not match the authorities on chain".into())
+		}
+		Ok(())
+	}
+}
+
+/// Session key management over RPC. All methods are unsafe.
+#[rpc(server)]
+pub trait SessionKeysRpcApi {
+	/// Generates new session keys in the keystore of the node and returns them.
+	#[method(name = "sessionKeys_rotate")]
+	fn rotate(&self) -> RpcResult<SessionKeysInfo>;
+
+	/// Compares the keystore of the node with the authorities at the given block, or the best
+	/// block.
+	#[method(name = "sessionKeys_verify")]
+	fn verify(&self, at: Option<Hash>) -> RpcResult<VerifyReport>;
+}
+
+/// Error code of the session keys RPC.
+const SESSION_KEYS_ERROR: i32 = 2;
+
+/// The RPC handler of the session key methods.
+pub struct SessionKeysRpc<C> {
+	client: Arc<C>,
+	keystore: SyncCryptoStorePtr,
+	deny_unsafe: DenyUnsafe,
+}
+
+impl<C> SessionKeysRpc<C> {
+	/// Create a new instance of the session keys RPC handler.
+	pub fn new(client: Arc<C>, keystore: SyncCryptoStorePtr, deny_unsafe: DenyUnsafe) -> Self {
+		Self { client, keystore, deny_unsafe }
+	}
+}
+
+impl<C> SessionKeysRpcApiServer for SessionKeysRpc<C>
+where
+	C: ProvideRuntimeApi<Block> + HeaderBackend<Block> + Send + Sync + 'static,
+	C::Api: AuraApi<Block, AuraId> + GrandpaApi<Block> + SessionKeysApi<Block>,
+{
+	fn rotate(&self) -> RpcResult<SessionKeysInfo> {
+		self.deny_unsafe.check_if_safe()?;
+
+		let best = BlockId::Hash(self.client.info().best_hash);
+		let keys = self
+			.client
+			.runtime_api()
+			.generate_session_keys(&best, None)
+			.map_err(|e| rpc_error("Failed to generate the session keys", e))?;
+		SessionKeysInfo::decode(keys).map_err(|e| rpc_error("Invalid session keys", e))
+	}
+
+	fn verify(&self, at: Option<Hash>) -> RpcResult<VerifyReport> {
+		self.deny_unsafe.check_if_safe()?;
+
+		let at = at.unwrap_or_else(|| self.client.info().best_hash);
+		verify(&*self.client, &*self.keystore, at)
+			.map_err(|e| rpc_error("Failed to read the authorities", e))
+	}
+}
+
+fn rpc_error(message: &str, error: impl std::fmt::Display) -> JsonRpseeError {
+	JsonRpseeError::Call(CallError::Custom(ErrorObject::owned(
+		SESSION_KEYS_ERROR,
+		message,
+		Some(error.to_string()),
+	)))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+	format!("0x{}", hex::encode(bytes))
+}
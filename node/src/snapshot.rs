@@ -0,0 +1,460 @@
+//! The `snapshot` subcommands: check runtime upgrades against the state of the local database.
+//!
+//! `create` writes the state of a block of the local database to a snapshot file. The other
+//! commands load a snapshot in memory and run a runtime built with the `try-runtime` feature on it,
+//! so that neither a running node nor an RPC endpoint is needed:
+//!
+//! - `on-runtime-upgrade` runs the migrations of the runtime and reports on each of them,
+//! - `follow-chain` executes the blocks of the local database that follow the snapshot, upgrading
+//!   the runtime at the first of them.
+
+use crate::service::{self, ExecutorDispatch, FullClient};
+use codec::{Decode, Encode};
+use node_template_runtime::{
+	migrations::{MigrationReport, UpgradeReport},
+	BlockNumber, Hash, Weight,
+};
+use sc_cli::{
+	CliConfiguration, DatabaseParams, Error, ImportParams, PruningParams, Result, SharedParams,
+	SubstrateCli,
+};
+use sc_client_api::{BlockBackend, StorageProvider};
+use sc_executor::NativeElseWasmExecutor;
+use sc_service::{Configuration, PartialComponents};
+use serde_json::json;
+use sp_blockchain::HeaderBackend;
+use sp_core::{
+	storage::{well_known_keys, ChildInfo, StateVersion, Storage, StorageChild, StorageKey},
+	testing::TaskExecutor,
+};
+use sp_externalities::Extensions;
+use sp_runtime::{
+	generic::BlockId,
+	traits::{BlakeTwo256, Header as _},
+};
+use sp_state_machine::{
+	backend::BackendRuntimeCode, ExecutionStrategy, InMemoryBackend, OverlayedChanges,
+	StateMachine,
+};
+use std::{fs, path::PathBuf};
+
+/// The state of a block, as written by `snapshot create`.
+#[derive(Encode, Decode)]
+struct Snapshot {
+	block_hash: Hash,
+	block_number: BlockNumber,
+	/// The state version of the runtime at the block.
+	state_version: u8,
+	top: Vec<(Vec<u8>, Vec<u8>)>,
+	/// The default child tries, by their storage key.
+	children: Vec<(Vec<u8>, Vec<(Vec<u8>, Vec<u8>)>)>,
+}
+
+impl Snapshot {
+	fn read(path: &PathBuf) -> Result<Self> {
+		let encoded = fs::read(path)?;
+		Self::decode(&mut &encoded[..])
+			.map_err(|e| Error::Input(format!("{} is not a snapshot: {}", path.display(), e)))
+	}
+
+	/// The in-memory state of the snapshot, running `code` instead of its runtime.
+	fn into_state(self, code: Vec<u8>) -> Result<State> {
+		let state_version = StateVersion::try_from(self.state_version).map_err(|_| {
+			Error::Input(format!("Unknown state version {} in the snapshot", self.state_version))
+		})?;
+		let mut storage = Storage {
+			top: self.top.into_iter().collect(),
+			children_default: self
+				.children
+				.into_iter()
+				.map(|(storage_key, data)| {
+					let child_info = ChildInfo::new_default(&storage_key);
+					(storage_key, StorageChild { data: data.into_iter().collect(), child_info })
+				})
+				.collect(),
+		};
+		storage.top.insert(well_known_keys::CODE.to_vec(), code);
+
+		Ok(State {
+			backend: (storage, state_version).into(),
+			state_version,
+			block_hash: self.block_hash,
+			block_number: self.block_number,
+		})
+	}
+}
+
+/// The state of the snapshot, as updated by the commands.
+struct State {
+	backend: InMemoryBackend<BlakeTwo256>,
+	state_version: StateVersion,
+	block_hash: Hash,
+	block_number: BlockNumber,
+}
+
+impl State {
+	/// Calls the runtime API `method` in wasm, returning its changes and its SCALE encoded result.
+	fn call(
+		&self,
+		executor: &NativeElseWasmExecutor<ExecutorDispatch>,
+		method: &str,
+		data: &[u8],
+	) -> Result<(OverlayedChanges, Vec<u8>)> {
+		let runtime_code = BackendRuntimeCode::new(&self.backend)
+			.runtime_code()
+			.map_err(|e| Error::Input(e.to_string()))?;
+		let mut changes = OverlayedChanges::default();
+		let result = StateMachine::new(
+			&self.backend,
+			&mut changes,
+			executor,
+			method,
+			data,
+			Extensions::default(),
+			&runtime_code,
+			TaskExecutor::new(),
+		)
+		.execute(ExecutionStrategy::AlwaysWasm)
+		.map_err(|e| {
+			Error::Input(format!(
+				"Calling {} failed, was the runtime built with the `try-runtime` feature? {}",
+				method, e
+			))
+		})?;
+		Ok((changes, result))
+	}
+
+	/// Applies `changes` to the state.
+	fn commit(&mut self, mut changes: OverlayedChanges) -> Result<()> {
+		let changes = changes
+			.drain_storage_changes(&self.backend, &mut Default::default(), self.state_version)
+			.map_err(|e| Error::Input(e.to_string()))?;
+		self.backend.apply_transaction(changes.transaction_storage_root, changes.transaction);
+		Ok(())
+	}
+}
+
+/// The wasm executor configured by the command line, as the node's.
+fn executor(config: &Configuration) -> NativeElseWasmExecutor<ExecutorDispatch> {
+	NativeElseWasmExecutor::new(
+		config.wasm_method,
+		config.default_heap_pages,
+		config.max_runtime_instances,
+		config.runtime_cache_size,
+	)
+}
+
+/// The runtime run on a snapshot.
+#[derive(Debug, Clone, clap::Args)]
+pub struct RuntimeParams {
+	/// The wasm blob of the runtime to run, built with the `try-runtime` feature. Defaults to the
+	/// runtime of the node, which only has the `try-runtime` APIs if the node was built with
+	/// `--features try-runtime`.
+	#[clap(long, value_name = "PATH")]
+	pub runtime: Option<PathBuf>,
+}
+
+impl RuntimeParams {
+	fn code(&self) -> Result<Vec<u8>> {
+		match &self.runtime {
+			Some(path) => Ok(fs::read(path)?),
+			None => node_template_runtime::WASM_BINARY.map(|code| code.to_vec()).ok_or_else(|| {
+				Error::Input("The node was built without a wasm runtime, pass --runtime".into())
+			}),
+		}
+	}
+}
+
+/// Snapshot subcommands.
+#[derive(Debug, clap::Subcommand)]
+pub enum SnapshotSubcommand {
+	/// Write the state of a block of the local database to a snapshot file.
+	Create(CreateSnapshotCmd),
+
+	/// Run the migrations of a runtime on a snapshot and report on each of them.
+	OnRuntimeUpgrade(OnRuntimeUpgradeCmd),
+
+	/// Execute the blocks of the local database that follow a snapshot with a runtime.
+	FollowChain(FollowChainCmd),
+}
+
+impl SnapshotSubcommand {
+	/// Run the command.
+	pub fn run<C: SubstrateCli>(&self, cli: &C) -> Result<()> {
+		match self {
+			SnapshotSubcommand::Create(cmd) => {
+				let runner = cli.create_runner(cmd)?;
+				runner.sync_run(|config| {
+					let PartialComponents { client, .. } = service::new_partial(&config)?;
+					cmd.run(&client)
+				})
+			},
+			SnapshotSubcommand::OnRuntimeUpgrade(cmd) => {
+				let runner = cli.create_runner(cmd)?;
+				runner.sync_run(|config| cmd.run(&config))
+			},
+			SnapshotSubcommand::FollowChain(cmd) => {
+				let runner = cli.create_runner(cmd)?;
+				runner.sync_run(|config| {
+					let PartialComponents { client, .. } = service::new_partial(&config)?;
+					cmd.run(&config, &client)
+				})
+			},
+		}
+	}
+}
+
+/// The `snapshot create` command.
+#[derive(Debug, Clone, clap::Parser)]
+pub struct CreateSnapshotCmd {
+	/// The file to write the snapshot to.
+	#[clap(value_name = "PATH")]
+	pub path: PathBuf,
+
+	/// The hash or number of the block whose state to write. Defaults to the best block.
+	#[clap(long, value_name = "HASH_OR_NUMBER")]
+	pub at: Option<String>,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub shared_params: SharedParams,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub pruning_params: PruningParams,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub database_params: DatabaseParams,
+}
+
+impl CreateSnapshotCmd {
+	/// Run the command.
+	pub fn run(&self, client: &FullClient) -> Result<()> {
+		let block_hash = crate::storage::block_hash(client, self.at.as_deref())?;
+		let at = BlockId::Hash(block_hash);
+		let block_number = client
+			.number(block_hash)?
+			.ok_or_else(|| Error::Input(format!("Block {:?} is not in the database", block_hash)))?;
+		let state_version = client.runtime_version_at(&at)?.state_version();
+
+		let child_prefix = well_known_keys::DEFAULT_CHILD_STORAGE_KEY_PREFIX;
+		let mut top = Vec::new();
+		let mut children = Vec::new();
+		for (StorageKey(key), data) in client.storage_pairs(&at, &StorageKey(Vec::new()))? {
+			// Child tries are stored apart, the backend computes their roots itself.
+			if !key.starts_with(child_prefix) {
+				top.push((key, data.0));
+				continue
+			}
+			let storage_key = key[child_prefix.len()..].to_vec();
+			let child_info = ChildInfo::new_default(&storage_key);
+			let mut data = Vec::new();
+			for key in client.child_storage_keys(&at, &child_info, &StorageKey(Vec::new()))? {
+				if let Some(value) = client.child_storage(&at, &child_info, &key)? {
+					data.push((key.0, value.0));
+				}
+			}
+			children.push((storage_key, data));
+		}
+
+		let snapshot = Snapshot {
+			block_hash,
+			block_number,
+			state_version: state_version.into(),
+			top,
+			children,
+		};
+		let encoded = snapshot.encode();
+		fs::write(&self.path, &encoded)?;
+		println!(
+			"Wrote the state of block #{} ({:?}) to {}: {} keys, {} child tries, {} bytes",
+			block_number,
+			block_hash,
+			self.path.display(),
+			snapshot.top.len(),
+			snapshot.children.len(),
+			encoded.len(),
+		);
+		Ok(())
+	}
+}
+
+impl CliConfiguration for CreateSnapshotCmd {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+
+	fn pruning_params(&self) -> Option<&PruningParams> {
+		Some(&self.pruning_params)
+	}
+
+	fn database_params(&self) -> Option<&DatabaseParams> {
+		Some(&self.database_params)
+	}
+}
+
+/// The `snapshot on-runtime-upgrade` command.
+#[derive(Debug, Clone, clap::Parser)]
+pub struct OnRuntimeUpgradeCmd {
+	/// The snapshot written by `snapshot create`.
+	#[clap(long, value_name = "PATH")]
+	pub snapshot: PathBuf,
+
+	/// Print the report as JSON.
+	#[clap(long)]
+	pub json: bool,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub runtime_params: RuntimeParams,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub shared_params: SharedParams,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub import_params: ImportParams,
+}
+
+impl OnRuntimeUpgradeCmd {
+	/// Run the command.
+	pub fn run(&self, config: &Configuration) -> Result<()> {
+		let state = Snapshot::read(&self.snapshot)?.into_state(self.runtime_params.code()?)?;
+		let (_, encoded) = state.call(&executor(config), "TryMigrationsApi_try_upgrade", &[])?;
+		let report = UpgradeReport::decode(&mut &encoded[..])
+			.map_err(|e| Error::Input(format!("Invalid upgrade report: {}", e)))?;
+
+		if self.json {
+			let output = json!({
+				"block": format!("{:?}", state.block_hash),
+				"number": state.block_number,
+				"migrations": report.migrations.iter().map(|migration| json!({
+					"name": name(migration),
+					"weight": migration.weight,
+					"preUpgrade": check_error(&migration.pre_upgrade),
+					"postUpgrade": check_error(&migration.post_upgrade),
+				})).collect::<Vec<_>>(),
+				"weight": report.weight,
+				"maxWeight": report.max_weight,
+				"ok": report.is_ok(),
+			});
+			println!(
+				"{}",
+				serde_json::to_string_pretty(&output).map_err(|e| Error::Input(e.to_string()))?
+			);
+		} else {
+			println!("Runtime upgrade at block #{} ({:?})", state.block_number, state.block_hash);
+			for migration in &report.migrations {
+				let outcome = match migration.failed_check() {
+					None => "ok".to_string(),
+					Some((check, error)) =>
+						format!("{} failed: {}", check, String::from_utf8_lossy(error)),
+				};
+				println!("  {}: weight {}, {}", name(migration), migration.weight, outcome);
+			}
+			println!(
+				"Total weight {} of a maximum of {} per block",
+				report.weight, report.max_weight
+			);
+		}
+
+		if report.is_ok() {
+			Ok(())
+		} else {
+			Err("The runtime upgrade failed its checks".into())
+		}
+	}
+}
+
+impl CliConfiguration for OnRuntimeUpgradeCmd {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+
+	fn import_params(&self) -> Option<&ImportParams> {
+		Some(&self.import_params)
+	}
+}
+
+fn name(migration: &MigrationReport) -> String {
+	String::from_utf8_lossy(&migration.name).into_owned()
+}
+
+fn check_error(result: &std::result::Result<(), Vec<u8>>) -> Option<String> {
+	result.as_ref().err().map(|error| String::from_utf8_lossy(error).into_owned())
+}
+
+/// The `snapshot follow-chain` command.
+#[derive(Debug, Clone, clap::Parser)]
+pub struct FollowChainCmd {
+	/// The snapshot written by `snapshot create`, of a block of the local database.
+	#[clap(long, value_name = "PATH")]
+	pub snapshot: PathBuf,
+
+	/// The number of the last block to execute. Defaults to the last finalized block.
+	#[clap(long, value_name = "NUMBER")]
+	pub to: Option<BlockNumber>,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub runtime_params: RuntimeParams,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub shared_params: SharedParams,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub import_params: ImportParams,
+}
+
+impl FollowChainCmd {
+	/// Run the command.
+	pub fn run(&self, config: &Configuration, client: &FullClient) -> Result<()> {
+		let mut state = Snapshot::read(&self.snapshot)?.into_state(self.runtime_params.code()?)?;
+		let executor = executor(config);
+		let to = self.to.unwrap_or_else(|| client.info().finalized_number);
+
+		for number in state.block_number + 1..=to {
+			let block = client
+				.block(&BlockId::Number(number))?
+				.ok_or_else(|| Error::Input(format!("Block #{} is not in the database", number)))?
+				.block;
+			if block.header.parent_hash != state.block_hash {
+				return Err(Error::Input(format!(
+					"Block #{} does not follow the snapshot's chain, was the snapshot taken from \
+					 another database?",
+					number
+				)))
+			}
+
+			let (changes, encoded) =
+				state.call(&executor, "TryRuntime_execute_block_no_check", &block.encode())?;
+			let weight = Weight::decode(&mut &encoded[..])
+				.map_err(|e| Error::Input(format!("Invalid block weight: {}", e)))?;
+			state.commit(changes)?;
+			state.block_hash = block.header.hash();
+			state.block_number = number;
+			println!(
+				"Executed block #{} ({:?}): {} extrinsics, weight {}",
+				number,
+				state.block_hash,
+				block.extrinsics.len(),
+				weight
+			);
+		}
+		Ok(())
+	}
+}
+
+impl CliConfiguration for FollowChainCmd {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+
+	fn import_params(&self) -> Option<&ImportParams> {
+		Some(&self.import_params)
+	}
+}
@@ -0,0 +1,618 @@
+//! The `storage` subcommand: read the state of the local database at a given block.
+//!
+//! Storage items are looked up by pallet and item name in the runtime metadata of the block, which
+//! also gives the hashers of map keys and the types used to decode keys and values. Raw keys and
+//! prefixes can be read as well, in which case values are printed as hex.
+
+use crate::{common::parse_public, service::FullClient};
+use codec::{Compact, Decode, Encode, Input};
+use frame_metadata::{
+	v14::{RuntimeMetadataV14, StorageEntryMetadata, StorageEntryType, StorageHasher},
+	RuntimeMetadata, RuntimeMetadataPrefixed,
+};
+use node_template_runtime::opaque::Block;
+use sc_cli::{CliConfiguration, DatabaseParams, Error, PruningParams, Result, SharedParams};
+use sc_client_api::StorageProvider;
+use scale_info::{form::PortableForm, PortableRegistry, TypeDef, TypeDefPrimitive};
+use serde_json::{json, Value};
+use sp_api::{Metadata as _, ProvideRuntimeApi};
+use sp_blockchain::HeaderBackend;
+use sp_core::{
+	blake2_128, blake2_256, sr25519,
+	storage::{StorageData, StorageKey},
+	twox_128, twox_256, twox_64, H256,
+};
+use sp_runtime::{generic::BlockId, AccountId32};
+use std::{str::FromStr, sync::Arc};
+
+/// The `storage` command used to inspect the state stored in the database.
+#[derive(Debug, Clone, clap::Parser)]
+pub struct StorageCmd {
+	/// The pallet of the storage item, e.g. `System`, `Balances` or `TemplateModule`.
+	#[clap(value_name = "PALLET", required_unless_present_any = &["raw_key", "raw_prefix"])]
+	pub pallet: Option<String>,
+
+	/// The name of the storage item, e.g. `Account`, `TotalIssuance` or `Something`.
+	#[clap(value_name = "ITEM", requires = "pallet")]
+	pub item: Option<String>,
+
+	/// A key of a map item, as a public key or secret URI for accounts, a number for integers, or
+	/// the `0x`-prefixed SCALE encoding for any type. Without keys, all entries are listed.
+	#[clap(long = "map-key", value_name = "KEY", requires = "item")]
+	pub map_keys: Vec<String>,
+
+	/// A raw `0x`-prefixed storage key to read.
+	#[clap(long, value_name = "HEX", conflicts_with_all = &["pallet", "raw_prefix"])]
+	pub raw_key: Option<String>,
+
+	/// A raw `0x`-prefixed key prefix whose entries to list.
+	#[clap(long, value_name = "HEX", conflicts_with = "pallet")]
+	pub raw_prefix: Option<String>,
+
+	/// The hash or number of the block to read the state at. Defaults to the best block.
+	#[clap(long, value_name = "HASH_OR_NUMBER")]
+	pub at: Option<String>,
+
+	/// The maximum number of entries to list.
+	#[clap(long, default_value = "100")]
+	pub limit: usize,
+
+	/// Print the result as JSON.
+	#[clap(long)]
+	pub json: bool,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub shared_params: SharedParams,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub pruning_params: PruningParams,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub database_params: DatabaseParams,
+}
+
+/// A storage entry read from the database.
+struct Entry {
+	key: Vec<u8>,
+	/// The map keys of the entry, when the hashers allow recovering them.
+	decoded_key: Option<Vec<Value>>,
+	/// The value, or `None` if it is not stored and the item has no default.
+	value: Option<Value>,
+}
+
+impl StorageCmd {
+	/// Run the command.
+	pub fn run(&self, client: Arc<FullClient>) -> Result<()> {
+		let hash = self.block_hash(&client)?;
+		let at = BlockId::Hash(hash);
+
+		let entries = if let Some(key) = &self.raw_key {
+			let key = parse_hex(key)?;
+			let value = client.storage(&at, &StorageKey(key.clone()))?;
+			vec![Entry { key, decoded_key: None, value: value.map(|v| hex_value(&v.0)) }]
+		} else if let Some(prefix) = &self.raw_prefix {
+			self.pairs(&client, &at, parse_hex(prefix)?)?
+				.into_iter()
+				.map(|(key, value)| Entry {
+					key: key.0,
+					decoded_key: None,
+					value: Some(hex_value(&value.0)),
+				})
+				.collect()
+		} else {
+			self.read_item(&client, &at)?
+		};
+
+		if self.json {
+			let entries: Vec<Value> = entries
+				.iter()
+				.map(|entry| {
+					json!({
+						"key": format!("0x{}", hex::encode(&entry.key)),
+						"decodedKey": entry.decoded_key,
+						"value": entry.value,
+					})
+				})
+				.collect();
+			let output = json!({ "block": format!("{:?}", hash), "entries": entries });
+			println!(
+				"{}",
+				serde_json::to_string_pretty(&output).map_err(|e| Error::Input(e.to_string()))?
+			);
+		} else {
+			println!("At block {:?}", hash);
+			for entry in &entries {
+				let key = match &entry.decoded_key {
+					Some(keys) if !keys.is_empty() => Value::Array(keys.clone()).to_string(),
+					_ => format!("0x{}", hex::encode(&entry.key)),
+				};
+				match &entry.value {
+					Some(value) => println!("{} => {}", key, value),
+					None => println!("{} => <none>", key),
+				}
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Resolves `--at` to a block hash.
+	fn block_hash(&self, client: &FullClient) -> Result<H256> {
+		block_hash(client, self.at.as_deref())
+	}
+
+	/// Reads the storage item named by `PALLET` and `ITEM`, decoding it with the runtime metadata.
+	fn read_item(&self, client: &FullClient, at: &BlockId<Block>) -> Result<Vec<Entry>> {
+		let metadata = runtime_metadata(client, at)?;
+		let pallet_name = self.pallet.as_deref().unwrap_or_default();
+		let (prefix, entry) = find_entry(&metadata, pallet_name, self.item.as_deref())?;
+		let registry = &metadata.types;
+		let item_prefix = [twox_128(prefix.as_bytes()), twox_128(entry.name.as_bytes())].concat();
+		let default = || {
+			let mut input = &entry.default[..];
+			decode_value(registry, entry_value_type(entry), &mut input).ok()
+		};
+
+		let (hashers, key_types) = match &entry.ty {
+			StorageEntryType::Plain(ty) => {
+				let value = client.storage(at, &StorageKey(item_prefix.clone()))?;
+				let value = match value {
+					Some(StorageData(data)) => Some(decode_all(registry, ty.id(), &data)?),
+					None => default(),
+				};
+				return Ok(vec![Entry { key: item_prefix, decoded_key: None, value }])
+			},
+			StorageEntryType::Map { hashers, key, .. } =>
+				(hashers, key_types(registry, key.id(), hashers.len())?),
+		};
+
+		if self.map_keys.len() > hashers.len() {
+			return Err(Error::Input(format!(
+				"{}::{} takes at most {} keys",
+				prefix,
+				entry.name,
+				hashers.len()
+			)))
+		}
+
+		let mut key = item_prefix;
+		for ((input, hasher), ty) in self.map_keys.iter().zip(hashers).zip(&key_types) {
+			let encoded = encode_key(registry, *ty, input)?;
+			key.extend(hash(hasher, &encoded));
+		}
+
+		if self.map_keys.len() == hashers.len() {
+			let value = client.storage(at, &StorageKey(key.clone()))?;
+			let decoded_key = decode_keys(registry, &key[32..], hashers, &key_types);
+			let value = match value {
+				Some(StorageData(data)) =>
+					Some(decode_all(registry, entry_value_type(entry), &data)?),
+				None => default(),
+			};
+			return Ok(vec![Entry { key, decoded_key, value }])
+		}
+
+		self.pairs(client, at, key)?
+			.into_iter()
+			.map(|(StorageKey(key), StorageData(data))| {
+				Ok(Entry {
+					decoded_key: decode_keys(registry, &key[32..], hashers, &key_types),
+					value: Some(decode_all(registry, entry_value_type(entry), &data)?),
+					key,
+				})
+			})
+			.collect()
+	}
+
+	/// The entries under `prefix`, up to `--limit`. Keys are iterated lazily, so that only the
+	/// listed entries are loaded.
+	fn pairs(
+		&self,
+		client: &FullClient,
+		at: &BlockId<Block>,
+		prefix: Vec<u8>,
+	) -> Result<Vec<(StorageKey, StorageData)>> {
+		let mut pairs = Vec::new();
+		for key in client.storage_keys_iter(at, Some(&StorageKey(prefix)), None)?.take(self.limit) {
+			if let Some(value) = client.storage(at, &key)? {
+				pairs.push((key, value));
+			}
+		}
+		Ok(pairs)
+	}
+}
+
+impl CliConfiguration for StorageCmd {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+
+	fn pruning_params(&self) -> Option<&PruningParams> {
+		Some(&self.pruning_params)
+	}
+
+	fn database_params(&self) -> Option<&DatabaseParams> {
+		Some(&self.database_params)
+	}
+}
+
+/// Resolves a block hash or number given on the command line to a block hash, defaulting to the
+/// best block.
+pub(crate) fn block_hash(client: &FullClient, at: Option<&str>) -> Result<H256> {
+	match at {
+		None => Ok(client.info().best_hash),
+		Some(at) if at.starts_with("0x") => H256::from_str(at)
+			.map_err(|e| Error::Input(format!("Invalid block hash {}: {}", at, e))),
+		Some(at) => {
+			let number = at
+				.parse()
+				.map_err(|e| Error::Input(format!("Invalid block number {}: {}", at, e)))?;
+			client
+				.hash(number)?
+				.ok_or_else(|| Error::Input(format!("Block #{} is not in the database", number)))
+		},
+	}
+}
+
+/// The runtime metadata at the given block.
+pub(crate) fn runtime_metadata(
+	client: &FullClient,
+	at: &BlockId<Block>,
+) -> Result<RuntimeMetadataV14> {
+	let metadata =
+		client.runtime_api().metadata(at).map_err(|e| Error::Application(Box::new(e)))?;
+	match RuntimeMetadataPrefixed::decode(&mut &metadata[..])
+		.map_err(|e| Error::Input(format!("Failed to decode the runtime metadata: {}", e)))?
+		.1
+	{
+		RuntimeMetadata::V14(metadata) => Ok(metadata),
+		_ => Err("Only V14 runtime metadata is supported".into()),
+	}
+}
+
+/// Finds a storage item by its pallet and item names, ignoring case. Lists the items of the pallet
+/// if the item is not given or unknown.
+fn find_entry<'a>(
+	metadata: &'a RuntimeMetadataV14,
+	pallet: &str,
+	item: Option<&str>,
+) -> Result<(&'a str, &'a StorageEntryMetadata<PortableForm>)> {
+	let storage = metadata
+		.pallets
+		.iter()
+		.find(|p| p.name.eq_ignore_ascii_case(pallet))
+		.ok_or_else(|| Error::Input(format!("Unknown pallet {}", pallet)))?
+		.storage
+		.as_ref()
+		.ok_or_else(|| Error::Input(format!("The {} pallet has no storage", pallet)))?;
+
+	let entry =
+		item.and_then(|item| storage.entries.iter().find(|e| e.name.eq_ignore_ascii_case(item)));
+	entry.map(|entry| (&storage.prefix[..], entry)).ok_or_else(|| {
+		let items: Vec<_> = storage.entries.iter().map(|e| &e.name[..]).collect();
+		Error::Input(format!("Storage items of {}: {}", storage.prefix, items.join(", ")))
+	})
+}
+
+fn entry_value_type(entry: &StorageEntryMetadata<PortableForm>) -> u32 {
+	match &entry.ty {
+		StorageEntryType::Plain(ty) => ty.id(),
+		StorageEntryType::Map { value, .. } => value.id(),
+	}
+}
+
+/// The types of the keys of a map with `count` hashers. Maps with several keys have a tuple key.
+fn key_types(registry: &PortableRegistry, key: u32, count: usize) -> Result<Vec<u32>> {
+	if count == 1 {
+		return Ok(vec![key])
+	}
+	match registry.resolve(key).map(|ty| ty.type_def()) {
+		Some(TypeDef::Tuple(tuple)) if tuple.fields().len() == count =>
+			Ok(tuple.fields().iter().map(|f| f.id()).collect()),
+		_ => Err("The key type of the map does not match its hashers".into()),
+	}
+}
+
+fn hash(hasher: &StorageHasher, data: &[u8]) -> Vec<u8> {
+	match hasher {
+		StorageHasher::Blake2_128 => blake2_128(data).to_vec(),
+		StorageHasher::Blake2_256 => blake2_256(data).to_vec(),
+		StorageHasher::Blake2_128Concat => [&blake2_128(data)[..], data].concat(),
+		StorageHasher::Twox128 => twox_128(data).to_vec(),
+		StorageHasher::Twox256 => twox_256(data).to_vec(),
+		StorageHasher::Twox64Concat => [&twox_64(data)[..], data].concat(),
+		StorageHasher::Identity => data.to_vec(),
+	}
+}
+
+/// Recovers the map keys from the hashed part of a storage key. Only possible when every hasher
+/// keeps the key in clear.
+fn decode_keys(
+	registry: &PortableRegistry,
+	mut hashed: &[u8],
+	hashers: &[StorageHasher],
+	types: &[u32],
+) -> Option<Vec<Value>> {
+	hashers
+		.iter()
+		.zip(types)
+		.map(|(hasher, ty)| {
+			let skip = match hasher {
+				StorageHasher::Blake2_128Concat => 16,
+				StorageHasher::Twox64Concat => 8,
+				StorageHasher::Identity => 0,
+				_ => return None,
+			};
+			hashed = hashed.get(skip..)?;
+			decode_value(registry, *ty, &mut hashed).ok()
+		})
+		.collect()
+}
+
+/// SCALE encodes a map key given on the command line.
+fn encode_key(registry: &PortableRegistry, ty: u32, input: &str) -> Result<Vec<u8>> {
+	if input.starts_with("0x") {
+		return parse_hex(input)
+	}
+
+	let ty = registry.resolve(ty).ok_or("Unknown key type")?;
+	if ty.path().segments().last().map(|s| &s[..]) == Some("AccountId32") {
+		let account: AccountId32 =
+			parse_public::<sr25519::Pair>(input).map_err(Error::Input)?.into();
+		return Ok(account.encode())
+	}
+
+	let invalid =
+		|e: std::num::ParseIntError| Error::Input(format!("Invalid key {}: {}", input, e));
+	Ok(match ty.type_def() {
+		TypeDef::Primitive(TypeDefPrimitive::U8) => input.parse::<u8>().map_err(invalid)?.encode(),
+		TypeDef::Primitive(TypeDefPrimitive::U16) =>
+			input.parse::<u16>().map_err(invalid)?.encode(),
+		TypeDef::Primitive(TypeDefPrimitive::U32) =>
+			input.parse::<u32>().map_err(invalid)?.encode(),
+		TypeDef::Primitive(TypeDefPrimitive::U64) =>
+			input.parse::<u64>().map_err(invalid)?.encode(),
+		TypeDef::Primitive(TypeDefPrimitive::U128) =>
+			input.parse::<u128>().map_err(invalid)?.encode(),
+		TypeDef::Primitive(TypeDefPrimitive::Str) => input.encode(),
+		_ =>
+			return Err(Error::Input(format!(
+				"Keys of type {:?} must be given as `0x`-prefixed SCALE encoding",
+				ty.path()
+			))),
+	})
+}
+
+/// Decodes `data` as a value of type `ty`, failing if any bytes are left.
+fn decode_all(registry: &PortableRegistry, ty: u32, mut data: &[u8]) -> Result<Value> {
+	let value = decode_value(registry, ty, &mut data)
+		.map_err(|e| Error::Input(format!("Failed to decode the value: {}", e)))?;
+	if !data.is_empty() {
+		return Err(Error::Input(format!("{} bytes left after decoding the value", data.len())))
+	}
+	Ok(value)
+}
+
+/// Decodes a value of type `ty` described by the metadata `registry` into JSON.
+///
+/// Composites with named fields become objects, enums become `{ "Variant": fields }` or the
+/// variant name, byte arrays become hex, accounts become SS58 addresses and integers that do not
+/// fit in a `u64` become strings.
+fn decode_value(registry: &PortableRegistry, ty: u32, input: &mut &[u8]) -> Result<Value, String> {
+	let ty = registry.resolve(ty).ok_or_else(|| format!("Unknown type {}", ty))?;
+	if ty.path().segments().last().map(|s| &s[..]) == Some("AccountId32") {
+		let account = AccountId32::decode(input).map_err(|e| e.to_string())?;
+		return Ok(account.to_string().into())
+	}
+
+	match ty.type_def() {
+		TypeDef::Composite(composite) => decode_fields(
+			registry,
+			composite.fields().iter().map(|f| (f.name().map(|n| &n[..]), f.ty().id())),
+			input,
+		),
+		TypeDef::Variant(variant) => {
+			let index = input.read_byte().map_err(|e| e.to_string())?;
+			let variant = variant
+				.variants()
+				.iter()
+				.find(|v| v.index() == index)
+				.ok_or_else(|| format!("Unknown variant index {}", index))?;
+			if variant.fields().is_empty() {
+				return Ok(variant.name().clone().into())
+			}
+			let fields = decode_fields(
+				registry,
+				variant.fields().iter().map(|f| (f.name().map(|n| &n[..]), f.ty().id())),
+				input,
+			)?;
+			let mut object = serde_json::Map::new();
+			object.insert(variant.name().clone(), fields);
+			Ok(Value::Object(object))
+		},
+		TypeDef::Sequence(sequence) => {
+			let len = Compact::<u32>::decode(input).map_err(|e| e.to_string())?.0 as usize;
+			decode_items(registry, sequence.type_param().id(), len, input)
+		},
+		TypeDef::Array(array) =>
+			decode_items(registry, array.type_param().id(), array.len() as usize, input),
+		TypeDef::Tuple(tuple) =>
+			decode_fields(registry, tuple.fields().iter().map(|f| (None, f.id())), input),
+		TypeDef::Primitive(primitive) => decode_primitive(primitive, input),
+		TypeDef::Compact(_) => {
+			let value = Compact::<u128>::decode(input).map_err(|e| e.to_string())?.0;
+			Ok(integer(value))
+		},
+		TypeDef::BitSequence(_) => Err("Bit sequences are not supported".into()),
+	}
+}
+
+/// Decodes the fields of a composite, a variant or a tuple.
+fn decode_fields<'a>(
+	registry: &PortableRegistry,
+	fields: impl Iterator<Item = (Option<&'a str>, u32)>,
+	input: &mut &[u8],
+) -> Result<Value, String> {
+	let mut named = serde_json::Map::new();
+	let mut unnamed = Vec::new();
+	for (name, ty) in fields {
+		let value = decode_value(registry, ty, input)?;
+		match name {
+			Some(name) => {
+				named.insert(name.to_string(), value);
+			},
+			None => unnamed.push(value),
+		}
+	}
+
+	Ok(match (named.is_empty(), unnamed.len()) {
+		(false, _) => Value::Object(named),
+		(true, 1) => unnamed.remove(0),
+		(true, _) => Value::Array(unnamed),
+	})
+}
+
+/// Decodes the items of a sequence or an array, rendering bytes as hex.
+fn decode_items(
+	registry: &PortableRegistry,
+	ty: u32,
+	len: usize,
+	input: &mut &[u8],
+) -> Result<Value, String> {
+	if let Some(TypeDef::Primitive(TypeDefPrimitive::U8)) =
+		registry.resolve(ty).map(|ty| ty.type_def())
+	{
+		// The length of sequences is read from the input, check it before allocating.
+		let remaining = input.remaining_len().map_err(|e| e.to_string())?.unwrap_or_default();
+		if len > remaining {
+			return Err(format!("{} bytes expected, {} left", len, remaining))
+		}
+		let mut bytes = vec![0; len];
+		input.read(&mut bytes).map_err(|e| e.to_string())?;
+		return Ok(hex_value(&bytes))
+	}
+
+	(0..len)
+		.map(|_| decode_value(registry, ty, input))
+		.collect::<Result<Vec<_>, _>>()
+		.map(Value::Array)
+}
+
+fn decode_primitive(primitive: &TypeDefPrimitive, input: &mut &[u8]) -> Result<Value, String> {
+	fn decode<T: Decode>(input: &mut &[u8]) -> Result<T, String> {
+		T::decode(input).map_err(|e| e.to_string())
+	}
+
+	Ok(match primitive {
+		TypeDefPrimitive::Bool => decode::<bool>(input)?.into(),
+		TypeDefPrimitive::Char =>
+			char::from_u32(decode::<u32>(input)?).ok_or("Invalid char")?.to_string().into(),
+		TypeDefPrimitive::Str => decode::<String>(input)?.into(),
+		TypeDefPrimitive::U8 => decode::<u8>(input)?.into(),
+		TypeDefPrimitive::U16 => decode::<u16>(input)?.into(),
+		TypeDefPrimitive::U32 => decode::<u32>(input)?.into(),
+		TypeDefPrimitive::U64 => decode::<u64>(input)?.into(),
+		TypeDefPrimitive::U128 => integer(decode::<u128>(input)?),
+		TypeDefPrimitive::U256 => hex_value(&decode::<[u8; 32]>(input)?),
+		TypeDefPrimitive::I8 => decode::<i8>(input)?.into(),
+		TypeDefPrimitive::I16 => decode::<i16>(input)?.into(),
+		TypeDefPrimitive::I32 => decode::<i32>(input)?.into(),
+		TypeDefPrimitive::I64 => decode::<i64>(input)?.into(),
+		TypeDefPrimitive::I128 => decode::<i128>(input)?.to_string().into(),
+		TypeDefPrimitive::I256 => hex_value(&decode::<[u8; 32]>(input)?),
+	})
+}
+
+/// An unsigned integer, as a number if it fits in a `u64` and as a string otherwise.
+fn integer(value: u128) -> Value {
+	match u64::try_from(value) {
+		Ok(value) => value.into(),
+		Err(_) => value.to_string().into(),
+	}
+}
+
+fn hex_value(bytes: &[u8]) -> Value {
+	format!("0x{}", hex::encode(bytes)).into()
+}
+
+fn parse_hex(input: &str) -> Result<Vec<u8>> {
+	hex::decode(input.trim_start_matches("0x")).map_err(|_| Error::HexDataConversion)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use node_template_runtime::{Balance, Index, Runtime};
+
+	fn metadata() -> RuntimeMetadataV14 {
+		let encoded = Runtime::metadata().encode();
+		match RuntimeMetadataPrefixed::decode(&mut &encoded[..]).unwrap().1 {
+			RuntimeMetadata::V14(metadata) => metadata,
+			_ => unreachable!("the runtime has V14 metadata; qed"),
+		}
+	}
+
+	/// Decodes `data` as the value of the storage item `pallet::item` of the runtime.
+	fn decode_item(pallet: &str, item: &str, data: &[u8]) -> Value {
+		let metadata = metadata();
+		let (_, entry) = find_entry(&metadata, pallet, Some(item)).unwrap();
+		decode_all(&metadata.types, entry_value_type(entry), data).unwrap()
+	}
+
+	#[test]
+	fn decodes_system_account() {
+		let account = frame_system::AccountInfo::<Index, pallet_balances::AccountData<Balance>> {
+			nonce: 3,
+			consumers: 0,
+			providers: 1,
+			sufficients: 0,
+			data: pallet_balances::AccountData { free: 1_000, reserved: 20, ..Default::default() },
+		};
+		assert_eq!(
+			decode_item("System", "Account", &account.encode()),
+			json!({
+				"nonce": 3,
+				"consumers": 0,
+				"providers": 1,
+				"sufficients": 0,
+				"data": { "free": 1_000, "reserved": 20, "misc_frozen": 0, "fee_frozen": 0 },
+			}),
+		);
+	}
+
+	#[test]
+	fn decodes_total_issuance_beyond_u64_as_a_string() {
+		let issuance: Balance = 1 << 70;
+		assert_eq!(
+			decode_item("Balances", "TotalIssuance", &issuance.encode()),
+			json!("1180591620717411303424"),
+		);
+		assert_eq!(decode_item("Balances", "TotalIssuance", &42u128.encode()), json!(42));
+	}
+
+	#[test]
+	fn decodes_something() {
+		assert_eq!(decode_item("TemplateModule", "Something", &7u32.encode()), json!(7));
+
+		let metadata = metadata();
+		let (_, entry) = find_entry(&metadata, "TemplateModule", Some("Something")).unwrap();
+		let truncated = &7u32.encode()[..3];
+		assert!(decode_all(&metadata.types, entry_value_type(entry), truncated).is_err());
+	}
+
+	#[test]
+	fn rejects_byte_sequences_longer_than_the_input_before_allocating() {
+		let mut registry = scale_info::Registry::new();
+		let ty = registry.register_type(&scale_info::meta_type::<Vec<u8>>()).id();
+		let registry = PortableRegistry::from(registry);
+
+		let encoded = vec![1u8, 2, 3].encode();
+		assert_eq!(decode_all(&registry, ty, &encoded).unwrap(), json!("0x010203"));
+
+		let huge = Compact(u32::MAX).encode();
+		assert!(decode_value(&registry, ty, &mut &huge[..]).is_err());
+	}
+}
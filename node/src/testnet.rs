@@ -0,0 +1,218 @@
+//! The `testnet` subcommand: generate everything needed to run a local N-validator network.
+//!
+//! For every validator this writes a base path the default run command can use as is:
+//!
+//! ```text
+//! <output>/node-<i>/chains/<chain id>/keystore/...            Aura and GRANDPA keys
+//! <output>/node-<i>/chains/<chain id>/network/secret_ed25519  libp2p node key
+//! ```
+//!
+//! together with `<output>/chain-spec-raw.json`, which lists every validator as an authority and
+//! as a bootnode, and `<output>/validators.json` with the secret phrases of the generated keys.
+
+use crate::chain_spec::{self, ChainSpec};
+use node_template_runtime::{AccountId, WASM_BINARY};
+use sc_cli::{Error, Result};
+use sc_keystore::LocalKeystore;
+use sc_network::config::identity;
+use sc_service::{config::MultiaddrWithPeerId, ChainSpec as _, ChainType};
+use serde::Serialize;
+use sp_consensus_aura::sr25519::AuthorityId as AuraId;
+use sp_core::{
+	crypto::{key_types, Ss58Codec},
+	ed25519, sr25519, Pair,
+};
+use sp_finality_grandpa::AuthorityId as GrandpaId;
+use sp_keystore::SyncCryptoStore;
+use std::{fs, path::PathBuf};
+
+/// The keys and network identity generated for one validator.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Validator {
+	/// The secret phrase both the Aura and the GRANDPA key are derived from.
+	secret_phrase: String,
+	/// The SS58 address of the Aura key, also used as the validator's account.
+	aura: String,
+	/// The SS58 address of the GRANDPA key.
+	grandpa: String,
+	/// The libp2p peer id of the node.
+	peer_id: String,
+	/// The base path the node should be started with.
+	base_path: PathBuf,
+	/// The p2p port of the node.
+	port: u16,
+	/// The RPC port of the node.
+	rpc_port: u16,
+	/// The WebSocket port of the node.
+	ws_port: u16,
+}
+
+/// The `testnet` command used to generate keys, node configs and a chain spec for a local
+/// network.
+#[derive(Debug, Clone, clap::Parser)]
+pub struct TestnetCmd {
+	/// Number of validators to generate.
+	#[clap(long, short = 'n', default_value = "3")]
+	pub validators: u16,
+
+	/// The directory the base paths and the chain spec are written to.
+	#[clap(long, short = 'o', default_value = "testnet")]
+	pub output_dir: PathBuf,
+
+	/// Name of the generated chain.
+	#[clap(long, default_value = "Local Testnet")]
+	pub chain_name: String,
+
+	/// Identifier of the generated chain.
+	#[clap(long, default_value = "local_testnet")]
+	pub chain_id: String,
+
+	/// The p2p port of the first node, incremented for every following node.
+	#[clap(long, default_value = "30333")]
+	pub base_port: u16,
+
+	/// The RPC port of the first node, incremented for every following node.
+	#[clap(long, default_value = "9933")]
+	pub base_rpc_port: u16,
+
+	/// The WebSocket port of the first node, incremented for every following node.
+	#[clap(long, default_value = "9944")]
+	pub base_ws_port: u16,
+}
+
+impl TestnetCmd {
+	/// Run the command.
+	pub fn run(&self) -> Result<()> {
+		if self.validators == 0 {
+			return Err(Error::Input("At least one validator is required".into()))
+		}
+		// Checks the ports of the last node before writing anything.
+		self.ports(self.validators - 1)?;
+		let wasm_binary = WASM_BINARY.ok_or("Wasm binary not available")?;
+
+		let mut validators = Vec::with_capacity(self.validators.into());
+		let mut authorities = Vec::with_capacity(self.validators.into());
+		let mut boot_nodes = Vec::with_capacity(self.validators.into());
+		let mut endowed_accounts = Vec::with_capacity(self.validators.into());
+
+		for index in 0..self.validators {
+			let base_path = self.output_dir.join(format!("node-{}", index));
+			let chain_path = base_path.join("chains").join(&self.chain_id);
+
+			let (aura_pair, secret_phrase, _) = sr25519::Pair::generate_with_phrase(None);
+			let grandpa_pair = ed25519::Pair::from_string(&secret_phrase, None)
+				.expect("generated phrase is valid; qed");
+
+			let keystore = LocalKeystore::open(chain_path.join("keystore"), None)?;
+			for (key_type, public) in [
+				(key_types::AURA, aura_pair.public().0),
+				(key_types::GRANDPA, grandpa_pair.public().0),
+			] {
+				SyncCryptoStore::insert_unknown(&keystore, key_type, &secret_phrase, &public)
+					.map_err(|_| Error::KeystoreOperation)?;
+			}
+
+			let node_key = identity::ed25519::SecretKey::generate();
+			let network_path = chain_path.join("network");
+			fs::create_dir_all(&network_path)?;
+			fs::write(network_path.join("secret_ed25519"), node_key.as_ref())?;
+			let peer_id =
+				identity::Keypair::Ed25519(node_key.into()).public().to_peer_id().to_base58();
+
+			let (port, rpc_port, ws_port) = self.ports(index)?;
+			boot_nodes.push(
+				format!("/ip4/127.0.0.1/tcp/{}/p2p/{}", port, peer_id)
+					.parse::<MultiaddrWithPeerId>()
+					.map_err(|e| Error::Input(e.to_string()))?,
+			);
+			authorities
+				.push((AuraId::from(aura_pair.public()), GrandpaId::from(grandpa_pair.public())));
+			endowed_accounts.push(AccountId::from(aura_pair.public()));
+			validators.push(Validator {
+				secret_phrase,
+				aura: aura_pair.public().to_ss58check(),
+				grandpa: grandpa_pair.public().to_ss58check(),
+				peer_id,
+				base_path,
+				port,
+				rpc_port,
+				ws_port,
+			});
+		}
+
+		// The first validator holds the sudo key, every validator gets funds to pay fees.
+		let root_key = endowed_accounts[0].clone();
+
+		let spec = ChainSpec::from_genesis(
+			&self.chain_name,
+			&self.chain_id,
+			ChainType::Local,
+			move || {
+				chain_spec::genesis_config(
+					wasm_binary,
+					authorities.clone(),
+					root_key.clone(),
+					endowed_accounts.iter().cloned().map(|k| (k, 1 << 60)).collect(),
+					None,
+				)
+			},
+			boot_nodes,
+			None,
+			None,
+			None,
+			Some(chain_spec::chain_properties()),
+			Default::default(),
+		);
+
+		let spec_path = self.output_dir.join("chain-spec-raw.json");
+		fs::write(&spec_path, spec.as_json(true)?)?;
+		fs::write(
+			self.output_dir.join("validators.json"),
+			serde_json::to_string_pretty(&validators).map_err(|e| Error::Input(e.to_string()))?,
+		)?;
+
+		println!(
+			"Generated {} validators for `{}`. Start them with:",
+			validators.len(),
+			spec.id()
+		);
+		for validator in &validators {
+			println!(
+				"  node-template --chain {} --base-path {} --validator --port {} --rpc-port {} \
+				--ws-port {}",
+				spec_path.display(),
+				validator.base_path.display(),
+				validator.port,
+				validator.rpc_port,
+				validator.ws_port,
+			);
+		}
+		eprintln!(
+			"The secret phrases in {} control the validators' keys and funds; keep them safe.",
+			self.output_dir.join("validators.json").display()
+		);
+
+		Ok(())
+	}
+
+	/// The p2p, RPC and WebSocket ports of the node of the given index.
+	fn ports(&self, index: u16) -> Result<(u16, u16, u16)> {
+		let port = |base: u16, name: &str| {
+			base.checked_add(index).ok_or_else(|| {
+				Error::Input(format!(
+					"The {} port of node {} exceeds {}, lower the base {} port",
+					name,
+					index,
+					u16::MAX,
+					name
+				))
+			})
+		};
+		Ok((
+			port(self.base_port, "p2p")?,
+			port(self.base_rpc_port, "RPC")?,
+			port(self.base_ws_port, "WebSocket")?,
+		))
+	}
+}
@@ -0,0 +1,302 @@
+//! The `tx` subcommand: build, sign, encode and submit extrinsics for common calls.
+//!
+//! The signer is given either as a secret URI (`--suri`), as a public key whose secret lives in a
+//! local keystore (`--signer` with `--keystore-path`), or as a bare public key for offline signing:
+//! `--payload-only` prints the bytes to sign, and `--signature` assembles the extrinsic from a
+//! signature produced elsewhere.
+
+use crate::{
+	common::parse_public,
+	extrinsic::{self, ExtrinsicParams},
+	rpc_client::RpcClient,
+};
+use codec::Encode;
+use node_template_runtime::{
+	self as runtime, AccountId, Balance, BalancesCall, BlockNumber, Hash, Index,
+};
+use sc_cli::{Error, KeystoreParams, Result};
+use sc_keystore::LocalKeystore;
+use sp_core::{crypto::key_types, sr25519, Pair};
+use sp_keystore::SyncCryptoStore;
+use sp_runtime::traits::Header as _;
+use std::convert::TryFrom;
+
+/// The calls the `tx` command can build.
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum TxCall {
+	/// Calls of the balances pallet.
+	#[clap(subcommand)]
+	Balances(BalancesTx),
+
+	/// Calls of the template pallet.
+	#[clap(subcommand)]
+	Template(TemplateTx),
+}
+
+/// Calls of the balances pallet.
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum BalancesTx {
+	/// Transfer funds, possibly reaping the sender.
+	Transfer {
+		/// The recipient, as a public key or secret URI.
+		#[clap(long)]
+		dest: String,
+		/// The amount to transfer.
+		#[clap(long)]
+		value: Balance,
+	},
+
+	/// Transfer funds, keeping the sender above the existential deposit.
+	TransferKeepAlive {
+		/// The recipient, as a public key or secret URI.
+		#[clap(long)]
+		dest: String,
+		/// The amount to transfer.
+		#[clap(long)]
+		value: Balance,
+	},
+}
+
+/// Calls of the template pallet.
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum TemplateTx {
+	/// Store a value.
+	DoSomething {
+		/// The value to store.
+		#[clap(long)]
+		something: u32,
+	},
+
+	/// Increment the stored value.
+	CauseError,
+}
+
+impl TxCall {
+	/// The runtime call.
+	pub fn to_call(&self) -> Result<runtime::Call> {
+		Ok(match self {
+			TxCall::Balances(BalancesTx::Transfer { dest, value }) =>
+				BalancesCall::transfer { dest: parse_account(dest)?.into(), value: *value }.into(),
+			TxCall::Balances(BalancesTx::TransferKeepAlive { dest, value }) =>
+				BalancesCall::transfer_keep_alive { dest: parse_account(dest)?.into(), value: *value }
+					.into(),
+			TxCall::Template(TemplateTx::DoSomething { something }) =>
+				runtime::pallet_template::Call::do_something { something: *something }.into(),
+			TxCall::Template(TemplateTx::CauseError) =>
+				runtime::pallet_template::Call::cause_error {}.into(),
+		})
+	}
+}
+
+/// The `tx` command used to build, sign and submit extrinsics.
+#[derive(Debug, Clone, clap::Parser)]
+pub struct TxCmd {
+	/// The call to make.
+	#[clap(subcommand)]
+	pub call: TxCall,
+
+	/// The secret URI of the signer.
+	#[clap(long, value_name = "SURI", conflicts_with = "signer")]
+	pub suri: Option<String>,
+
+	/// The public key of the signer, signing with the keystore or offline.
+	#[clap(long, value_name = "PUBLIC")]
+	pub signer: Option<String>,
+
+	/// Only print the bytes to sign, for offline signing.
+	#[clap(long, requires = "signer", conflicts_with_all = &["signature", "submit"])]
+	pub payload_only: bool,
+
+	/// The hex encoded sr25519 signature of the bytes printed by `--payload-only`.
+	#[clap(long, value_name = "HEX", requires = "signer")]
+	pub signature: Option<String>,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub keystore_params: KeystoreParams,
+
+	/// The WebSocket RPC endpoint used to fetch chain parameters and to submit the extrinsic.
+	#[clap(long, default_value = "ws://127.0.0.1:9944")]
+	pub url: String,
+
+	/// Do not connect to a node. The nonce, genesis hash and versions must be given.
+	#[clap(long, conflicts_with = "submit")]
+	pub offline: bool,
+
+	/// Submit the extrinsic and wait for its inclusion instead of printing it.
+	#[clap(long)]
+	pub submit: bool,
+
+	/// The nonce, instead of the signer's next one.
+	#[clap(long)]
+	pub nonce: Option<Index>,
+
+	/// The index of the call among the template pallet calls of the signer, instead of its next
+	/// one. Only checked for template pallet calls.
+	#[clap(long)]
+	pub template_index: Option<u32>,
+
+	/// A tip for the block author.
+	#[clap(long, default_value = "0")]
+	pub tip: Balance,
+
+	/// Number of blocks the extrinsic is valid for; `0` makes it immortal. Defaults to the longest
+	/// period the runtime allows.
+	#[clap(long)]
+	pub mortality: Option<u64>,
+
+	/// The number of the block the mortality period starts at, instead of the best block.
+	#[clap(long, requires = "era_block_hash")]
+	pub era_block_number: Option<BlockNumber>,
+
+	/// The hash of the block the mortality period starts at, instead of the best block.
+	#[clap(long, requires = "era_block_number")]
+	pub era_block_hash: Option<Hash>,
+
+	/// The genesis hash, instead of the node's.
+	#[clap(long)]
+	pub genesis_hash: Option<Hash>,
+
+	/// The runtime spec version, instead of the node's.
+	#[clap(long)]
+	pub spec_version: Option<u32>,
+
+	/// The runtime transaction version, instead of the node's.
+	#[clap(long)]
+	pub transaction_version: Option<u32>,
+}
+
+impl TxCmd {
+	/// Run the command.
+	pub fn run(&self) -> Result<()> {
+		tokio::runtime::Runtime::new()?.block_on(self.build_and_submit())
+	}
+
+	async fn build_and_submit(&self) -> Result<()> {
+		let call = self.call.to_call()?;
+		let (signer, pair) = match (&self.suri, &self.signer) {
+			(Some(suri), _) => {
+				let pair = sr25519::Pair::from_string(suri, None)
+					.map_err(|e| Error::Input(format!("Invalid secret URI: {:?}", e)))?;
+				(pair.public(), Some(pair))
+			},
+			(None, Some(signer)) =>
+				(parse_public::<sr25519::Pair>(signer).map_err(Error::Input)?, None),
+			(None, None) => return Err("Either `--suri` or `--signer` is required".into()),
+		};
+
+		let client = if self.offline { None } else { Some(RpcClient::connect(&self.url).await?) };
+		let params = self.extrinsic_params(client.as_ref(), &signer.into()).await?;
+		let payload = extrinsic::signed_payload(call, &params);
+
+		if self.payload_only {
+			println!("0x{}", hex::encode(extrinsic::message_to_sign(&payload)));
+			return Ok(())
+		}
+
+		let signature = match (&pair, &self.signature) {
+			(Some(pair), _) => payload.using_encoded(|e| pair.sign(e)),
+			(None, Some(signature)) => hex::decode(signature.trim_start_matches("0x"))
+				.ok()
+				.and_then(|bytes| sr25519::Signature::try_from(&bytes[..]).ok())
+				.ok_or(Error::SignatureFormatInvalid)?,
+			(None, None) => self.sign_with_keystore(&signer, &payload)?,
+		};
+		let extrinsic = extrinsic::assemble(signer, payload, signature);
+
+		match client {
+			Some(client) if self.submit => {
+				let block_hash = client.submit_and_watch(&extrinsic).await?;
+				println!("Included in block {:?}", block_hash);
+			},
+			_ => println!("0x{}", hex::encode(extrinsic.encode())),
+		}
+
+		Ok(())
+	}
+
+	/// Signs `payload` with the account key of `signer` found in the local keystore.
+	fn sign_with_keystore(
+		&self,
+		signer: &sr25519::Public,
+		payload: &runtime::SignedPayload,
+	) -> Result<sr25519::Signature> {
+		let path = self.keystore_params.keystore_path.as_ref().ok_or(
+			"`--signer` needs `--keystore-path`, `--signature` or `--payload-only` to sign",
+		)?;
+		let keystore = LocalKeystore::open(path, self.keystore_params.read_password()?)?;
+
+		let signature = SyncCryptoStore::sign_with(
+			&keystore,
+			key_types::ACCOUNT,
+			&signer.into(),
+			&extrinsic::message_to_sign(payload),
+		)
+		.map_err(|_| Error::KeystoreOperation)?
+		.ok_or_else(|| Error::Input(format!("No account key for {} in the keystore", signer)))?;
+
+		sr25519::Signature::try_from(&signature[..]).map_err(|_| Error::SignatureFormatInvalid)
+	}
+
+	/// Resolves the parameters of the extrinsic, fetching from the node those not given.
+	async fn extrinsic_params(
+		&self,
+		client: Option<&RpcClient>,
+		signer: &AccountId,
+	) -> Result<ExtrinsicParams> {
+		let offline = |flag: &str| Error::Input(format!("`--{}` is required with `--offline`", flag));
+
+		let (spec_version, transaction_version) =
+			match (self.spec_version, self.transaction_version, client) {
+				(Some(spec), Some(tx), _) => (spec, tx),
+				(spec, tx, Some(client)) => {
+					let version = client.runtime_version().await?;
+					(
+						spec.unwrap_or(version.spec_version),
+						tx.unwrap_or(version.transaction_version),
+					)
+				},
+				(None, _, None) => return Err(offline("spec-version")),
+				(_, None, None) => return Err(offline("transaction-version")),
+			};
+		let genesis_hash = match (self.genesis_hash, client) {
+			(Some(hash), _) => hash,
+			(None, Some(client)) => client.genesis_hash().await?,
+			(None, None) => return Err(offline("genesis-hash")),
+		};
+		let nonce = match (self.nonce, client) {
+			(Some(nonce), _) => nonce,
+			(None, Some(client)) => client.account_nonce(signer).await?,
+			(None, None) => return Err(offline("nonce")),
+		};
+
+		let mut params =
+			ExtrinsicParams::immortal(genesis_hash, spec_version, transaction_version, nonce);
+		params.tip = self.tip;
+		params.template_index = match (self.template_index, client, &self.call) {
+			(Some(index), _, _) => index,
+			(None, Some(client), _) => client.template_index(signer).await?,
+			(None, None, TxCall::Template(_)) => return Err(offline("template-index")),
+			(None, None, TxCall::Balances(_)) => 0,
+		};
+
+		let period = self.mortality.unwrap_or_else(extrinsic::max_mortal_period);
+		if period == 0 {
+			return Ok(params)
+		}
+		let (number, hash) = match (self.era_block_number, self.era_block_hash, client) {
+			(Some(number), Some(hash), _) => (number, hash),
+			(_, _, Some(client)) => {
+				let best = client.best_header().await?;
+				(*best.number(), best.hash())
+			},
+			_ => return Err(offline("era-block-number/--era-block-hash` or `--mortality 0")),
+		};
+
+		Ok(params.mortal(period, number, hash))
+	}
+}
+
+fn parse_account(key: &str) -> Result<AccountId> {
+	parse_public::<sr25519::Pair>(key).map(Into::into).map_err(Error::Input)
+}
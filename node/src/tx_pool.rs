@@ -0,0 +1,276 @@
+//! Inspection and management of the transaction pool over RPC.
+//!
+//! The `author_*` methods only list the pending extrinsics as opaque bytes. These methods decode
+//! them, with their validity as the pool sees it, break them down by sender, and let an operator
+//! evict the transactions of a hash or a sender. Evicted transactions are banned from the pool
+//! for the ban time of the pool options, so that peers do not gossip them straight back in.
+
+use crate::{common::parse_account_id, rpc::DenyUnsafe};
+use codec::{Decode, Encode};
+use frame_support::dispatch::GetCallMetadata;
+use jsonrpsee::{
+	core::{Error as JsonRpseeError, RpcResult},
+	proc_macros::rpc,
+	types::error::{CallError, ErrorObject},
+};
+use node_template_runtime::{self as runtime, opaque::Block, AccountId, Hash};
+use sc_transaction_pool::Options as PoolOptions;
+use sc_transaction_pool_api::{InPoolTransaction, TransactionPool};
+use serde::Serialize;
+use sp_runtime::MultiAddress;
+use std::{collections::BTreeMap, sync::Arc};
+
+/// Whether a transaction can be included in the next block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PoolQueue {
+	/// All the tags it requires are provided, it can be included.
+	Ready,
+	/// It waits for other transactions to provide the tags it requires.
+	Future,
+}
+
+/// A transaction in the pool.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PooledTransaction {
+	/// The hash of the extrinsic.
+	pub hash: Hash,
+	/// The queue the transaction is in.
+	pub queue: PoolQueue,
+	/// The priority of the transaction, higher first.
+	pub priority: u64,
+	/// The number of blocks the transaction stays valid for, from its validation.
+	pub longevity: u64,
+	/// The hex encoded tags the transaction requires.
+	pub requires: Vec<String>,
+	/// The hex encoded tags the transaction provides.
+	pub provides: Vec<String>,
+	/// Whether the transaction is gossiped to the peers.
+	pub propagate: bool,
+	/// The signer, for signed extrinsics.
+	pub sender: Option<AccountId>,
+	/// The pallet of the call, if the extrinsic decodes.
+	pub pallet: Option<String>,
+	/// The name of the call, if the extrinsic decodes.
+	pub call: Option<String>,
+	/// The call and its arguments, as printed by `Debug`.
+	pub args: Option<String>,
+	/// The length of the encoded extrinsic.
+	pub bytes: usize,
+}
+
+/// The number of transactions of a sender in the pool.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SenderCount {
+	/// The signer of the transactions.
+	pub sender: AccountId,
+	/// The number of ready transactions.
+	pub ready: usize,
+	/// The number of future transactions.
+	pub future: usize,
+}
+
+/// The limits of one queue of the pool.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueStatus {
+	/// The number of transactions in the queue.
+	pub count: usize,
+	/// The total length of the transactions in the queue.
+	pub bytes: usize,
+	/// The maximum number of transactions in the queue.
+	pub max_count: usize,
+	/// The maximum total length of the transactions in the queue.
+	pub max_bytes: usize,
+}
+
+/// The occupation and limits of the pool.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolStatus {
+	/// The ready queue.
+	pub ready: QueueStatus,
+	/// The future queue.
+	pub future: QueueStatus,
+	/// Whether transactions that are not ready yet are rejected instead of queued.
+	pub reject_future_transactions: bool,
+	/// How long evicted transactions are banned from the pool, in seconds.
+	pub ban_time_secs: u64,
+}
+
+/// Inspection and management of the transaction pool. The removal methods are unsafe.
+#[rpc(server)]
+pub trait TxPoolApi {
+	/// The transactions in the pool, ready ones first in the order they would be included,
+	/// optionally only those of `sender`.
+	#[method(name = "txPool_pending")]
+	fn pending(&self, sender: Option<String>) -> RpcResult<Vec<PooledTransaction>>;
+
+	/// The number of transactions of each sender in the pool, largest first.
+	#[method(name = "txPool_senders")]
+	fn senders(&self) -> RpcResult<Vec<SenderCount>>;
+
+	/// The occupation and limits of the pool.
+	#[method(name = "txPool_status")]
+	fn status(&self) -> RpcResult<PoolStatus>;
+
+	/// Removes the transactions with the given hashes, and those depending on them. Returns the
+	/// hashes of all removed transactions.
+	#[method(name = "txPool_removeByHash")]
+	fn remove_by_hash(&self, hashes: Vec<Hash>) -> RpcResult<Vec<Hash>>;
+
+	/// Removes the transactions of `sender`, and those depending on them. Returns the hashes of
+	/// all removed transactions.
+	#[method(name = "txPool_removeBySender")]
+	fn remove_by_sender(&self, sender: String) -> RpcResult<Vec<Hash>>;
+}
+
+/// Error code of the transaction pool RPC.
+const TX_POOL_ERROR: i32 = 3;
+
+/// The RPC handler of the transaction pool methods.
+pub struct TxPoolRpc<P> {
+	pool: Arc<P>,
+	options: PoolOptions,
+	deny_unsafe: DenyUnsafe,
+}
+
+impl<P> TxPoolRpc<P> {
+	/// Create a new instance of the transaction pool RPC handler, for a pool built with `options`.
+	pub fn new(pool: Arc<P>, options: PoolOptions, deny_unsafe: DenyUnsafe) -> Self {
+		Self { pool, options, deny_unsafe }
+	}
+}
+
+impl<P> TxPoolRpc<P>
+where
+	P: TransactionPool<Block = Block, Hash = Hash> + 'static,
+{
+	/// The transactions in the pool, ready ones first.
+	fn transactions(&self) -> Vec<PooledTransaction> {
+		let ready = self.pool.ready().map(|tx| describe(&*tx, PoolQueue::Ready));
+		let future = self.pool.futures().into_iter().map(|tx| describe(&tx, PoolQueue::Future));
+		ready.chain(future).collect()
+	}
+}
+
+impl<P> TxPoolApiServer for TxPoolRpc<P>
+where
+	P: TransactionPool<Block = Block, Hash = Hash> + 'static,
+{
+	fn pending(&self, sender: Option<String>) -> RpcResult<Vec<PooledTransaction>> {
+		let sender = sender.map(|sender| parse_account(&sender)).transpose()?;
+		let mut transactions = self.transactions();
+		if let Some(sender) = sender {
+			transactions.retain(|tx| tx.sender.as_ref() == Some(&sender));
+		}
+		Ok(transactions)
+	}
+
+	fn senders(&self) -> RpcResult<Vec<SenderCount>> {
+		let mut counts = BTreeMap::<AccountId, (usize, usize)>::new();
+		for tx in self.transactions() {
+			if let Some(sender) = tx.sender {
+				let (ready, future) = counts.entry(sender).or_default();
+				match tx.queue {
+					PoolQueue::Ready => *ready += 1,
+					PoolQueue::Future => *future += 1,
+				}
+			}
+		}
+
+		let mut senders = counts
+			.into_iter()
+			.map(|(sender, (ready, future))| SenderCount { sender, ready, future })
+			.collect::<Vec<_>>();
+		senders.sort_by_key(|count| std::cmp::Reverse(count.ready + count.future));
+		Ok(senders)
+	}
+
+	fn status(&self) -> RpcResult<PoolStatus> {
+		let status = self.pool.status();
+		Ok(PoolStatus {
+			ready: QueueStatus {
+				count: status.ready,
+				bytes: status.ready_bytes,
+				max_count: self.options.ready.count,
+				max_bytes: self.options.ready.total_bytes,
+			},
+			future: QueueStatus {
+				count: status.future,
+				bytes: status.future_bytes,
+				max_count: self.options.future.count,
+				max_bytes: self.options.future.total_bytes,
+			},
+			reject_future_transactions: self.options.reject_future_transactions,
+			ban_time_secs: self.options.ban_time.as_secs(),
+		})
+	}
+
+	fn remove_by_hash(&self, hashes: Vec<Hash>) -> RpcResult<Vec<Hash>> {
+		self.deny_unsafe.check_if_safe()?;
+
+		Ok(self.pool.remove_invalid(&hashes).iter().map(|tx| *tx.hash()).collect())
+	}
+
+	fn remove_by_sender(&self, sender: String) -> RpcResult<Vec<Hash>> {
+		self.deny_unsafe.check_if_safe()?;
+
+		let sender = parse_account(&sender)?;
+		let hashes = self
+			.transactions()
+			.into_iter()
+			.filter(|tx| tx.sender.as_ref() == Some(&sender))
+			.map(|tx| tx.hash)
+			.collect::<Vec<_>>();
+		Ok(self.pool.remove_invalid(&hashes).iter().map(|tx| *tx.hash()).collect())
+	}
+}
+
+/// Describes a transaction of the pool, decoding its extrinsic.
+fn describe<T>(tx: &T, queue: PoolQueue) -> PooledTransaction
+where
+	T: InPoolTransaction<Hash = Hash>,
+	T::Transaction: Encode,
+{
+	let encoded = tx.data().encode();
+	let extrinsic = runtime::UncheckedExtrinsic::decode(&mut &encoded[..]).ok();
+	let sender = extrinsic.as_ref().and_then(|extrinsic| match &extrinsic.signature {
+		Some((MultiAddress::Id(account), _, _)) => Some(account.clone()),
+		_ => None,
+	});
+	let metadata = extrinsic.as_ref().map(|extrinsic| extrinsic.function.get_call_metadata());
+
+	PooledTransaction {
+		hash: *tx.hash(),
+		queue,
+		priority: *tx.priority(),
+		longevity: *tx.longevity(),
+		requires: tx.requires().iter().map(|tag| to_hex(tag)).collect(),
+		provides: tx.provides().iter().map(|tag| to_hex(tag)).collect(),
+		propagate: tx.is_propagable(),
+		sender,
+		pallet: metadata.as_ref().map(|metadata| metadata.pallet_name.to_string()),
+		call: metadata.as_ref().map(|metadata| metadata.function_name.to_string()),
+		args: extrinsic.map(|extrinsic| format!("{:?}", extrinsic.function)),
+		bytes: encoded.len(),
+	}
+}
+
+fn parse_account(account: &str) -> RpcResult<AccountId> {
+	parse_account_id(account).map_err(|e| rpc_error("Invalid account", e))
+}
+
+fn rpc_error(message: &str, error: impl std::fmt::Display) -> JsonRpseeError {
+	JsonRpseeError::Call(CallError::Custom(ErrorObject::owned(
+		TX_POOL_ERROR,
+		message,
+		Some(error.to_string()),
+	)))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+	format!("0x{}", hex::encode(bytes))
+}
@@ -0,0 +1,111 @@
+//! The `upgrade-runtime` subcommand: submit a new runtime through `Sudo::sudo_unchecked_weight`.
+
+use crate::{common::storage_prefix, extrinsic, rpc_client::RpcClient};
+use codec::Decode;
+use node_template_runtime::{self as runtime, AccountId, SudoCall, SystemCall};
+use sc_cli::{Error, Result};
+use sc_executor_common::runtime_blob::RuntimeBlob;
+use sp_core::{sr25519, Pair};
+use sp_version::RuntimeVersion;
+use std::{fs, path::PathBuf};
+
+/// The `upgrade-runtime` command used to replace the runtime of a running chain.
+#[derive(Debug, Clone, clap::Parser)]
+pub struct UpgradeRuntimeCmd {
+	/// The wasm blob of the new runtime, compressed or not.
+	#[clap(value_name = "WASM")]
+	pub wasm: PathBuf,
+
+	/// The secret URI of the sudo key.
+	#[clap(long, value_name = "SURI", default_value = "//Alice")]
+	pub suri: String,
+
+	/// The WebSocket RPC endpoint of a node of the chain.
+	#[clap(long, default_value = "ws://127.0.0.1:9944")]
+	pub url: String,
+}
+
+impl UpgradeRuntimeCmd {
+	/// Run the command.
+	pub fn run(&self) -> Result<()> {
+		tokio::runtime::Runtime::new()?.block_on(self.upgrade())
+	}
+
+	async fn upgrade(&self) -> Result<()> {
+		let code = fs::read(&self.wasm)?;
+		let new_version = embedded_version(&code)?;
+
+		let client = RpcClient::connect(&self.url).await?;
+		let current_version = client.runtime_version().await?;
+		check_upgrade(&current_version, &new_version)?;
+
+		let sudo = sr25519::Pair::from_string(&self.suri, None)
+			.map_err(|e| Error::Input(format!("Invalid secret URI: {:?}", e)))?;
+		let sudo_account = AccountId::from(sudo.public());
+		let on_chain_sudo = client
+			.storage(storage_prefix("Sudo", "Key"), None)
+			.await?
+			.and_then(|key| AccountId::decode(&mut &key[..]).ok());
+		if on_chain_sudo.as_ref() != Some(&sudo_account) {
+			return Err(Error::Input(format!("{} is not the sudo key of the chain", sudo_account)))
+		}
+
+		let call = runtime::Call::Sudo(SudoCall::sudo_unchecked_weight {
+			call: Box::new(SystemCall::set_code { code }.into()),
+			weight: 0,
+		});
+		let params = client.extrinsic_params(&sudo_account).await?;
+		let extrinsic = extrinsic::create_signed(&sudo, call, &params);
+
+		println!(
+			"Upgrading {} from spec version {} to {}",
+			current_version.spec_name, current_version.spec_version, new_version.spec_version
+		);
+		let block_hash = client.submit_and_watch(&extrinsic).await?;
+		println!("Included in block {:?}", block_hash);
+
+		let events = client.events(block_hash).await?;
+		for record in &events {
+			match &record.event {
+				runtime::Event::Sudo(pallet_sudo::Event::Sudid { sudo_result: Err(e) }) =>
+					return Err(Error::Input(format!(
+						"The upgrade was dispatched but failed: {:?}",
+						e
+					))),
+				runtime::Event::System(frame_system::Event::CodeUpdated) => {
+					println!("Runtime code updated, the new version applies from the next block");
+					return Ok(())
+				},
+				_ => {},
+			}
+		}
+
+		Err("The upgrade was included but no `CodeUpdated` event was deposited".into())
+	}
+}
+
+/// Reads the `RuntimeVersion` embedded in the given wasm blob.
+fn embedded_version(code: &[u8]) -> Result<RuntimeVersion> {
+	let blob = RuntimeBlob::uncompress_if_needed(code)
+		.map_err(|e| Error::Input(format!("Invalid wasm blob: {}", e)))?;
+	sc_executor::read_embedded_version(&blob)
+		.map_err(|e| Error::Input(format!("Failed to read the runtime version: {}", e)))?
+		.ok_or_else(|| "The wasm blob does not embed a runtime version".into())
+}
+
+/// Checks that `new` is an upgrade of the `current` runtime.
+fn check_upgrade(current: &RuntimeVersion, new: &RuntimeVersion) -> Result<()> {
+	if new.spec_name != current.spec_name {
+		return Err(Error::Input(format!(
+			"The new runtime is a `{}` runtime, the chain runs `{}`",
+			new.spec_name, current.spec_name
+		)))
+	}
+	if new.spec_version <= current.spec_version {
+		return Err(Error::Input(format!(
+			"The new spec version {} must be greater than the current one, {}",
+			new.spec_version, current.spec_version
+		)))
+	}
+	Ok(())
+}
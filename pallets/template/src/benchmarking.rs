@@ -9,11 +9,19 @@ use frame_system::RawOrigin;
 
 benchmarks! {
 	do_something {
-		let s in 0 .. 100;
 		let caller: T::AccountId = whitelisted_caller();
-	}: _(RawOrigin::Signed(caller), s)
+	}: _(RawOrigin::Signed(caller), 100)
 	verify {
-		assert_eq!(Something::<T>::get(), Some(s));
+		assert_eq!(Something::<T>::get(), Some(100));
+	}
+
+	cause_error {
+		// The increment succeeds, writing the value back.
+		Something::<T>::put(100);
+		let caller: T::AccountId = whitelisted_caller();
+	}: _(RawOrigin::Signed(caller))
+	verify {
+		assert_eq!(Something::<T>::get(), Some(101));
 	}
 
 	impl_benchmark_test_suite!(Template, crate::mock::new_test_ext(), crate::mock::Test);
@@ -0,0 +1,155 @@
+//! The signed extension ordering, prioritizing and rate limiting the calls of this pallet.
+
+use crate::{Call, CallCounter, CallCounters, Config};
+use codec::{Decode, Encode};
+use core::marker::PhantomData;
+use frame_support::{
+	pallet_prelude::*,
+	sp_runtime::traits::{DispatchInfoOf, Dispatchable, SignedExtension},
+	sp_std::prelude::*,
+	traits::{Contains, IsSubType},
+	weights::DispatchInfo,
+};
+
+/// The code of [`InvalidTransaction::Custom`] for calls beyond the rate limit of their signer.
+pub const RATE_LIMITED: u8 = 1;
+
+/// Orders, prioritizes and rate limits the signed calls of this pallet.
+///
+/// Like `CheckNonce` does for all the extrinsics of an account, the extension carries the index of
+/// the call among the calls of its signer to this pallet, starting at 0. The call provides the tag
+/// `("template", signer, index)` and requires the one of the previous index, so that the pool
+/// includes the updates of an account in the order they were signed even when they interleave
+/// with its other transactions.
+///
+/// Calls of the [`Config::Admins`], and operational calls, get [`Config::PriorityBoost`] on top
+/// of the priority given by the other extensions. An account other than the admins makes at most
+/// [`Config::MaxCallsPerWindow`] calls per window of [`Config::CallWindow`] blocks: the pool
+/// rejects the calls beyond the limit of the current window, counting those of the account it
+/// already holds, and the block author skips them.
+///
+/// Reading and writing the counter of the signer is part of the weight of each call, see
+/// [`crate::extensions_weight`].
+///
+/// The index is ignored for calls to other pallets.
+#[derive(Encode, Decode, Clone, Eq, PartialEq, TypeInfo)]
+#[scale_info(skip_type_params(T))]
+pub struct CheckTemplateCall<T: Config + Send + Sync>(#[codec(compact)] pub u32, PhantomData<T>);
+
+impl<T: Config + Send + Sync> CheckTemplateCall<T> {
+	/// Utility constructor, for the call of the given index.
+	pub fn from(index: u32) -> Self {
+		Self(index, PhantomData)
+	}
+
+	/// The counter of `who` at the current block, reset if its window is over.
+	fn counter(who: &T::AccountId) -> CallCounter<T::BlockNumber> {
+		let mut counter = CallCounters::<T>::get(who);
+		let window_start = crate::window_start::<T>(T::CallWindow::get());
+		if counter.window_start != window_start {
+			counter.window_start = window_start;
+			counter.in_window = 0;
+		}
+		counter
+	}
+
+	fn tag(who: &T::AccountId, index: u32) -> TransactionTag {
+		(b"template", who, index).encode()
+	}
+}
+
+impl<T: Config + Send + Sync> core::fmt::Debug for CheckTemplateCall<T> {
+	#[cfg(feature = "std")]
+	fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+		write!(f, "CheckTemplateCall({})", self.0)
+	}
+
+	#[cfg(not(feature = "std"))]
+	fn fmt(&self, _: &mut core::fmt::Formatter) -> core::fmt::Result {
+		Ok(())
+	}
+}
+
+impl<T: Config + Send + Sync> SignedExtension for CheckTemplateCall<T>
+where
+	<T as frame_system::Config>::Call: Dispatchable<Info = DispatchInfo> + IsSubType<Call<T>>,
+{
+	const IDENTIFIER: &'static str = "CheckTemplateCall";
+	type AccountId = T::AccountId;
+	type Call = <T as frame_system::Config>::Call;
+	type AdditionalSigned = ();
+	type Pre = ();
+
+	fn additional_signed(&self) -> Result<(), TransactionValidityError> {
+		Ok(())
+	}
+
+	fn validate(
+		&self,
+		who: &Self::AccountId,
+		call: &Self::Call,
+		info: &DispatchInfoOf<Self::Call>,
+		_len: usize,
+	) -> TransactionValidity {
+		if call.is_sub_type().is_none() {
+			return Ok(ValidTransaction::default())
+		}
+
+		let counter = Self::counter(who);
+		if self.0 < counter.index {
+			return Err(InvalidTransaction::Stale.into())
+		}
+		// The calls of lower index are either in the pool or yet to be submitted, and all of them
+		// count against the current window.
+		let earlier = self.0 - counter.index;
+		let admin = T::Admins::contains(who);
+		if !admin && counter.in_window.saturating_add(earlier) >= T::MaxCallsPerWindow::get() {
+			return Err(InvalidTransaction::Custom(RATE_LIMITED).into())
+		}
+
+		let priority = if info.class == DispatchClass::Operational || admin {
+			T::PriorityBoost::get()
+		} else {
+			0
+		};
+		let requires = if earlier > 0 { vec![Self::tag(who, self.0 - 1)] } else { Vec::new() };
+
+		Ok(ValidTransaction {
+			priority,
+			requires,
+			provides: vec![Self::tag(who, self.0)],
+			longevity: TransactionLongevity::MAX,
+			propagate: true,
+		})
+	}
+
+	fn pre_dispatch(
+		self,
+		who: &Self::AccountId,
+		call: &Self::Call,
+		_info: &DispatchInfoOf<Self::Call>,
+		_len: usize,
+	) -> Result<(), TransactionValidityError> {
+		if call.is_sub_type().is_none() {
+			return Ok(())
+		}
+
+		let mut counter = Self::counter(who);
+		if self.0 != counter.index {
+			return Err(if self.0 < counter.index {
+				InvalidTransaction::Stale
+			} else {
+				InvalidTransaction::Future
+			}
+			.into())
+		}
+		if !T::Admins::contains(who) && counter.in_window >= T::MaxCallsPerWindow::get() {
+			return Err(InvalidTransaction::Custom(RATE_LIMITED).into())
+		}
+
+		counter.index += 1;
+		counter.in_window = counter.in_window.saturating_add(1);
+		CallCounters::<T>::insert(who, counter);
+		Ok(())
+	}
+}
@@ -0,0 +1,230 @@
+//! The signed extension waiving the fees of the calls of this pallet a runtime policy makes free.
+
+use crate::{Call, CallCounters, CheckTemplateCall, Config, FeelessUsage, FeelessUsages, Pallet};
+use codec::{Decode, Encode};
+use frame_support::{
+	pallet_prelude::*,
+	sp_runtime::traits::{
+		DispatchInfoOf, Dispatchable, PostDispatchInfoOf, SaturatedConversion, Saturating,
+		SignedExtension, SignedExtensionMetadata,
+	},
+	sp_std::prelude::*,
+	traits::IsSubType,
+	weights::{DispatchInfo, Weight},
+};
+
+/// Decides which calls of this pallet are free of fees.
+pub trait FeelessPolicy<T: Config> {
+	/// Whether `call` of `who` is free, as long as `who` has free calls left in the period.
+	fn is_feeless(who: &T::AccountId, call: &Call<T>) -> bool;
+
+	/// The weight of [`FeelessPolicy::is_feeless`], such as the storage it reads, at most. It is
+	/// part of the weight of each call of this pallet, see [`crate::extensions_weight`].
+	fn weight() -> Weight;
+}
+
+impl<T: Config> FeelessPolicy<T> for () {
+	fn is_feeless(_: &T::AccountId, _: &Call<T>) -> bool {
+		false
+	}
+
+	fn weight() -> Weight {
+		0
+	}
+}
+
+impl<T: Config> Pallet<T> {
+	/// The free calls `who` made in the current period, if `call` is free for `who`.
+	///
+	/// That is, if `call` is a call of this pallet the [`Config::FeelessPolicy`] makes free, and
+	/// `who` made less than [`Config::MaxFeelessPerPeriod`] free calls in the current period.
+	/// [`ChargeUnlessFeeless`] further requires the call to be the next one of `who`.
+	pub fn feeless_usage(
+		who: &T::AccountId,
+		call: &<T as frame_system::Config>::Call,
+	) -> Option<FeelessUsage<T::BlockNumber>>
+	where
+		<T as frame_system::Config>::Call: IsSubType<Call<T>>,
+	{
+		let call = call.is_sub_type()?;
+		if !T::FeelessPolicy::is_feeless(who, call) {
+			return None
+		}
+
+		let mut usage = FeelessUsages::<T>::get(who);
+		let period_start = crate::window_start::<T>(T::FeelessPeriod::get());
+		if usage.period_start != period_start {
+			usage.period_start = period_start;
+			usage.used = 0;
+		}
+		if usage.used < T::MaxFeelessPerPeriod::get() {
+			Some(usage)
+		} else {
+			None
+		}
+	}
+}
+
+/// Wraps the extension charging the fees, `ChargeTransactionPayment` in a runtime, along with the
+/// [`CheckTemplateCall`] of the call, and skips the former for the calls of this pallet that are
+/// free.
+///
+/// A call is free when the [`Config::FeelessPolicy`] says so, it is the next call of its signer
+/// to this pallet, that is its index is the one [`CheckTemplateCall`] expects at the current
+/// block, and its signer made less than [`Config::MaxFeelessPerPeriod`] free calls in the current
+/// period of [`Config::FeelessPeriod`] blocks; the fees and tip of the call are then waived. Other
+/// calls, including those signed after a call still in the pool and those of a signer out of free
+/// calls, go through the wrapped extension and pay as usual.
+///
+/// Free calls are spam the pool cannot price, so it admits them under tighter limits: they get no
+/// priority besides that of [`CheckTemplateCall`], expire at the end of the period, and provide
+/// the tag `("feeless", signer, period start, free calls made)`. Only the next call of an account
+/// being free, the pool holds at most one free call per account at a time, and validates the
+/// calls queued behind it as paying.
+///
+/// Deciding whether a call is free, and counting the free calls, is part of the weight of each
+/// call, see [`crate::extensions_weight`].
+///
+/// The extension encodes as the wrapped one followed by [`CheckTemplateCall`], and lists both in
+/// the metadata, so that clients building extrinsics see the extensions of an unwrapped runtime.
+#[derive(Encode, Decode, Clone, Eq, PartialEq, TypeInfo)]
+#[scale_info(skip_type_params(T))]
+pub struct ChargeUnlessFeeless<T: Config + Send + Sync, S>(pub S, pub CheckTemplateCall<T>);
+
+impl<T: Config + Send + Sync, S> ChargeUnlessFeeless<T, S> {
+	/// Utility constructor, wrapping `charge` for the template call of the given index.
+	pub fn from(charge: S, index: u32) -> Self {
+		Self(charge, CheckTemplateCall::from(index))
+	}
+
+	/// The free calls `who` made in the current period, if `call` of `who` carrying this
+	/// extension is free, see [`Pallet::feeless_usage`].
+	fn feeless_usage(
+		&self,
+		who: &T::AccountId,
+		call: &<T as frame_system::Config>::Call,
+	) -> Option<FeelessUsage<T::BlockNumber>>
+	where
+		<T as frame_system::Config>::Call: IsSubType<Call<T>>,
+	{
+		// Calls queued behind another call of their signer pay, so that they stay valid whether
+		// or not the earlier one turns out free.
+		if self.1 .0 != CallCounters::<T>::get(who).index {
+			return None
+		}
+		Pallet::<T>::feeless_usage(who, call)
+	}
+
+	/// Whether `call` of `who` carrying this extension is free of fees in the current block.
+	pub fn is_feeless(&self, who: &T::AccountId, call: &<T as frame_system::Config>::Call) -> bool
+	where
+		<T as frame_system::Config>::Call: IsSubType<Call<T>>,
+	{
+		self.feeless_usage(who, call).is_some()
+	}
+
+	fn tag(who: &T::AccountId, usage: &FeelessUsage<T::BlockNumber>) -> TransactionTag {
+		(b"feeless", who, usage.period_start, usage.used).encode()
+	}
+}
+
+impl<T, S> core::fmt::Debug for ChargeUnlessFeeless<T, S>
+where
+	T: Config + Send + Sync,
+	S: core::fmt::Debug,
+{
+	#[cfg(feature = "std")]
+	fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+		write!(f, "ChargeUnlessFeeless({:?}, {:?})", self.0, self.1)
+	}
+
+	#[cfg(not(feature = "std"))]
+	fn fmt(&self, _: &mut core::fmt::Formatter) -> core::fmt::Result {
+		Ok(())
+	}
+}
+
+impl<T, S> SignedExtension for ChargeUnlessFeeless<T, S>
+where
+	T: Config + Send + Sync,
+	<T as frame_system::Config>::Call: Dispatchable<Info = DispatchInfo> + IsSubType<Call<T>>,
+	S: SignedExtension<AccountId = T::AccountId, Call = <T as frame_system::Config>::Call>,
+{
+	const IDENTIFIER: &'static str = S::IDENTIFIER;
+	type AccountId = T::AccountId;
+	type Call = <T as frame_system::Config>::Call;
+	type AdditionalSigned = S::AdditionalSigned;
+	/// `None` for free calls, the `Pre` of the wrapped extension otherwise.
+	type Pre = Option<S::Pre>;
+
+	// `CheckTemplateCall` signs nothing, so the payload is the one of the wrapped extension.
+	fn additional_signed(&self) -> Result<S::AdditionalSigned, TransactionValidityError> {
+		self.0.additional_signed()
+	}
+
+	fn metadata() -> Vec<SignedExtensionMetadata> {
+		let mut metadata = S::metadata();
+		metadata.extend(CheckTemplateCall::<T>::metadata());
+		metadata
+	}
+
+	fn validate(
+		&self,
+		who: &Self::AccountId,
+		call: &Self::Call,
+		info: &DispatchInfoOf<Self::Call>,
+		len: usize,
+	) -> TransactionValidity {
+		let charged = match self.feeless_usage(who, call) {
+			Some(usage) => {
+				let period_end = usage.period_start.saturating_add(T::FeelessPeriod::get());
+				let remaining =
+					period_end.saturating_sub(frame_system::Pallet::<T>::block_number());
+				ValidTransaction {
+					priority: 0,
+					requires: Vec::new(),
+					provides: vec![Self::tag(who, &usage)],
+					longevity: remaining.saturated_into::<u64>().max(1),
+					propagate: true,
+				}
+			},
+			None => self.0.validate(who, call, info, len)?,
+		};
+		Ok(charged.combine_with(self.1.validate(who, call, info, len)?))
+	}
+
+	fn pre_dispatch(
+		self,
+		who: &Self::AccountId,
+		call: &Self::Call,
+		info: &DispatchInfoOf<Self::Call>,
+		len: usize,
+	) -> Result<Self::Pre, TransactionValidityError> {
+		// Decided before `CheckTemplateCall` counts the call.
+		let usage = self.feeless_usage(who, call);
+		self.1.pre_dispatch(who, call, info, len)?;
+
+		let mut usage = match usage {
+			Some(usage) => usage,
+			None => return self.0.pre_dispatch(who, call, info, len).map(Some),
+		};
+		usage.used += 1;
+		FeelessUsages::<T>::insert(who, usage);
+		Ok(None)
+	}
+
+	fn post_dispatch(
+		pre: Option<Self::Pre>,
+		info: &DispatchInfoOf<Self::Call>,
+		post_info: &PostDispatchInfoOf<Self::Call>,
+		len: usize,
+		result: &DispatchResult,
+	) -> Result<(), TransactionValidityError> {
+		match pre {
+			// Free calls were charged nothing, there is nothing to refund.
+			Some(None) => Ok(()),
+			Some(Some(pre)) => S::post_dispatch(Some(pre), info, post_info, len, result),
+			None => S::post_dispatch(None, info, post_info, len, result),
+		}
+	}
+}
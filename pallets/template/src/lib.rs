@@ -14,9 +14,60 @@ mod tests;
 #[cfg(feature = "runtime-benchmarks")]
 mod benchmarking;
 
+pub mod extension;
+pub mod feeless;
+pub mod weights;
+pub use extension::CheckTemplateCall;
+pub use feeless::{ChargeUnlessFeeless, FeelessPolicy};
+pub use weights::WeightInfo;
+
+use codec::{Decode, Encode, MaxEncodedLen};
+use frame_support::{
+	sp_runtime::traits::One,
+	traits::Get,
+	weights::Weight,
+	RuntimeDebug,
+};
+use scale_info::TypeInfo;
+
+/// The calls an account made to this pallet, see [`CheckTemplateCall`].
+#[derive(Clone, Default, PartialEq, Eq, Encode, Decode, RuntimeDebug, TypeInfo, MaxEncodedLen)]
+pub struct CallCounter<BlockNumber> {
+	/// The number of calls the account made, the index its next call must carry.
+	pub index: u32,
+	/// The first block of the last window the account made a call in.
+	pub window_start: BlockNumber,
+	/// The number of calls the account made in that window.
+	pub in_window: u32,
+}
+
+/// The free calls an account made to this pallet, see [`ChargeUnlessFeeless`].
+#[derive(Clone, Default, PartialEq, Eq, Encode, Decode, RuntimeDebug, TypeInfo, MaxEncodedLen)]
+pub struct FeelessUsage<BlockNumber> {
+	/// The first block of the last period the account made a free call in.
+	pub period_start: BlockNumber,
+	/// The number of free calls the account made in that period.
+	pub used: u32,
+}
+
+/// The weight the signed extensions of this pallet add to each of its calls: [`CheckTemplateCall`]
+/// reads and writes the `CallCounters` of the signer, and [`ChargeUnlessFeeless`] asks the
+/// [`Config::FeelessPolicy`] whether the call is free, then reads and writes the `FeelessUsages` of
+/// the signer.
+pub fn extensions_weight<T: Config>() -> Weight {
+	T::DbWeight::get().reads_writes(2, 2).saturating_add(T::FeelessPolicy::weight())
+}
+
+/// The first block of the current window, windows of `length` blocks starting at block 0.
+fn window_start<T: frame_system::Config>(length: T::BlockNumber) -> T::BlockNumber {
+	let now = frame_system::Pallet::<T>::block_number();
+	now - now % length.max(One::one())
+}
+
 #[frame_support::pallet]
 pub mod pallet {
-	use frame_support::pallet_prelude::*;
+	use super::{CallCounter, FeelessPolicy, FeelessUsage, WeightInfo};
+	use frame_support::{pallet_prelude::*, traits::Contains};
 	use frame_system::pallet_prelude::*;
 
 	/// Configure the pallet by specifying the parameters and types on which it depends.
@@ -24,6 +75,35 @@ pub mod pallet {
 	pub trait Config: frame_system::Config {
 		/// Because this pallet emits events, it depends on the runtime's definition of an event.
 		type Event: From<Event<Self>> + IsType<<Self as frame_system::Config>::Event>;
+		/// Weight information for the extrinsics of this pallet.
+		type WeightInfo: WeightInfo;
+
+		/// The accounts whose calls get the `PriorityBoost` in the transaction pool, and are not
+		/// rate limited.
+		type Admins: Contains<Self::AccountId>;
+
+		/// The priority added to the calls of the `Admins`, and to operational calls.
+		#[pallet::constant]
+		type PriorityBoost: Get<TransactionPriority>;
+
+		/// The maximum number of calls an account makes per window.
+		#[pallet::constant]
+		type MaxCallsPerWindow: Get<u32>;
+
+		/// The length of the rate limit windows, in blocks.
+		#[pallet::constant]
+		type CallWindow: Get<Self::BlockNumber>;
+
+		/// Decides which calls are free of fees, see [`crate::ChargeUnlessFeeless`].
+		type FeelessPolicy: FeelessPolicy<Self>;
+
+		/// The maximum number of free calls an account makes per period.
+		#[pallet::constant]
+		type MaxFeelessPerPeriod: Get<u32>;
+
+		/// The length of the periods free calls are counted in, in blocks.
+		#[pallet::constant]
+		type FeelessPeriod: Get<Self::BlockNumber>;
 	}
 
 	#[pallet::pallet]
@@ -38,6 +118,40 @@ pub mod pallet {
 	// https://docs.substrate.io/main-docs/build/runtime-storage/#declaring-storage-items
 	pub type Something<T> = StorageValue<_, u32>;
 
+	/// The calls each account made to this pallet, maintained by [`crate::CheckTemplateCall`].
+	#[pallet::storage]
+	pub type CallCounters<T: Config> =
+		StorageMap<_, Blake2_128Concat, T::AccountId, CallCounter<T::BlockNumber>, ValueQuery>;
+
+	/// The free calls each account made, maintained by [`crate::ChargeUnlessFeeless`].
+	#[pallet::storage]
+	pub type FeelessUsages<T: Config> =
+		StorageMap<_, Blake2_128Concat, T::AccountId, FeelessUsage<T::BlockNumber>, ValueQuery>;
+
+	// The genesis config lets a chain spec provide an initial value for the pallet's storage.
+	// https://docs.substrate.io/main-docs/build/genesis-configuration/
+	#[pallet::genesis_config]
+	pub struct GenesisConfig {
+		/// The initial value of `Something`, left unset if `None`.
+		pub something: Option<u32>,
+	}
+
+	#[cfg(feature = "std")]
+	impl Default for GenesisConfig {
+		fn default() -> Self {
+			Self { something: None }
+		}
+	}
+
+	#[pallet::genesis_build]
+	impl<T: Config> GenesisBuild<T> for GenesisConfig {
+		fn build(&self) {
+			if let Some(something) = self.something {
+				<Something<T>>::put(something);
+			}
+		}
+	}
+
 	// Pallets use events to inform users when important changes are made.
 	// https://docs.substrate.io/main-docs/build/events-errors/
 	#[pallet::event]
@@ -64,7 +178,9 @@ pub mod pallet {
 	impl<T: Config> Pallet<T> {
 		/// An example dispatchable that takes a singles value as a parameter, writes the value to
 		/// storage and emits an event. This function must be dispatched by a signed extrinsic.
-		#[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+		#[pallet::weight(
+			T::WeightInfo::do_something().saturating_add(crate::extensions_weight::<T>())
+		)]
 		pub fn do_something(origin: OriginFor<T>, something: u32) -> DispatchResult {
 			// Check that the extrinsic was signed and get the signer.
 			// This function will return an error if the extrinsic is not signed.
@@ -81,7 +197,9 @@ pub mod pallet {
 		}
 
 		/// An example dispatchable that may throw a custom error.
-		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(1,1))]
+		#[pallet::weight(
+			T::WeightInfo::cause_error().saturating_add(crate::extensions_weight::<T>())
+		)]
 		pub fn cause_error(origin: OriginFor<T>) -> DispatchResult {
 			let _who = ensure_signed(origin)?;
 
@@ -1,5 +1,8 @@
 use crate as pallet_template;
-use frame_support::traits::{ConstU16, ConstU64};
+use frame_support::{
+	traits::{ConstU16, ConstU32, ConstU64, Contains},
+	weights::Weight,
+};
 use frame_system as system;
 use sp_core::H256;
 use sp_runtime::{
@@ -18,7 +21,7 @@ frame_support::construct_runtime!(
 		UncheckedExtrinsic = UncheckedExtrinsic,
 	{
 		System: frame_system::{Pallet, Call, Config, Storage, Event<T>},
-		TemplateModule: pallet_template::{Pallet, Call, Storage, Event<T>},
+		TemplateModule: pallet_template::{Pallet, Call, Config, Storage, Event<T>},
 	}
 );
 
@@ -46,11 +49,43 @@ impl system::Config for Test {
 	type SystemWeightInfo = ();
 	type SS58Prefix = ConstU16<42>;
 	type OnSetCode = ();
-	type MaxConsumers = frame_support::traits::ConstU32<16>;
+	type MaxConsumers = ConstU32<16>;
+}
+
+/// The only admin of the template pallet.
+pub const ADMIN: u64 = 100;
+
+pub struct Admins;
+
+impl Contains<u64> for Admins {
+	fn contains(who: &u64) -> bool {
+		*who == ADMIN
+	}
+}
+
+/// Makes `do_something` free.
+pub struct FreeDoSomething;
+
+impl pallet_template::FeelessPolicy<Test> for FreeDoSomething {
+	fn is_feeless(_: &u64, call: &pallet_template::Call<Test>) -> bool {
+		matches!(call, pallet_template::Call::do_something { .. })
+	}
+
+	fn weight() -> Weight {
+		0
+	}
 }
 
 impl pallet_template::Config for Test {
 	type Event = Event;
+	type WeightInfo = ();
+	type Admins = Admins;
+	type PriorityBoost = ConstU64<1000>;
+	type MaxCallsPerWindow = ConstU32<3>;
+	type CallWindow = ConstU64<10>;
+	type FeelessPolicy = FreeDoSomething;
+	type MaxFeelessPerPeriod = ConstU32<2>;
+	type FeelessPeriod = ConstU64<10>;
 }
 
 // Build genesis storage according to the mock runtime.
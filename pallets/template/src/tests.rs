@@ -1,5 +1,20 @@
-use crate::{mock::*, Error};
-use frame_support::{assert_noop, assert_ok};
+use crate::{
+	extension::RATE_LIMITED, mock::*, CallCounter, CallCounters, ChargeUnlessFeeless,
+	CheckTemplateCall, Error, FeelessUsage, FeelessUsages, GenesisConfig,
+};
+use codec::Encode;
+use frame_support::{
+	assert_noop, assert_ok,
+	weights::{DispatchClass, DispatchInfo},
+};
+use proptest::prelude::*;
+use sp_runtime::{
+	traits::SignedExtension,
+	transaction_validity::{
+		InvalidTransaction, TransactionValidity, TransactionValidityError, ValidTransaction,
+	},
+	DispatchError,
+};
 
 #[test]
 fn it_works_for_default_value() {
@@ -18,3 +33,314 @@ fn correct_error_for_none_value() {
 		assert_noop!(TemplateModule::cause_error(Origin::signed(1)), Error::<Test>::NoneValue);
 	});
 }
+
+#[test]
+fn genesis_config_sets_something() {
+	let mut t = frame_system::GenesisConfig::default().build_storage::<Test>().unwrap();
+	GenesisConfig { something: Some(7) }.assimilate_storage::<Test>(&mut t).unwrap();
+
+	sp_io::TestExternalities::new(t).execute_with(|| {
+		assert_eq!(TemplateModule::something(), Some(7));
+	});
+}
+
+fn template_call() -> Call {
+	Call::TemplateModule(crate::Call::do_something { something: 42 })
+}
+
+fn validate(who: u64, index: u32, call: &Call, info: &DispatchInfo) -> TransactionValidity {
+	CheckTemplateCall::<Test>::from(index).validate(&who, call, info, 0)
+}
+
+fn pre_dispatch(who: u64, index: u32) -> Result<(), TransactionValidityError> {
+	CheckTemplateCall::<Test>::from(index).pre_dispatch(
+		&who,
+		&template_call(),
+		&DispatchInfo::default(),
+		0,
+	)
+}
+
+fn tag(who: u64, index: u32) -> Vec<u8> {
+	(b"template", who, index).encode()
+}
+
+#[test]
+fn check_template_call_ignores_other_calls() {
+	new_test_ext().execute_with(|| {
+		let remark = Call::System(frame_system::Call::remark { remark: vec![] });
+		let info = DispatchInfo::default();
+
+		assert_eq!(validate(1, 7, &remark, &info), Ok(ValidTransaction::default()));
+		assert_ok!(CheckTemplateCall::<Test>::from(7).pre_dispatch(&1, &remark, &info, 0));
+		assert_eq!(CallCounters::<Test>::get(1), Default::default());
+	});
+}
+
+#[test]
+fn check_template_call_orders_the_calls_of_an_account() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let info = DispatchInfo::default();
+
+		let first = validate(1, 0, &template_call(), &info).unwrap();
+		assert_eq!(first.provides, vec![tag(1, 0)]);
+		assert!(first.requires.is_empty());
+		let second = validate(1, 1, &template_call(), &info).unwrap();
+		assert_eq!(second.provides, vec![tag(1, 1)]);
+		assert_eq!(second.requires, vec![tag(1, 0)]);
+
+		assert_eq!(pre_dispatch(1, 1), Err(InvalidTransaction::Future.into()));
+		assert_ok!(pre_dispatch(1, 0));
+		assert_eq!(pre_dispatch(1, 0), Err(InvalidTransaction::Stale.into()));
+		assert_eq!(
+			validate(1, 0, &template_call(), &info),
+			Err(InvalidTransaction::Stale.into())
+		);
+		assert!(validate(1, 1, &template_call(), &info).unwrap().requires.is_empty());
+		// The counters are per account.
+		assert_ok!(pre_dispatch(2, 0));
+	});
+}
+
+#[test]
+fn check_template_call_boosts_admins_and_operational_calls() {
+	new_test_ext().execute_with(|| {
+		let normal = DispatchInfo::default();
+		let operational = DispatchInfo { class: DispatchClass::Operational, ..Default::default() };
+
+		assert_eq!(validate(1, 0, &template_call(), &normal).unwrap().priority, 0);
+		assert_eq!(validate(1, 0, &template_call(), &operational).unwrap().priority, 1000);
+		assert_eq!(validate(ADMIN, 0, &template_call(), &normal).unwrap().priority, 1000);
+	});
+}
+
+#[test]
+fn check_template_call_rate_limits_each_window() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(3);
+		let info = DispatchInfo::default();
+		let rate_limited = Err(InvalidTransaction::Custom(RATE_LIMITED).into());
+
+		// The pool counts the calls it may already hold against the window.
+		assert_ok!(validate(1, 2, &template_call(), &info));
+		assert_eq!(validate(1, 3, &template_call(), &info), rate_limited);
+
+		for index in 0..3 {
+			assert_ok!(pre_dispatch(1, index));
+		}
+		assert_eq!(pre_dispatch(1, 3), rate_limited);
+		assert_eq!(validate(1, 3, &template_call(), &info), rate_limited);
+
+		// The admins are not limited.
+		for index in 0..4 {
+			assert_ok!(validate(ADMIN, index, &template_call(), &info));
+			assert_ok!(pre_dispatch(ADMIN, index));
+		}
+
+		// The next window starts at block 10.
+		System::set_block_number(10);
+		assert_ok!(validate(1, 3, &template_call(), &info));
+		assert_ok!(pre_dispatch(1, 3));
+		assert_eq!(
+			CallCounters::<Test>::get(1),
+			CallCounter { index: 4, window_start: 10, in_window: 1 }
+		);
+	});
+}
+
+/// Wraps `CheckNonZeroSender`, standing for the extension charging the fees.
+type Feeless = ChargeUnlessFeeless<Test, frame_system::CheckNonZeroSender<Test>>;
+
+fn feeless(index: u32) -> Feeless {
+	ChargeUnlessFeeless::from(frame_system::CheckNonZeroSender::new(), index)
+}
+
+fn feeless_tag(who: u64, period_start: u64, used: u32) -> Vec<u8> {
+	(b"feeless", who, period_start, used).encode()
+}
+
+#[test]
+fn charge_unless_feeless_defers_other_calls_to_the_wrapped_extension() {
+	new_test_ext().execute_with(|| {
+		let info = DispatchInfo::default();
+		let remark = Call::System(frame_system::Call::remark { remark: vec![] });
+		let cause_error = Call::TemplateModule(crate::Call::cause_error {});
+
+		for call in [&remark, &cause_error] {
+			assert!(!feeless(0).is_feeless(&1, call));
+			assert_eq!(
+				feeless(0).validate(&0, call, &info, 0),
+				Err(InvalidTransaction::BadSigner.into())
+			);
+		}
+		assert_eq!(feeless(0).validate(&1, &remark, &info, 0), Ok(ValidTransaction::default()));
+		assert_eq!(feeless(0).pre_dispatch(&1, &remark, &info, 0), Ok(Some(())));
+
+		// The calls of the pallet also go through `CheckTemplateCall`.
+		assert_eq!(
+			feeless(0).validate(&1, &cause_error, &info, 0),
+			Ok(ValidTransaction { provides: vec![tag(1, 0)], ..Default::default() })
+		);
+		assert_eq!(feeless(0).pre_dispatch(&1, &cause_error, &info, 0), Ok(Some(())));
+		assert_eq!(
+			feeless(0).pre_dispatch(&1, &cause_error, &info, 0),
+			Err(InvalidTransaction::Stale.into())
+		);
+		assert_eq!(FeelessUsages::<Test>::get(1), Default::default());
+	});
+}
+
+#[test]
+fn charge_unless_feeless_waives_the_free_calls_of_each_period() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(3);
+		let info = DispatchInfo::default();
+		let call = template_call();
+
+		for used in 0..2 {
+			assert!(feeless(used).is_feeless(&1, &call));
+			// The pool holds one free call of an account at a time, until the end of the period.
+			let validity = feeless(used).validate(&1, &call, &info, 0).unwrap();
+			assert_eq!(validity.priority, 0);
+			assert_eq!(validity.provides, vec![feeless_tag(1, 0, used), tag(1, used)]);
+			assert_eq!(validity.longevity, 7);
+			assert_eq!(feeless(used).pre_dispatch(&1, &call, &info, 0), Ok(None));
+		}
+		assert_eq!(FeelessUsages::<Test>::get(1), FeelessUsage { period_start: 0, used: 2 });
+
+		// The account is out of free calls for the period, the wrapped extension applies.
+		assert!(!feeless(2).is_feeless(&1, &call));
+		assert_eq!(
+			feeless(2).validate(&1, &call, &info, 0),
+			Ok(ValidTransaction { provides: vec![tag(1, 2)], ..Default::default() })
+		);
+		assert_eq!(feeless(2).pre_dispatch(&1, &call, &info, 0), Ok(Some(())));
+		// The free calls are per account.
+		assert!(feeless(0).is_feeless(&2, &call));
+
+		// The next period starts at block 10.
+		System::set_block_number(10);
+		let validity = feeless(3).validate(&1, &call, &info, 0).unwrap();
+		assert_eq!(validity.provides, vec![feeless_tag(1, 10, 0), tag(1, 3)]);
+		assert_eq!(validity.longevity, 10);
+		assert_eq!(feeless(3).pre_dispatch(&1, &call, &info, 0), Ok(None));
+		assert_eq!(FeelessUsages::<Test>::get(1), FeelessUsage { period_start: 10, used: 1 });
+	});
+}
+
+#[test]
+fn charge_unless_feeless_only_waives_the_next_call_of_an_account() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(3);
+		let info = DispatchInfo::default();
+		let call = template_call();
+
+		// A call queued behind another one of its signer pays, even if the other is free.
+		assert!(feeless(0).is_feeless(&1, &call));
+		assert!(!feeless(1).is_feeless(&1, &call));
+		assert_eq!(
+			feeless(1).validate(&1, &call, &info, 0),
+			Ok(ValidTransaction {
+				requires: vec![tag(1, 0)],
+				provides: vec![tag(1, 1)],
+				..Default::default()
+			})
+		);
+		assert_eq!(
+			feeless(1).pre_dispatch(&1, &call, &info, 0),
+			Err(InvalidTransaction::Future.into())
+		);
+
+		// Once the other is included, it is the next call and free.
+		assert_eq!(feeless(0).pre_dispatch(&1, &call, &info, 0), Ok(None));
+		assert!(feeless(1).is_feeless(&1, &call));
+		assert_eq!(feeless(1).pre_dispatch(&1, &call, &info, 0), Ok(None));
+		assert_eq!(FeelessUsages::<Test>::get(1), FeelessUsage { period_start: 0, used: 2 });
+	});
+}
+
+/// A call of the pallet, for generating sequences of them.
+#[derive(Clone, Debug)]
+enum Op {
+	DoSomething(u32),
+	CauseError,
+}
+
+fn op() -> impl Strategy<Value = Op> {
+	prop_oneof![
+		// Bias towards values next to the overflow boundary.
+		prop_oneof![any::<u32>(), (u32::MAX - 4)..=u32::MAX].prop_map(Op::DoSomething),
+		Just(Op::CauseError),
+	]
+}
+
+proptest! {
+	#[test]
+	fn cause_error_increments_or_reports_overflow(value in any::<u32>()) {
+		new_test_ext().execute_with(|| {
+			assert_ok!(TemplateModule::do_something(Origin::signed(1), value));
+
+			match value.checked_add(1) {
+				Some(next) => {
+					assert_ok!(TemplateModule::cause_error(Origin::signed(1)));
+					prop_assert_eq!(TemplateModule::something(), Some(next));
+				},
+				None => {
+					assert_noop!(
+						TemplateModule::cause_error(Origin::signed(1)),
+						Error::<Test>::StorageOverflow
+					);
+				},
+			}
+			Ok(())
+		})?;
+	}
+
+	#[test]
+	fn cause_error_overflows_after_exactly_the_remaining_increments(distance in 0u32..16) {
+		new_test_ext().execute_with(|| {
+			assert_ok!(TemplateModule::do_something(Origin::signed(1), u32::MAX - distance));
+
+			for _ in 0..distance {
+				assert_ok!(TemplateModule::cause_error(Origin::signed(1)));
+			}
+			prop_assert_eq!(TemplateModule::something(), Some(u32::MAX));
+			assert_noop!(
+				TemplateModule::cause_error(Origin::signed(1)),
+				Error::<Test>::StorageOverflow
+			);
+			Ok(())
+		})?;
+	}
+
+	#[test]
+	fn storage_follows_a_model_of_the_calls(ops in prop::collection::vec(op(), 0..64)) {
+		new_test_ext().execute_with(|| {
+			let mut model: Option<u32> = None;
+			for op in ops {
+				match op {
+					Op::DoSomething(value) => {
+						assert_ok!(TemplateModule::do_something(Origin::signed(1), value));
+						model = Some(value);
+					},
+					Op::CauseError => {
+						let expected: Result<u32, DispatchError> = match model {
+							None => Err(Error::<Test>::NoneValue.into()),
+							Some(old) => old
+								.checked_add(1)
+								.ok_or_else(|| Error::<Test>::StorageOverflow.into()),
+						};
+						let result = TemplateModule::cause_error(Origin::signed(1));
+						prop_assert_eq!(result, expected.clone().map(|_| ()));
+						if let Ok(new) = expected {
+							model = Some(new);
+						}
+					},
+				}
+				prop_assert_eq!(TemplateModule::something(), model);
+			}
+			Ok(())
+		})?;
+	}
+}
@@ -0,0 +1,47 @@
+//! Weights for pallet-template.
+//!
+//! The node template runtime uses the weights it measures with `node-template benchmark all`, in
+//! `runtime/src/weights/pallet_template.rs`. These are the defaults for runtimes that have not
+//! benchmarked the pallet: a fixed execution cost plus the storage accesses of each call. The
+//! storage accesses of the signed extensions come on top, see [`crate::extensions_weight`].
+
+#![allow(unused_parens)]
+
+use core::marker::PhantomData;
+use frame_support::{
+	traits::Get,
+	weights::{constants::RocksDbWeight, Weight},
+};
+
+/// Weight functions needed for pallet-template.
+pub trait WeightInfo {
+	fn do_something() -> Weight;
+	fn cause_error() -> Weight;
+}
+
+/// Weights for pallet-template using the database weights of the runtime.
+pub struct SubstrateWeight<T>(PhantomData<T>);
+impl<T: frame_system::Config> WeightInfo for SubstrateWeight<T> {
+	// Storage: TemplateModule Something (r:0 w:1)
+	fn do_something() -> Weight {
+		(10_000 as Weight).saturating_add(T::DbWeight::get().writes(1 as Weight))
+	}
+	// Storage: TemplateModule Something (r:1 w:1)
+	fn cause_error() -> Weight {
+		(10_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(1 as Weight))
+			.saturating_add(T::DbWeight::get().writes(1 as Weight))
+	}
+}
+
+// For backwards compatibility and tests
+impl WeightInfo for () {
+	fn do_something() -> Weight {
+		(10_000 as Weight).saturating_add(RocksDbWeight::get().writes(1 as Weight))
+	}
+	fn cause_error() -> Weight {
+		(10_000 as Weight)
+			.saturating_add(RocksDbWeight::get().reads(1 as Weight))
+			.saturating_add(RocksDbWeight::get().writes(1 as Weight))
+	}
+}
@@ -0,0 +1,212 @@
+//! Feeds arbitrary sequences of calls from arbitrary origins into the runtime.
+//!
+//! The calls are decoded from the fuzzer's bytes and applied as extrinsics through `Executive`,
+//! the way a block author applies them, over blocks of a single-authority chain. After each block
+//! the target checks that the total issuance matches the sum of all balances and that the block
+//! weight stayed within the limits. A panic anywhere is a crash.
+//!
+//! ```sh
+//! SKIP_WASM_BUILD=1 cargo +nightly fuzz run runtime_dispatch
+//! ```
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use codec::{DecodeLimit, Encode};
+use frame_support::weights::{DispatchClass, GetDispatchInfo};
+use libfuzzer_sys::fuzz_target;
+use node_template_runtime::{
+	pallet_template, AccountId, AuraConfig, Balance, Balances, BalancesConfig, BlockNumber,
+	BlockWeights, BuildStorage, Call, Executive, GenesisConfig, Hash, Header, Runtime, SignedExtra,
+	SignedPayload, Sudo, SudoCall, SudoConfig, System, SystemCall, SystemConfig, TimestampCall,
+	UncheckedExtrinsic, SLOT_DURATION,
+};
+use sp_consensus_aura::{Slot, AURA_ENGINE_ID};
+use sp_keyring::Sr25519Keyring;
+use sp_runtime::{generic::Era, traits::Header as _, Digest, DigestItem};
+
+/// The accounts signing extrinsics, the first four endowed at genesis and Alice as sudo.
+const ACCOUNTS: [Sr25519Keyring; 6] = [
+	Sr25519Keyring::Alice,
+	Sr25519Keyring::Bob,
+	Sr25519Keyring::Charlie,
+	Sr25519Keyring::Dave,
+	Sr25519Keyring::Eve,
+	Sr25519Keyring::Ferdie,
+];
+
+/// The balance of every endowed account at genesis.
+const ENDOWMENT: Balance = 1 << 60;
+
+/// The origin an extrinsic is dispatched from.
+#[derive(Arbitrary, Debug)]
+enum Origin {
+	/// Signed by one of `ACCOUNTS`.
+	Signed(u8),
+	/// Wrapped in `Sudo::sudo`, signed by the sudo key.
+	Root,
+	/// Unsigned.
+	Unsigned,
+}
+
+#[derive(Arbitrary, Debug)]
+enum Action {
+	/// Applies the SCALE encoded `call`, skipped if it does not decode.
+	Apply { origin: Origin, call: Vec<u8> },
+	/// Finalizes the current block and starts the next one.
+	NextBlock,
+}
+
+fuzz_target!(|actions: Vec<Action>| {
+	let mut ext: sp_io::TestExternalities = genesis_config().build_storage().unwrap().into();
+	ext.execute_with(|| {
+		start_block(1, System::parent_hash());
+		for action in actions {
+			match action {
+				Action::Apply { origin, call } => apply(origin, &call),
+				Action::NextBlock => next_block(),
+			}
+		}
+		next_block();
+	});
+});
+
+fn genesis_config() -> GenesisConfig {
+	GenesisConfig {
+		// Only the native runtime is fuzzed.
+		system: SystemConfig { code: Vec::new() },
+		balances: BalancesConfig {
+			balances: ACCOUNTS[..4].iter().map(|key| (key.to_account_id(), ENDOWMENT)).collect(),
+		},
+		aura: AuraConfig { authorities: vec![Sr25519Keyring::Alice.public().into()] },
+		sudo: SudoConfig { key: Some(Sr25519Keyring::Alice.to_account_id()) },
+		..Default::default()
+	}
+}
+
+/// Initializes block `number` in Aura slot `number` on top of `parent_hash`, and applies its
+/// timestamp inherent.
+fn start_block(number: BlockNumber, parent_hash: Hash) {
+	let slot = Slot::from(number as u64);
+	let digest = Digest { logs: vec![DigestItem::PreRuntime(AURA_ENGINE_ID, slot.encode())] };
+	Executive::initialize_block(&Header::new(
+		number,
+		Default::default(),
+		Default::default(),
+		parent_hash,
+		digest,
+	));
+
+	let now = number as u64 * SLOT_DURATION;
+	let timestamp = UncheckedExtrinsic::new_unsigned(Call::Timestamp(TimestampCall::set { now }));
+	Executive::apply_extrinsic(timestamp).unwrap().unwrap();
+}
+
+/// Checks the invariants of the current block, finalizes it and starts the next one.
+fn next_block() {
+	check_weight();
+	let header = Executive::finalize_block();
+	check_issuance();
+	start_block(header.number + 1, header.hash());
+}
+
+fn apply(origin: Origin, call: &[u8]) {
+	let call = match Call::decode_with_depth_limit(sp_api::MAX_EXTRINSIC_DEPTH, &mut &call[..]) {
+		Ok(call) => call,
+		Err(_) => return,
+	};
+	if !is_fuzzed(&call) {
+		return
+	}
+
+	let extrinsic = match origin {
+		Origin::Signed(index) => sign(ACCOUNTS[index as usize % ACCOUNTS.len()], call),
+		Origin::Root => match Sudo::key().and_then(|key| keyring(&key)) {
+			Some(sudo) => sign(sudo, Call::Sudo(SudoCall::sudo { call: Box::new(call) })),
+			// The sudo key was handed to an account the fuzzer cannot sign for.
+			None => return,
+		},
+		// The transaction pool refuses inherents, only the block author includes them.
+		Origin::Unsigned if call.get_dispatch_info().class == DispatchClass::Mandatory => return,
+		Origin::Unsigned => UncheckedExtrinsic::new_unsigned(call),
+	};
+	// Invalid extrinsics and failing calls are expected, only panics are not.
+	let _ = Executive::apply_extrinsic(extrinsic);
+}
+
+/// Whether the fuzzer applies `call`, excluding the root calls that replace the runtime or write
+/// raw storage, whose effects are neither meaningful for the native runtime nor bound by any
+/// invariant.
+fn is_fuzzed(call: &Call) -> bool {
+	match call {
+		Call::System(
+			SystemCall::set_code { .. } |
+			SystemCall::set_code_without_checks { .. } |
+			SystemCall::set_storage { .. } |
+			SystemCall::kill_storage { .. } |
+			SystemCall::kill_prefix { .. },
+		) => false,
+		Call::Sudo(
+			SudoCall::sudo { call } |
+			SudoCall::sudo_unchecked_weight { call, .. } |
+			SudoCall::sudo_as { call, .. },
+		) => is_fuzzed(call),
+		_ => true,
+	}
+}
+
+fn keyring(account: &AccountId) -> Option<Sr25519Keyring> {
+	ACCOUNTS.iter().copied().find(|key| key.to_account_id() == *account)
+}
+
+/// An immortal extrinsic calling `call`, signed by `signer` with its current nonce and template
+/// call index.
+fn sign(signer: Sr25519Keyring, call: Call) -> UncheckedExtrinsic {
+	let nonce = System::account_nonce(signer.to_account_id());
+	let template_index =
+		pallet_template::CallCounters::<Runtime>::get(signer.to_account_id()).index;
+	let extra: SignedExtra = (
+		frame_system::CheckNonZeroSender::new(),
+		frame_system::CheckSpecVersion::new(),
+		frame_system::CheckTxVersion::new(),
+		frame_system::CheckGenesis::new(),
+		frame_system::CheckEra::from(Era::Immortal),
+		frame_system::CheckNonce::from(nonce),
+		frame_system::CheckWeight::new(),
+		pallet_template::ChargeUnlessFeeless::from(
+			pallet_transaction_payment::ChargeTransactionPayment::from(0),
+			template_index,
+		),
+	);
+	let payload = SignedPayload::new(call, extra).unwrap();
+	let signature = payload.using_encoded(|payload| signer.sign(payload));
+	let (call, extra, _) = payload.deconstruct();
+	UncheckedExtrinsic::new_signed(call, signer.to_account_id().into(), signature.into(), extra)
+}
+
+/// Checks that no dispatch class used more than its share of the block weight, and that the
+/// block as a whole stayed within its maximum. Mandatory dispatches are exempt from both limits.
+fn check_weight() {
+	let limits = BlockWeights::get();
+	let consumed = System::block_weight();
+	for class in [DispatchClass::Normal, DispatchClass::Operational] {
+		let max = limits.get(class).max_total.unwrap_or(limits.max_block);
+		assert!(
+			*consumed.get(class) <= max,
+			"{:?} weight {} exceeds its limit {}",
+			class,
+			consumed.get(class),
+			max,
+		);
+	}
+	let total = consumed.total().saturating_sub(*consumed.get(DispatchClass::Mandatory));
+	assert!(total <= limits.max_block, "Block weight {} exceeds {}", total, limits.max_block);
+}
+
+/// Checks that the total issuance is the sum of the balances of all accounts.
+fn check_issuance() {
+	let balances: Balance = frame_system::Account::<Runtime>::iter_values()
+		.map(|account| account.data.free + account.data.reserved)
+		.sum();
+	assert_eq!(Balances::total_issuance(), balances, "Total issuance is inconsistent");
+}
@@ -14,8 +14,11 @@ use sp_consensus_aura::sr25519::AuthorityId as AuraId;
 use sp_core::{crypto::KeyTypeId, OpaqueMetadata};
 use sp_runtime::{
 	create_runtime_str, generic, impl_opaque_keys,
-	traits::{AccountIdLookup, BlakeTwo256, Block as BlockT, IdentifyAccount, NumberFor, Verify},
-	transaction_validity::{TransactionSource, TransactionValidity},
+	traits::{
+		AccountIdLookup, BlakeTwo256, Block as BlockT, IdentifyAccount, NumberFor, StaticLookup,
+		Verify,
+	},
+	transaction_validity::{TransactionPriority, TransactionSource, TransactionValidity},
 	ApplyExtrinsicResult, MultiSignature,
 };
 use sp_std::prelude::*;
@@ -27,7 +30,8 @@ use sp_version::RuntimeVersion;
 pub use frame_support::{
 	construct_runtime, parameter_types,
 	traits::{
-		ConstU128, ConstU32, ConstU64, ConstU8, KeyOwnerProofSystem, Randomness, StorageInfo,
+		ConstU128, ConstU32, ConstU64, ConstU8, Contains, Get, KeyOwnerProofSystem, Randomness,
+		StorageInfo,
 	},
 	weights::{
 		constants::{BlockExecutionWeight, ExtrinsicBaseWeight, RocksDbWeight, WEIGHT_PER_SECOND},
@@ -37,6 +41,7 @@ pub use frame_support::{
 };
 pub use frame_system::Call as SystemCall;
 pub use pallet_balances::Call as BalancesCall;
+pub use pallet_sudo::Call as SudoCall;
 pub use pallet_timestamp::Call as TimestampCall;
 use pallet_transaction_payment::CurrencyAdapter;
 #[cfg(any(feature = "std", test))]
@@ -46,6 +51,9 @@ pub use sp_runtime::{Perbill, Permill};
 /// Import the template pallet.
 pub use pallet_template;
 
+pub mod migrations;
+pub mod weights;
+
 /// An index to a block.
 pub type BlockNumber = u32;
 
@@ -99,12 +107,12 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	// The version of the runtime specification. A full node will not attempt to use its native
 	//   runtime in substitute for the on-chain Wasm runtime unless all of `spec_name`,
 	//   `spec_version`, and `authoring_version` are the same between Wasm and native.
-	// This value is set to 100 to notify Polkadot-JS App (https://polkadot.js.org/apps) to use
+	// This value started at 100 to notify Polkadot-JS App (https://polkadot.js.org/apps) to use
 	//   the compatible custom types.
-	spec_version: 100,
+	spec_version: 101,
 	impl_version: 1,
 	apis: RUNTIME_API_VERSIONS,
-	transaction_version: 1,
+	transaction_version: 2,
 	state_version: 1,
 };
 
@@ -190,7 +198,7 @@ impl frame_system::Config for Runtime {
 	/// The data to be stored in an account.
 	type AccountData = pallet_balances::AccountData<Balance>;
 	/// Weight information for the extrinsics of this pallet.
-	type SystemWeightInfo = ();
+	type SystemWeightInfo = weights::frame_system::WeightInfo<Runtime>;
 	/// This is used as an identifier of the chain. 42 is the generic substrate prefix.
 	type SS58Prefix = SS58Prefix;
 	/// The set code logic, just the default since we're not a parachain.
@@ -222,7 +230,7 @@ impl pallet_grandpa::Config for Runtime {
 
 	type HandleEquivocation = ();
 
-	type WeightInfo = ();
+	type WeightInfo = weights::grandpa::WeightInfo<Runtime>;
 	type MaxAuthorities = ConstU32<32>;
 }
 
@@ -231,7 +239,7 @@ impl pallet_timestamp::Config for Runtime {
 	type Moment = u64;
 	type OnTimestampSet = Aura;
 	type MinimumPeriod = ConstU64<{ SLOT_DURATION / 2 }>;
-	type WeightInfo = ();
+	type WeightInfo = weights::pallet_timestamp::WeightInfo<Runtime>;
 }
 
 /// Existential deposit.
@@ -248,7 +256,7 @@ impl pallet_balances::Config for Runtime {
 	type DustRemoval = ();
 	type ExistentialDeposit = ConstU128<EXISTENTIAL_DEPOSIT>;
 	type AccountStore = System;
-	type WeightInfo = pallet_balances::weights::SubstrateWeight<Runtime>;
+	type WeightInfo = weights::pallet_balances::WeightInfo<Runtime>;
 }
 
 impl pallet_transaction_payment::Config for Runtime {
@@ -265,9 +273,51 @@ impl pallet_sudo::Config for Runtime {
 	type Call = Call;
 }
 
+/// The sudo key, the admin of the template pallet.
+pub struct SudoKey;
+
+impl Contains<AccountId> for SudoKey {
+	fn contains(who: &AccountId) -> bool {
+		Sudo::key().as_ref() == Some(who)
+	}
+}
+
+parameter_types! {
+	/// Puts the template calls of the sudo key, and operational ones, ahead of the normal
+	/// transactions of any tip.
+	pub const TemplatePriorityBoost: TransactionPriority = TransactionPriority::max_value() / 2;
+}
+
+/// The number of template calls an account other than the sudo key makes per window.
+pub type MaxTemplateCallsPerWindow = ConstU32<20>;
+
+/// Makes `do_something` free, once a day, for the accounts holding at least the existential
+/// deposit, so that spamming free calls takes funding an account per call a day.
+pub struct FreeDoSomething;
+
+impl pallet_template::FeelessPolicy<Runtime> for FreeDoSomething {
+	fn is_feeless(who: &AccountId, call: &pallet_template::Call<Runtime>) -> bool {
+		matches!(call, pallet_template::Call::do_something { .. }) &&
+			Balances::free_balance(who) >= EXISTENTIAL_DEPOSIT
+	}
+
+	/// Reading the balance of the signer from `System::Account`.
+	fn weight() -> Weight {
+		RocksDbWeight::get().reads(1)
+	}
+}
+
 /// Configure the pallet-template in pallets/template.
 impl pallet_template::Config for Runtime {
 	type Event = Event;
+	type WeightInfo = weights::pallet_template::WeightInfo<Runtime>;
+	type Admins = SudoKey;
+	type PriorityBoost = TemplatePriorityBoost;
+	type MaxCallsPerWindow = MaxTemplateCallsPerWindow;
+	type CallWindow = ConstU32<MINUTES>;
+	type FeelessPolicy = FreeDoSomething;
+	type MaxFeelessPerPeriod = ConstU32<1>;
+	type FeelessPeriod = ConstU32<DAYS>;
 }
 
 // Create the runtime by composing the FRAME pallets that were previously configured.
@@ -305,7 +355,11 @@ pub type SignedExtra = (
 	frame_system::CheckEra<Runtime>,
 	frame_system::CheckNonce<Runtime>,
 	frame_system::CheckWeight<Runtime>,
-	pallet_transaction_payment::ChargeTransactionPayment<Runtime>,
+	// Also carries the `CheckTemplateCall` of the extrinsic.
+	pallet_template::ChargeUnlessFeeless<
+		Runtime,
+		pallet_transaction_payment::ChargeTransactionPayment<Runtime>,
+	>,
 );
 /// Unchecked extrinsic type as expected by this runtime.
 pub type UncheckedExtrinsic = generic::UncheckedExtrinsic<Address, Call, Signature, SignedExtra>;
@@ -318,6 +372,7 @@ pub type Executive = frame_executive::Executive<
 	frame_system::ChainContext<Runtime>,
 	Runtime,
 	AllPalletsWithSystem,
+	migrations::Migrations,
 >;
 
 #[cfg(feature = "runtime-benchmarks")]
@@ -330,11 +385,27 @@ mod benches {
 		[frame_benchmarking, BaselineBench::<Runtime>]
 		[frame_system, SystemBench::<Runtime>]
 		[pallet_balances, Balances]
+		[pallet_grandpa, Grandpa]
 		[pallet_timestamp, Timestamp]
 		[pallet_template, TemplateModule]
 	);
 }
 
+/// Whether the fees of `uxt` are waived, see [`pallet_template::ChargeUnlessFeeless`].
+fn is_feeless(uxt: &UncheckedExtrinsic) -> bool {
+	let (address, _, extra) = match &uxt.signature {
+		Some(signature) => signature,
+		None => return false,
+	};
+	match <Runtime as frame_system::Config>::Lookup::lookup(address.clone()) {
+		Ok(who) => extra.7.is_feeless(&who, &uxt.function),
+		Err(_) => false,
+	}
+}
+
+#[cfg(test)]
+mod tests;
+
 impl_runtime_apis! {
 	impl sp_api::Core<Block> for Runtime {
 		fn version() -> RuntimeVersion {
@@ -456,12 +527,20 @@ impl_runtime_apis! {
 			uxt: <Block as BlockT>::Extrinsic,
 			len: u32,
 		) -> pallet_transaction_payment_rpc_runtime_api::RuntimeDispatchInfo<Balance> {
-			TransactionPayment::query_info(uxt, len)
+			let feeless = is_feeless(&uxt);
+			let mut info = TransactionPayment::query_info(uxt, len);
+			if feeless {
+				info.partial_fee = 0;
+			}
+			info
 		}
 		fn query_fee_details(
 			uxt: <Block as BlockT>::Extrinsic,
 			len: u32,
 		) -> pallet_transaction_payment::FeeDetails<Balance> {
+			if is_feeless(&uxt) {
+				return pallet_transaction_payment::FeeDetails { inclusion_fee: None, tip: 0 }
+			}
 			TransactionPayment::query_fee_details(uxt, len)
 		}
 	}
@@ -520,15 +599,28 @@ impl_runtime_apis! {
 	#[cfg(feature = "try-runtime")]
 	impl frame_try_runtime::TryRuntime<Block> for Runtime {
 		fn on_runtime_upgrade() -> (Weight, Weight) {
-			// NOTE: intentional unwrap: we don't want to propagate the error backwards, and want to
-			// have a backtrace here. If any of the pre/post migration checks fail, we shall stop
-			// right here and right now.
-			let weight = Executive::try_runtime_upgrade().unwrap();
-			(weight, BlockWeights::get().max_block)
+			// Stop right here if any of the pre/post migration checks fail, naming the migration.
+			// `TryMigrationsApi::try_upgrade` reports on every migration instead.
+			let report = migrations::try_upgrade();
+			for migration in &report.migrations {
+				if let Some((check, error)) = migration.failed_check() {
+					let name = sp_std::str::from_utf8(&migration.name).unwrap_or_default();
+					let error = sp_std::str::from_utf8(error).unwrap_or_default();
+					panic!("{} of {} failed: {}", check, name, error);
+				}
+			}
+			(report.weight, report.max_weight)
 		}
 
 		fn execute_block_no_check(block: Block) -> Weight {
 			Executive::execute_block_no_check(block)
 		}
 	}
+
+	#[cfg(feature = "try-runtime")]
+	impl migrations::TryMigrationsApi<Block> for Runtime {
+		fn try_upgrade() -> migrations::UpgradeReport {
+			migrations::try_upgrade()
+		}
+	}
 }
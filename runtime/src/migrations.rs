@@ -0,0 +1,122 @@
+//! The migrations run on runtime upgrades, and their `try-runtime` checks.
+//!
+//! With the `try-runtime` feature, the runtime exposes [`TryMigrationsApi`], which runs every
+//! migration with its `pre_upgrade` and `post_upgrade` checks and reports on each of them instead
+//! of stopping at the first failure. The node's `snapshot on-runtime-upgrade` command calls it.
+
+use codec::{Decode, Encode};
+use frame_support::weights::Weight;
+use sp_runtime::RuntimeDebug;
+use sp_std::prelude::*;
+
+#[cfg(feature = "try-runtime")]
+use frame_support::traits::OnRuntimeUpgrade;
+
+/// The migrations run before the `on_runtime_upgrade` hooks of the pallets, oldest first.
+///
+/// Add a migration here along with the upgrade that needs it, and remove it once that upgrade has
+/// been enacted on every chain.
+pub type Migrations = ();
+
+/// The outcome of one migration, or of the `on_runtime_upgrade` hook of one pallet.
+#[derive(Clone, PartialEq, Eq, Encode, Decode, RuntimeDebug)]
+pub struct MigrationReport {
+	/// The type name of the migration or pallet.
+	pub name: Vec<u8>,
+	/// The weight the migration reported.
+	pub weight: Weight,
+	/// The outcome of the `pre_upgrade` check, with its error message.
+	pub pre_upgrade: Result<(), Vec<u8>>,
+	/// The outcome of the `post_upgrade` check, with its error message.
+	pub post_upgrade: Result<(), Vec<u8>>,
+}
+
+impl MigrationReport {
+	/// Whether both checks of the migration passed.
+	pub fn is_ok(&self) -> bool {
+		self.failed_check().is_none()
+	}
+
+	/// The name of the first check that failed, with its error message.
+	pub fn failed_check(&self) -> Option<(&'static str, &[u8])> {
+		match (&self.pre_upgrade, &self.post_upgrade) {
+			(Err(error), _) => Some(("pre_upgrade", error)),
+			(_, Err(error)) => Some(("post_upgrade", error)),
+			_ => None,
+		}
+	}
+
+	/// Runs the checks and the migration `M`.
+	#[cfg(feature = "try-runtime")]
+	fn run<M: OnRuntimeUpgrade>() -> Self {
+		let pre_upgrade = M::pre_upgrade().map_err(|e| e.as_bytes().to_vec());
+		let weight = M::on_runtime_upgrade();
+		let post_upgrade = M::post_upgrade().map_err(|e| e.as_bytes().to_vec());
+		Self {
+			name: core::any::type_name::<M>().as_bytes().to_vec(),
+			weight,
+			pre_upgrade,
+			post_upgrade,
+		}
+	}
+}
+
+/// The outcome of a runtime upgrade.
+#[derive(Clone, PartialEq, Eq, Encode, Decode, RuntimeDebug)]
+pub struct UpgradeReport {
+	/// The [`Migrations`], followed by the `on_runtime_upgrade` hooks of the pallets, in the order
+	/// they ran.
+	pub migrations: Vec<MigrationReport>,
+	/// The total weight of the upgrade.
+	pub weight: Weight,
+	/// The maximum weight of a block.
+	pub max_weight: Weight,
+}
+
+impl UpgradeReport {
+	/// Whether every check passed and the upgrade fits in a block.
+	pub fn is_ok(&self) -> bool {
+		self.migrations.iter().all(MigrationReport::is_ok) && self.weight <= self.max_weight
+	}
+}
+
+/// Runs migrations one after the other, each with its checks, collecting a report of each.
+#[cfg(feature = "try-runtime")]
+pub trait TryMigrate {
+	/// Runs the migrations, appending their reports to `reports`.
+	fn try_migrate(reports: &mut Vec<MigrationReport>);
+}
+
+#[cfg(feature = "try-runtime")]
+#[impl_trait_for_tuples::impl_for_tuples(30)]
+#[tuple_types_custom_trait_bound(OnRuntimeUpgrade)]
+impl TryMigrate for Tuple {
+	fn try_migrate(reports: &mut Vec<MigrationReport>) {
+		for_tuples!( #( reports.push(MigrationReport::run::<Tuple>()); )* );
+	}
+}
+
+/// Runs the runtime upgrade the way `Executive` does, the [`Migrations`] then the hooks of all
+/// pallets, but checking each migration right before and after it runs and carrying on past
+/// failed checks.
+#[cfg(feature = "try-runtime")]
+pub fn try_upgrade() -> UpgradeReport {
+	let mut migrations = Vec::new();
+	Migrations::try_migrate(&mut migrations);
+	crate::AllPalletsWithSystem::try_migrate(&mut migrations);
+
+	UpgradeReport {
+		weight: migrations.iter().fold(0, |total: Weight, m| total.saturating_add(m.weight)),
+		max_weight: crate::BlockWeights::get().max_block,
+		migrations,
+	}
+}
+
+#[cfg(feature = "try-runtime")]
+sp_api::decl_runtime_apis! {
+	/// Runs the runtime upgrade on the current state, reporting on each migration.
+	pub trait TryMigrationsApi {
+		/// Runs the upgrade, see [`try_upgrade`].
+		fn try_upgrade() -> UpgradeReport;
+	}
+}
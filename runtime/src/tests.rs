@@ -0,0 +1,545 @@
+use crate::*;
+use codec::Encode;
+use frame_support::{
+	assert_ok,
+	traits::Currency,
+	weights::{DispatchClass, GetDispatchInfo},
+};
+use sp_consensus_aura::{Slot, AURA_ENGINE_ID};
+use sp_core::{storage::StateVersion, traits::CodeExecutor, H256};
+use sp_externalities::Externalities;
+use sp_keyring::{
+	Ed25519Keyring,
+	Sr25519Keyring::{self, Alice, Bob, Charlie, One},
+};
+use sp_runtime::{
+	generic::Era,
+	traits::Header as _,
+	transaction_validity::{InvalidTransaction, TransactionSource, TransactionValidityError},
+	Digest, DigestItem,
+};
+
+/// The balance of every endowed account at genesis, as in the node's `testnet_genesis`.
+const ENDOWMENT: Balance = 1 << 60;
+
+/// The genesis of a single-authority chain, with Alice, Bob and Charlie endowed and Alice as sudo.
+fn genesis_config() -> GenesisConfig {
+	GenesisConfig {
+		system: SystemConfig { code: WASM_BINARY.unwrap_or_default().to_vec() },
+		balances: BalancesConfig {
+			balances: [Alice, Bob, Charlie]
+				.iter()
+				.map(|key| (key.to_account_id(), ENDOWMENT))
+				.collect(),
+		},
+		aura: AuraConfig { authorities: vec![Alice.public().into()] },
+		grandpa: GrandpaConfig { authorities: vec![(Ed25519Keyring::Alice.public().into(), 1)] },
+		sudo: SudoConfig { key: Some(Alice.to_account_id()) },
+		transaction_payment: Default::default(),
+		template_module: TemplateModuleConfig { something: None },
+	}
+}
+
+fn new_test_ext() -> sp_io::TestExternalities {
+	genesis_config().build_storage().unwrap().into()
+}
+
+/// Initializes block `number` in Aura slot `number` on top of `parent_hash`, and applies its
+/// timestamp inherent.
+fn start_block(number: BlockNumber, parent_hash: Hash) {
+	let slot = Slot::from(number as u64);
+	let digest = Digest { logs: vec![DigestItem::PreRuntime(AURA_ENGINE_ID, slot.encode())] };
+	Executive::initialize_block(&Header::new(
+		number,
+		Default::default(),
+		Default::default(),
+		parent_hash,
+		digest,
+	));
+
+	let now = number as u64 * SLOT_DURATION;
+	let timestamp = UncheckedExtrinsic::new_unsigned(Call::Timestamp(TimestampCall::set { now }));
+	assert_ok!(Executive::apply_extrinsic(timestamp));
+}
+
+/// Finalizes the current block and starts the next one, returning the finalized header.
+fn next_block() -> Header {
+	let header = Executive::finalize_block();
+	start_block(header.number + 1, header.hash());
+	header
+}
+
+/// Starts block 1 on top of genesis.
+fn start_chain() {
+	start_block(1, System::parent_hash());
+}
+
+fn signed_extra(nonce: Index, template_index: u32, era: Era) -> SignedExtra {
+	(
+		frame_system::CheckNonZeroSender::new(),
+		frame_system::CheckSpecVersion::new(),
+		frame_system::CheckTxVersion::new(),
+		frame_system::CheckGenesis::new(),
+		frame_system::CheckEra::from(era),
+		frame_system::CheckNonce::from(nonce),
+		frame_system::CheckWeight::new(),
+		pallet_template::ChargeUnlessFeeless::from(
+			pallet_transaction_payment::ChargeTransactionPayment::from(0),
+			template_index,
+		),
+	)
+}
+
+fn sign_payload(signer: Sr25519Keyring, payload: SignedPayload) -> UncheckedExtrinsic {
+	let signature = payload.using_encoded(|payload| signer.sign(payload));
+	let (call, extra, _) = payload.deconstruct();
+	UncheckedExtrinsic::new_signed(call, signer.to_account_id().into(), signature.into(), extra)
+}
+
+/// An immortal extrinsic calling `call`, signed by `signer` with its current nonce and template
+/// call index.
+fn sign(signer: Sr25519Keyring, call: Call) -> UncheckedExtrinsic {
+	let account = signer.to_account_id();
+	let nonce = System::account_nonce(&account);
+	let template_index = pallet_template::CallCounters::<Runtime>::get(&account).index;
+	let extra = signed_extra(nonce, template_index, Era::Immortal);
+	let payload = SignedPayload::new(call, extra).unwrap();
+	sign_payload(signer, payload)
+}
+
+/// The payload of an immortal extrinsic signed for the chain with the given genesis hash.
+fn payload_for_genesis(call: Call, nonce: Index, genesis_hash: Hash) -> SignedPayload {
+	SignedPayload::from_raw(
+		call,
+		signed_extra(nonce, 0, Era::Immortal),
+		(
+			(),
+			VERSION.spec_version,
+			VERSION.transaction_version,
+			genesis_hash,
+			genesis_hash,
+			(),
+			(),
+			(),
+		),
+	)
+}
+
+fn remark(length: usize) -> Call {
+	Call::System(SystemCall::remark { remark: vec![0; length] })
+}
+
+fn invalid(error: InvalidTransaction) -> ApplyExtrinsicResult {
+	Err(TransactionValidityError::Invalid(error))
+}
+
+#[test]
+fn executes_empty_blocks() {
+	new_test_ext().execute_with(|| {
+		start_chain();
+		let first = next_block();
+		let second = next_block();
+
+		assert_eq!(first.number, 1);
+		assert_eq!(second.parent_hash, first.hash());
+		assert_eq!(System::block_number(), 3);
+		assert_eq!(Aura::current_slot(), Slot::from(3));
+		assert_eq!(Timestamp::now(), 3 * SLOT_DURATION);
+	});
+}
+
+#[test]
+fn rejects_extrinsics_born_in_unknown_blocks() {
+	new_test_ext().execute_with(|| {
+		start_chain();
+
+		// The era of a mortal extrinsic created at block 1000 starts at a block yet unknown.
+		let era = Era::mortal(64, 1000);
+		let payload = SignedPayload::from_raw(
+			remark(1),
+			signed_extra(0, 0, era),
+			(
+				(),
+				VERSION.spec_version,
+				VERSION.transaction_version,
+				Hash::default(),
+				H256::default(),
+				(),
+				(),
+				(),
+			),
+		);
+		let xt = sign_payload(Bob, payload);
+
+		assert_eq!(Executive::apply_extrinsic(xt), invalid(InvalidTransaction::AncientBirthBlock));
+	});
+}
+
+#[test]
+fn rejects_expired_extrinsics() {
+	new_test_ext().execute_with(|| {
+		start_chain();
+		next_block();
+
+		// Valid from block 1 to block 5.
+		let payload = SignedPayload::new(remark(1), signed_extra(0, 0, Era::mortal(4, 1))).unwrap();
+		let xt = sign_payload(Bob, payload);
+		for _ in 2..6 {
+			next_block();
+		}
+
+		// The era now starts at block 5, whose hash the signature does not cover.
+		assert_eq!(Executive::apply_extrinsic(xt), invalid(InvalidTransaction::BadProof));
+	});
+}
+
+#[test]
+fn rejects_extrinsics_signed_for_another_chain() {
+	new_test_ext().execute_with(|| {
+		start_chain();
+
+		let genesis_hash = System::block_hash(0);
+		let other_chain =
+			sign_payload(Bob, payload_for_genesis(remark(1), 0, H256::repeat_byte(1)));
+		let this_chain = sign_payload(Bob, payload_for_genesis(remark(1), 0, genesis_hash));
+
+		assert_eq!(Executive::apply_extrinsic(other_chain), invalid(InvalidTransaction::BadProof));
+		assert_ok!(Executive::apply_extrinsic(this_chain));
+	});
+}
+
+#[test]
+fn checks_nonces() {
+	new_test_ext().execute_with(|| {
+		start_chain();
+		let genesis_hash = System::block_hash(0);
+		let with_nonce =
+			|nonce| sign_payload(Bob, payload_for_genesis(remark(1), nonce, genesis_hash));
+
+		assert_eq!(Executive::apply_extrinsic(with_nonce(1)), invalid(InvalidTransaction::Future));
+		assert_eq!(Executive::apply_extrinsic(with_nonce(0)), Ok(Ok(())));
+		assert_eq!(Executive::apply_extrinsic(with_nonce(0)), invalid(InvalidTransaction::Stale));
+		assert_eq!(Executive::apply_extrinsic(with_nonce(1)), Ok(Ok(())));
+		assert_eq!(System::account_nonce(Bob.to_account_id()), 2);
+	});
+}
+
+#[test]
+fn rejects_extrinsics_that_cannot_pay_fees() {
+	new_test_ext().execute_with(|| {
+		start_chain();
+
+		assert_eq!(
+			Executive::apply_extrinsic(sign(One, remark(1))),
+			invalid(InvalidTransaction::Payment)
+		);
+	});
+}
+
+#[test]
+fn rejects_extrinsics_exceeding_the_extrinsic_limits() {
+	new_test_ext().execute_with(|| {
+		start_chain();
+
+		let weight = BlockWeights::get().max_block;
+		let heavy =
+			Call::Sudo(SudoCall::sudo_unchecked_weight { call: Box::new(remark(1)), weight });
+		assert_eq!(
+			Executive::apply_extrinsic(sign(Alice, heavy)),
+			invalid(InvalidTransaction::ExhaustsResources)
+		);
+
+		let max_length = *BlockLength::get().max.get(DispatchClass::Normal) as usize;
+		assert_eq!(
+			Executive::apply_extrinsic(sign(Bob, remark(max_length))),
+			invalid(InvalidTransaction::ExhaustsResources)
+		);
+	});
+}
+
+#[test]
+fn enforces_the_block_weight_limit() {
+	new_test_ext().execute_with(|| {
+		start_chain();
+
+		// Two of these exceed the share of the block available to normal extrinsics.
+		let weight = BlockWeights::get().max_block * 2 / 5;
+		let heavy =
+			|| Call::Sudo(SudoCall::sudo_unchecked_weight { call: Box::new(remark(1)), weight });
+
+		assert_eq!(Executive::apply_extrinsic(sign(Alice, heavy())), Ok(Ok(())));
+		assert_eq!(
+			Executive::apply_extrinsic(sign(Alice, heavy())),
+			invalid(InvalidTransaction::ExhaustsResources)
+		);
+
+		// The next block has room again.
+		next_block();
+		assert_eq!(Executive::apply_extrinsic(sign(Alice, heavy())), Ok(Ok(())));
+	});
+}
+
+#[test]
+fn enforces_the_block_length_limit() {
+	new_test_ext().execute_with(|| {
+		start_chain();
+
+		// Two of these exceed the share of the block available to normal extrinsics.
+		let length = *BlockLength::get().max.get(DispatchClass::Normal) as usize * 3 / 5;
+
+		assert_eq!(Executive::apply_extrinsic(sign(Bob, remark(length))), Ok(Ok(())));
+		assert_eq!(
+			Executive::apply_extrinsic(sign(Charlie, remark(length))),
+			invalid(InvalidTransaction::ExhaustsResources)
+		);
+	});
+}
+
+#[test]
+fn do_something_charges_the_predicted_fee() {
+	new_test_ext().execute_with(|| {
+		start_chain();
+		let bob = Bob.to_account_id();
+		// The first call of the day is free.
+		assert_eq!(Executive::apply_extrinsic(sign(Bob, do_something(6))), Ok(Ok(())));
+		let issuance = Balances::total_issuance();
+
+		let xt = sign(Bob, do_something(7));
+		let fee =
+			TransactionPayment::compute_fee(xt.encode().len() as u32, &xt.get_dispatch_info(), 0);
+		assert_eq!(Executive::apply_extrinsic(xt), Ok(Ok(())));
+
+		assert!(fee > 0);
+		assert_eq!(TemplateModule::something(), Some(7));
+		assert_eq!(Balances::free_balance(&bob), ENDOWMENT - fee);
+		// The runtime burns the fees.
+		assert_eq!(Balances::total_issuance(), issuance - fee);
+		System::assert_has_event(Event::TransactionPayment(
+			pallet_transaction_payment::Event::TransactionFeePaid {
+				who: bob,
+				actual_fee: fee,
+				tip: 0,
+			},
+		));
+	});
+}
+
+fn do_something(something: u32) -> Call {
+	Call::TemplateModule(pallet_template::Call::do_something { something })
+}
+
+#[test]
+fn template_calls_weigh_the_storage_of_their_signed_extensions() {
+	use pallet_template::WeightInfo as _;
+	type Weights = weights::pallet_template::WeightInfo<Runtime>;
+
+	// `CallCounters` and `FeelessUsages` read and written, and the balance of the signer read.
+	let extensions = RocksDbWeight::get().reads_writes(3, 2);
+	assert_eq!(do_something(1).get_dispatch_info().weight, Weights::do_something() + extensions);
+	let cause_error = Call::TemplateModule(pallet_template::Call::cause_error {});
+	assert_eq!(cause_error.get_dispatch_info().weight, Weights::cause_error() + extensions);
+}
+
+#[test]
+fn template_calls_of_the_sudo_key_come_first() {
+	new_test_ext().execute_with(|| {
+		start_chain();
+		let parent_hash = System::parent_hash();
+		let priority = |signer, call| {
+			let xt = sign(signer, call);
+			Executive::validate_transaction(TransactionSource::External, xt, parent_hash)
+				.unwrap()
+				.priority
+		};
+
+		// Alice holds the sudo key.
+		assert_eq!(priority(Alice, remark(1)), priority(Bob, remark(1)));
+		assert_eq!(
+			priority(Alice, do_something(1)),
+			priority(Bob, do_something(1)) + TemplatePriorityBoost::get()
+		);
+	});
+}
+
+#[test]
+fn rate_limits_template_calls() {
+	new_test_ext().execute_with(|| {
+		start_chain();
+		let limit = <MaxTemplateCallsPerWindow as frame_support::traits::Get<u32>>::get();
+
+		for something in 0..limit {
+			assert_eq!(Executive::apply_extrinsic(sign(Bob, do_something(something))), Ok(Ok(())));
+		}
+		assert_eq!(
+			Executive::apply_extrinsic(sign(Bob, do_something(limit))),
+			invalid(InvalidTransaction::Custom(pallet_template::extension::RATE_LIMITED))
+		);
+		// Other calls and other accounts are not limited, nor is the sudo key.
+		assert_eq!(Executive::apply_extrinsic(sign(Bob, remark(1))), Ok(Ok(())));
+		assert_eq!(Executive::apply_extrinsic(sign(Charlie, do_something(0))), Ok(Ok(())));
+		for something in 0..=limit {
+			let call = do_something(something);
+			assert_eq!(Executive::apply_extrinsic(sign(Alice, call)), Ok(Ok(())));
+		}
+
+		// The next window starts at block `MINUTES`.
+		while System::block_number() < MINUTES {
+			next_block();
+		}
+		assert_eq!(Executive::apply_extrinsic(sign(Bob, do_something(limit))), Ok(Ok(())));
+	});
+}
+
+#[test]
+fn first_do_something_of_the_day_is_free() {
+	new_test_ext().execute_with(|| {
+		start_chain();
+		let bob = Bob.to_account_id();
+		let parent_hash = System::parent_hash();
+
+		let free = sign(Bob, do_something(1));
+		assert!(is_feeless(&free));
+		// The pool admits it without priority, until the end of the day.
+		let validity =
+			Executive::validate_transaction(TransactionSource::External, free.clone(), parent_hash)
+				.unwrap();
+		assert_eq!(validity.priority, 0);
+		assert!(validity.longevity < DAYS as u64);
+		assert_eq!(Executive::apply_extrinsic(free), Ok(Ok(())));
+		assert_eq!(Balances::free_balance(&bob), ENDOWMENT);
+
+		// The next one of the day pays.
+		let paid = sign(Bob, do_something(2));
+		assert!(!is_feeless(&paid));
+		assert_eq!(Executive::apply_extrinsic(paid), Ok(Ok(())));
+		assert!(Balances::free_balance(&bob) < ENDOWMENT);
+
+		// Other calls pay, and so do accounts below the existential deposit.
+		assert!(!is_feeless(&sign(Charlie, remark(1))));
+		assert_eq!(
+			Executive::apply_extrinsic(sign(One, do_something(3))),
+			invalid(InvalidTransaction::Payment)
+		);
+
+		// The next day starts at block `DAYS`.
+		System::set_block_number(DAYS);
+		assert!(is_feeless(&sign(Bob, do_something(4))));
+	});
+}
+
+#[test]
+fn reaps_accounts_below_the_existential_deposit() {
+	new_test_ext().execute_with(|| {
+		start_chain();
+		let one = One.to_account_id();
+		let transfer = |value| {
+			Call::Balances(BalancesCall::transfer { dest: One.to_account_id().into(), value })
+		};
+
+		// Accounts are not created below the existential deposit.
+		assert_eq!(
+			Executive::apply_extrinsic(sign(Bob, transfer(EXISTENTIAL_DEPOSIT - 1))),
+			Ok(Err(pallet_balances::Error::<Runtime>::ExistentialDeposit.into()))
+		);
+		assert!(!System::account_exists(&one));
+
+		let value = ENDOWMENT / 4;
+		assert_eq!(Executive::apply_extrinsic(sign(Bob, transfer(value))), Ok(Ok(())));
+		assert!(System::account_exists(&one));
+
+		// Transferring everything left after the fee reaps the account.
+		let dest = Charlie.to_account_id().into();
+		let transfer_all = Call::Balances(BalancesCall::transfer_all { dest, keep_alive: false });
+		assert_eq!(Executive::apply_extrinsic(sign(One, transfer_all)), Ok(Ok(())));
+
+		assert!(!System::account_exists(&one));
+		assert_eq!(Balances::total_balance(&one), 0);
+		assert_eq!(System::account_nonce(&one), 0);
+		System::assert_has_event(Event::System(frame_system::Event::KilledAccount {
+			account: one,
+		}));
+	});
+}
+
+/// Executes `method` on a fresh genesis state, natively or in wasm, returning whether it ran
+/// natively and the storage root afterwards.
+fn execute_on_genesis(method: &str, data: &[u8], use_native: bool) -> (bool, H256) {
+	type NativeCall = fn() -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+
+	let mut ext = new_test_ext();
+	let mut ext = ext.ext();
+	let code = WASM_BINARY.expect("Only called with the wasm binary; qed");
+	let runtime_code = sp_core::traits::RuntimeCode {
+		code_fetcher: &sp_core::traits::WrappedRuntimeCode(code.into()),
+		hash: sp_core::blake2_256(code).to_vec(),
+		heap_pages: None,
+	};
+	let executor = sc_executor::NativeElseWasmExecutor::<ExecutorDispatch>::new(
+		sc_executor::WasmExecutionMethod::Interpreted,
+		None,
+		1,
+		2,
+	);
+
+	let (result, used_native) =
+		executor.call::<(), NativeCall>(&mut ext, &runtime_code, method, data, use_native, None);
+	assert!(result.is_ok(), "{} failed: {:?}", method, result.err());
+	(used_native, H256::from_slice(&ext.storage_root(StateVersion::V1)))
+}
+
+struct ExecutorDispatch;
+
+impl sc_executor::NativeExecutionDispatch for ExecutorDispatch {
+	type ExtendHostFunctions = ();
+
+	fn dispatch(method: &str, data: &[u8]) -> Option<Vec<u8>> {
+		api::dispatch(method, data)
+	}
+
+	fn native_version() -> sc_executor::NativeVersion {
+		native_version()
+	}
+}
+
+#[test]
+fn native_and_wasm_execution_agree() {
+	if WASM_BINARY.is_none() {
+		// Built with `SKIP_WASM_BUILD`.
+		return
+	}
+
+	let block = new_test_ext().execute_with(|| {
+		start_chain();
+		let extrinsics = [
+			sign(
+				Bob,
+				Call::Balances(BalancesCall::transfer {
+					dest: One.to_account_id().into(),
+					value: ENDOWMENT / 4,
+				}),
+			),
+			sign(
+				Alice,
+				Call::TemplateModule(pallet_template::Call::do_something { something: 42 }),
+			),
+			sign(Charlie, Call::TemplateModule(pallet_template::Call::cause_error {})),
+			sign(Charlie, remark(1024)),
+		];
+		for xt in &extrinsics {
+			assert!(Executive::apply_extrinsic(xt.clone()).is_ok());
+		}
+		let header = Executive::finalize_block();
+		let timestamp = Call::Timestamp(TimestampCall::set { now: SLOT_DURATION });
+		let mut all = vec![UncheckedExtrinsic::new_unsigned(timestamp)];
+		all.extend(extrinsics);
+		Block { header, extrinsics: all }
+	});
+
+	let (native, native_root) = execute_on_genesis("Core_execute_block", &block.encode(), true);
+	let (wasm, wasm_root) = execute_on_genesis("Core_execute_block", &block.encode(), false);
+
+	assert!(native);
+	assert!(!wasm);
+	assert_eq!(native_root, wasm_root);
+	assert_eq!(native_root, block.header.state_root);
+}
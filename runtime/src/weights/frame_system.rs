@@ -0,0 +1,54 @@
+//! Placeholder weights for `frame_system`, written by hand in the layout of
+//! `.maintain/frame-weight-template.hbs`.
+//!
+//! NOT MEASURED: the values are Substrate's reference measurements of the pallet, not benchmark
+//! results of this runtime. Running `node-template benchmark all --chain dev` on the reference
+//! hardware overwrites this file.
+
+#![cfg_attr(rustfmt, rustfmt_skip)]
+#![allow(unused_parens)]
+#![allow(unused_imports)]
+
+use frame_support::{traits::Get, weights::Weight};
+use sp_std::marker::PhantomData;
+
+/// Weight functions for `frame_system`.
+pub struct WeightInfo<T>(PhantomData<T>);
+impl<T: frame_system::Config> frame_system::WeightInfo for WeightInfo<T> {
+	fn remark(_b: u32, ) -> Weight {
+		(0 as Weight)
+	}
+	fn remark_with_event(b: u32, ) -> Weight {
+		(0 as Weight)
+			// Standard Error: 0
+			.saturating_add((1_000 as Weight).saturating_mul(b as Weight))
+	}
+	// Storage: System Digest (r:1 w:1)
+	// Storage: unknown [0x3a686561707061676573] (r:0 w:1)
+	fn set_heap_pages() -> Weight {
+		(2_426_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(1 as Weight))
+			.saturating_add(T::DbWeight::get().writes(2 as Weight))
+	}
+	// Storage: Skipped Metadata (r:0 w:0)
+	fn set_storage(i: u32, ) -> Weight {
+		(0 as Weight)
+			// Standard Error: 1_000
+			.saturating_add((377_000 as Weight).saturating_mul(i as Weight))
+			.saturating_add(T::DbWeight::get().writes((1 as Weight).saturating_mul(i as Weight)))
+	}
+	// Storage: Skipped Metadata (r:0 w:0)
+	fn kill_storage(i: u32, ) -> Weight {
+		(0 as Weight)
+			// Standard Error: 1_000
+			.saturating_add((369_000 as Weight).saturating_mul(i as Weight))
+			.saturating_add(T::DbWeight::get().writes((1 as Weight).saturating_mul(i as Weight)))
+	}
+	// Storage: Skipped Metadata (r:0 w:0)
+	fn kill_prefix(p: u32, ) -> Weight {
+		(0 as Weight)
+			// Standard Error: 1_000
+			.saturating_add((807_000 as Weight).saturating_mul(p as Weight))
+			.saturating_add(T::DbWeight::get().writes((1 as Weight).saturating_mul(p as Weight)))
+	}
+}
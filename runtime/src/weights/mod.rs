@@ -0,0 +1,25 @@
+//! The weights of the runtime's pallets, measured by `node-template benchmark all`.
+//!
+//! The files of this module are hand-written placeholders until the benchmarks are run on the
+//! reference hardware; each says where its values come from.
+//!
+//! `benchmark all` runs the benchmarks of each pallet listed here and rewrites its file from
+//! `.maintain/frame-weight-template.hbs`, each defining a `WeightInfo<T>` that implements the
+//! pallet's `WeightInfo` trait. The benchmarks of GRANDPA measure the checks behind its calls
+//! rather than the calls themselves, so its file is written from
+//! `.maintain/frame-weight-functions-template.hbs` instead, as plain functions that [`grandpa`]
+//! builds the weights of its calls on.
+//!
+//! The template pallet adds the storage its signed extensions access, `CallCounters`,
+//! `FeelessUsages` and the balance the feeless policy reads, to the weights of [`pallet_template`],
+//! see `pallet_template::extensions_weight`.
+//!
+//! The other pallets have no weights of their own to measure: Aura, randomness, sudo and
+//! transaction payment have no `WeightInfo`.
+
+pub mod frame_system;
+pub mod grandpa;
+pub mod pallet_balances;
+pub mod pallet_grandpa;
+pub mod pallet_template;
+pub mod pallet_timestamp;
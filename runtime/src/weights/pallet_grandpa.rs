@@ -0,0 +1,26 @@
+//! Placeholder weights for `pallet_grandpa`, written by hand in the layout of
+//! `.maintain/frame-weight-functions-template.hbs`.
+//!
+//! NOT MEASURED: the values are taken from the default weights of the pallet, not benchmark results
+//! of this runtime. Running `node-template benchmark all --chain dev` on the reference hardware
+//! overwrites this file.
+
+#![cfg_attr(rustfmt, rustfmt_skip)]
+#![allow(unused_parens)]
+#![allow(unused_imports)]
+
+use frame_support::{traits::Get, weights::Weight};
+use sp_std::marker::PhantomData;
+
+/// Weight functions measured by the benchmarks of `pallet_grandpa`.
+pub struct WeightInfo<T>(PhantomData<T>);
+impl<T: frame_system::Config> WeightInfo<T> {
+	pub fn check_equivocation_proof(_x: u32, ) -> Weight {
+		(95_000_000 as Weight)
+	}
+	// Storage: Grandpa Stalled (r:0 w:1)
+	pub fn note_stalled() -> Weight {
+		(3_000_000 as Weight)
+			.saturating_add(T::DbWeight::get().writes(1 as Weight))
+	}
+}
@@ -0,0 +1,29 @@
+//! Placeholder weights for `pallet_template`, written by hand in the layout of
+//! `.maintain/frame-weight-template.hbs`.
+//!
+//! NOT MEASURED: the values are the default weights of the pallet, not benchmark results of this
+//! runtime. Running `node-template benchmark all --chain dev` on the reference hardware overwrites
+//! this file.
+
+#![cfg_attr(rustfmt, rustfmt_skip)]
+#![allow(unused_parens)]
+#![allow(unused_imports)]
+
+use frame_support::{traits::Get, weights::Weight};
+use sp_std::marker::PhantomData;
+
+/// Weight functions for `pallet_template`.
+pub struct WeightInfo<T>(PhantomData<T>);
+impl<T: frame_system::Config> pallet_template::WeightInfo for WeightInfo<T> {
+	// Storage: TemplateModule Something (r:0 w:1)
+	fn do_something() -> Weight {
+		(10_000 as Weight)
+			.saturating_add(T::DbWeight::get().writes(1 as Weight))
+	}
+	// Storage: TemplateModule Something (r:1 w:1)
+	fn cause_error() -> Weight {
+		(10_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(1 as Weight))
+			.saturating_add(T::DbWeight::get().writes(1 as Weight))
+	}
+}
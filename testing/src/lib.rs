@@ -0,0 +1,250 @@
+//! An in-process test network of node-template nodes.
+//!
+//! Every node runs the full service of `node-template` on the local testnet, in its own temporary
+//! base path, inside the tokio runtime of the test. The nodes talk over libp2p's in-memory
+//! transport, so the network needs neither open ports nor connectivity.
+//!
+//! ```ignore
+//! #[tokio::test(flavor = "multi_thread")]
+//! async fn produces_blocks() {
+//! 	let network = TestNetwork::start(&[Sr25519Keyring::Alice, Sr25519Keyring::Bob], 0);
+//! 	network.wait_for("block #3", BLOCK_TIMEOUT, |node| node.best_number() >= 3).await;
+//! }
+//! ```
+
+#![warn(missing_docs)]
+
+use codec::Decode;
+use node_template::{
+	cli::Cli,
+	extrinsic::{self, ExtrinsicParams},
+	service::{self, FullClient, FullPool},
+};
+use node_template_runtime::{
+	self as runtime,
+	opaque::Block,
+	pallet_template::{self, CallCounter},
+	AccountId, Balance, BlockNumber, Hash, Index,
+};
+use sc_cli::SubstrateCli;
+use sc_client_api::StorageProvider;
+use sc_network::{
+	config::{MultiaddrWithPeerId, TransportConfig},
+	multiaddr::Protocol,
+	Multiaddr, NetworkService,
+};
+use sc_service::TaskManager;
+use sc_transaction_pool_api::{TransactionPool, TransactionSource};
+use sp_blockchain::HeaderBackend;
+use sp_core::storage::StorageKey;
+use sp_keyring::Sr25519Keyring;
+use sp_runtime::generic::BlockId;
+use std::{
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Arc,
+	},
+	time::{Duration, Instant},
+};
+use tempfile::TempDir;
+
+/// How long to wait for a few blocks to be produced, with the runtime's six second slots.
+pub const BLOCK_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long to wait for a few blocks to be finalized.
+pub const FINALITY_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// The next in-memory port to listen on, unique across the nodes of the test process.
+static NEXT_PORT: AtomicU64 = AtomicU64::new(1);
+
+/// The account information stored by `frame_system`.
+pub type AccountInfo = frame_system::AccountInfo<Index, pallet_balances::AccountData<Balance>>;
+
+/// A running node of the test network.
+pub struct TestNode {
+	/// The name of the node.
+	pub name: String,
+	/// The client of the node.
+	pub client: Arc<FullClient>,
+	/// The network service of the node.
+	pub network: Arc<NetworkService<Block, Hash>>,
+	/// The transaction pool of the node.
+	pub transaction_pool: Arc<FullPool>,
+	/// The in-memory address the node listens on.
+	pub address: MultiaddrWithPeerId,
+	// Dropped before the base path, stopping the node before its database is removed.
+	_task_manager: TaskManager,
+	_base_path: TempDir,
+}
+
+impl TestNode {
+	/// Starts a node of the local testnet, an authority if `key` is given, connecting to
+	/// `boot_nodes`. Must be called from within a multi-threaded tokio runtime.
+	pub fn start(
+		name: &str,
+		key: Option<Sr25519Keyring>,
+		boot_nodes: Vec<MultiaddrWithPeerId>,
+	) -> Self {
+		let base_path = tempfile::tempdir().expect("Creates a temporary directory; qed");
+		let mut args = vec![
+			"node-template".to_string(),
+			"--chain=local".into(),
+			format!("--base-path={}", base_path.path().display()),
+			format!("--name={}", name),
+			"--no-prometheus".into(),
+			"--no-telemetry".into(),
+			"--no-hardware-benchmarks".into(),
+		];
+		if let Some(key) = key {
+			args.push(format!("--{}", key.to_string().to_lowercase()));
+		}
+		let cli = Cli::from_iter(args);
+		let mut config = cli
+			.create_configuration(&cli.run.base, tokio::runtime::Handle::current())
+			.expect("The test configuration is valid; qed");
+
+		let port = NEXT_PORT.fetch_add(1, Ordering::Relaxed);
+		let listen_address: Multiaddr = Protocol::Memory(port).into();
+		config.network.transport = TransportConfig::MemoryOnly;
+		config.network.listen_addresses = vec![listen_address.clone()];
+		config.network.boot_nodes = boot_nodes;
+		config.network.allow_non_globals_in_dht = true;
+		config.rpc_http = None;
+		config.rpc_ws = None;
+		config.rpc_ipc = None;
+
+		let service::NewFull { task_manager, client, network, transaction_pool } =
+			service::new_full_base(config, &cli.run).expect("The test node starts; qed");
+		let address =
+			MultiaddrWithPeerId { multiaddr: listen_address, peer_id: *network.local_peer_id() };
+
+		Self {
+			name: name.into(),
+			client,
+			network,
+			transaction_pool,
+			address,
+			_task_manager: task_manager,
+			_base_path: base_path,
+		}
+	}
+
+	/// The number of the best block.
+	pub fn best_number(&self) -> BlockNumber {
+		self.client.info().best_number
+	}
+
+	/// The number of the last finalized block.
+	pub fn finalized_number(&self) -> BlockNumber {
+		self.client.info().finalized_number
+	}
+
+	/// The decoded value at `key` in the state of the best block.
+	pub fn storage<T: Decode>(&self, key: Vec<u8>) -> Option<T> {
+		let at = BlockId::Hash(self.client.info().best_hash);
+		self.client
+			.storage(&at, &StorageKey(key))
+			.expect("The state of the best block is available; qed")
+			.map(|value| T::decode(&mut &value.0[..]).expect("The stored value decodes; qed"))
+	}
+
+	/// The account information of `account` at the best block.
+	pub fn account(&self, account: &AccountId) -> AccountInfo {
+		self.storage(frame_system::Account::<runtime::Runtime>::hashed_key_for(account))
+			.unwrap_or_default()
+	}
+
+	/// The template pallet calls `account` made, as of the best block.
+	pub fn template_calls(&self, account: &AccountId) -> CallCounter<BlockNumber> {
+		let key = pallet_template::CallCounters::<runtime::Runtime>::hashed_key_for(account);
+		self.storage(key).unwrap_or_default()
+	}
+
+	/// The value of `TemplateModule::Something` at the best block.
+	pub fn something(&self) -> Option<u32> {
+		let key = runtime::pallet_template::Something::<runtime::Runtime>::hashed_key();
+		self.storage(key.to_vec())
+	}
+
+	/// Signs `call` by `signer`, with its next nonce and template call index at the best block.
+	pub fn sign(&self, signer: Sr25519Keyring, call: runtime::Call) -> runtime::UncheckedExtrinsic {
+		self.sign_after(signer, call, 0)
+	}
+
+	/// Signs `call` by `signer` to follow `pending` template calls of `signer` yet to be included,
+	/// skipping as many nonces and template call indices past those at the best block.
+	pub fn sign_after(
+		&self,
+		signer: Sr25519Keyring,
+		call: runtime::Call,
+		pending: u32,
+	) -> runtime::UncheckedExtrinsic {
+		let info = self.client.info();
+		let account = signer.to_account_id();
+		let mut params = ExtrinsicParams::immortal(
+			info.genesis_hash,
+			runtime::VERSION.spec_version,
+			runtime::VERSION.transaction_version,
+			self.account(&account).nonce + pending,
+		);
+		params.template_index = self.template_calls(&account).index + pending;
+		extrinsic::create_signed(&signer.pair(), call, &params)
+	}
+
+	/// Submits `extrinsic` to the transaction pool of the node.
+	pub async fn submit(&self, extrinsic: runtime::UncheckedExtrinsic) -> Hash {
+		let at = BlockId::Hash(self.client.info().best_hash);
+		self.transaction_pool
+			.submit_one(&at, TransactionSource::External, extrinsic.into())
+			.await
+			.unwrap_or_else(|e| panic!("{} rejected the transaction: {}", self.name, e))
+	}
+}
+
+/// A network of test nodes, stopped when dropped.
+pub struct TestNetwork {
+	/// The nodes, authorities first.
+	pub nodes: Vec<TestNode>,
+}
+
+impl TestNetwork {
+	/// Starts an authority node for each of `authorities`, which must be authorities of the
+	/// local testnet to author blocks, followed by `full_nodes` nodes that do not author. Every
+	/// node connects to the first one.
+	pub fn start(authorities: &[Sr25519Keyring], full_nodes: usize) -> Self {
+		let mut nodes: Vec<TestNode> = Vec::new();
+		let keys = authorities.iter().map(|key| Some(*key)).chain((0..full_nodes).map(|_| None));
+		for (index, key) in keys.enumerate() {
+			let name = key.map_or_else(|| format!("full-{}", index), |key| key.to_string());
+			let boot_nodes = nodes.first().map(|node| node.address.clone()).into_iter().collect();
+			nodes.push(TestNode::start(&name, key, boot_nodes));
+		}
+		Self { nodes }
+	}
+
+	/// The node at `index`.
+	pub fn node(&self, index: usize) -> &TestNode {
+		&self.nodes[index]
+	}
+
+	/// Waits until `condition` holds for every node, panicking after `timeout`.
+	pub async fn wait_for(
+		&self,
+		what: &str,
+		timeout: Duration,
+		condition: impl Fn(&TestNode) -> bool,
+	) {
+		let start = Instant::now();
+		loop {
+			let pending: Vec<_> =
+				self.nodes.iter().filter(|node| !condition(node)).map(|node| &node.name).collect();
+			if pending.is_empty() {
+				return
+			}
+			if start.elapsed() > timeout {
+				panic!("Timed out after {:?} waiting for {} on {:?}", timeout, what, pending);
+			}
+			tokio::time::sleep(Duration::from_millis(200)).await;
+		}
+	}
+}
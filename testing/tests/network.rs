@@ -0,0 +1,90 @@
+use node_template_runtime::{self as runtime, EXISTENTIAL_DEPOSIT};
+use node_template_testing::{TestNetwork, BLOCK_TIMEOUT, FINALITY_TIMEOUT};
+use sp_blockchain::HeaderBackend;
+use sp_keyring::Sr25519Keyring::{Alice, Bob, Charlie, One};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn authorities_produce_blocks_imported_by_all_nodes() {
+	let network = TestNetwork::start(&[Alice, Bob], 1);
+
+	network.wait_for("block #3", BLOCK_TIMEOUT, |node| node.best_number() >= 3).await;
+
+	let hash = |index: usize| network.node(index).client.hash(2).unwrap();
+	assert!(hash(0).is_some());
+	assert_eq!(hash(0), hash(1));
+	assert_eq!(hash(0), hash(2));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn grandpa_finalizes_blocks() {
+	let network = TestNetwork::start(&[Alice, Bob], 1);
+
+	network
+		.wait_for("finalized block #2", FINALITY_TIMEOUT, |node| node.finalized_number() >= 2)
+		.await;
+
+	let finalized = |index: usize| network.node(index).client.hash(2).unwrap();
+	assert_eq!(finalized(0), finalized(1));
+	assert_eq!(finalized(0), finalized(2));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn transactions_propagate_from_full_nodes_to_authorities() {
+	let network = TestNetwork::start(&[Alice, Bob], 1);
+	network.wait_for("block #1", BLOCK_TIMEOUT, |node| node.best_number() >= 1).await;
+	let full_node = network.node(2);
+
+	// Only the authorities author blocks, so the transfer is only included if it propagates.
+	let value = 100 * EXISTENTIAL_DEPOSIT;
+	let dest = One.to_account_id().into();
+	let call = runtime::Call::Balances(runtime::BalancesCall::transfer { dest, value });
+	full_node.submit(full_node.sign(Charlie, call)).await;
+
+	network
+		.wait_for("the transfer", BLOCK_TIMEOUT, |node| {
+			node.account(&One.to_account_id()).data.free == value
+		})
+		.await;
+	assert_eq!(full_node.account(&Charlie.to_account_id()).nonce, 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn template_module_state_is_consistent_across_nodes() {
+	let network = TestNetwork::start(&[Alice, Bob], 1);
+	network.wait_for("block #1", BLOCK_TIMEOUT, |node| node.best_number() >= 1).await;
+	let alice = network.node(0);
+	assert_eq!(alice.something(), None);
+
+	let call = runtime::Call::TemplateModule(runtime::pallet_template::Call::do_something {
+		something: 42,
+	});
+	alice.submit(alice.sign(Alice, call)).await;
+
+	network.wait_for("Something = 42", BLOCK_TIMEOUT, |node| node.something() == Some(42)).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pool_admits_consecutive_do_something_calls() {
+	let network = TestNetwork::start(&[Alice, Bob], 0);
+	network.wait_for("block #1", BLOCK_TIMEOUT, |node| node.best_number() >= 1).await;
+	let alice = network.node(0);
+	let charlie = Charlie.to_account_id();
+	let balance = alice.account(&charlie).data.free;
+	let do_something = |something| {
+		runtime::Call::TemplateModule(runtime::pallet_template::Call::do_something { something })
+	};
+
+	// The first call of the day is free, the one queued behind it pays, and the pool takes both.
+	let first = alice.sign(Charlie, do_something(1));
+	let second = alice.sign_after(Charlie, do_something(2), 1);
+	alice.submit(first).await;
+	alice.submit(second).await;
+
+	network
+		.wait_for("both calls", BLOCK_TIMEOUT, |node| node.template_calls(&charlie).index == 2)
+		.await;
+	assert_eq!(alice.something(), Some(2));
+	let account = alice.account(&charlie);
+	assert_eq!(account.nonce, 2);
+	assert!(account.data.free < balance);
+}